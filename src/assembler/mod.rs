@@ -0,0 +1,704 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::cpu::{AddressingMode, CpuVariant, OpCodeDef};
+use super::disasm::ca65_mnemonic;
+
+// Where programs go unless they say otherwise, the same place Cpu::load puts them.
+pub const DEFAULT_ORIGIN: u16 = 0x0600;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AsmError {
+	Syntax { line: usize, text: String },
+	UnknownMnemonic { line: usize, mnemonic: String },
+	UnsupportedMode { line: usize, mnemonic: String },
+	UndefinedLabel { line: usize, label: String },
+	DuplicateLabel { line: usize, label: String },
+	ValueOutOfRange { line: usize, value: i32 },
+	BranchOutOfRange { line: usize, target: u16 },
+	BackwardOrg { line: usize, address: u16 },
+}
+
+impl fmt::Display for AsmError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			AsmError::Syntax { line, text } => {
+				write!(f, "line {}: cannot parse \"{}\"", line, text)
+			}
+			AsmError::UnknownMnemonic { line, mnemonic } => {
+				write!(f, "line {}: unknown mnemonic {}", line, mnemonic)
+			}
+			AsmError::UnsupportedMode { line, mnemonic } => {
+				write!(f, "line {}: addressing mode not available for {}", line, mnemonic)
+			}
+			AsmError::UndefinedLabel { line, label } => {
+				write!(f, "line {}: undefined label {}", line, label)
+			}
+			AsmError::DuplicateLabel { line, label } => {
+				write!(f, "line {}: label {} defined more than once", line, label)
+			}
+			AsmError::ValueOutOfRange { line, value } => {
+				write!(f, "line {}: value {} out of range", line, value)
+			}
+			AsmError::BranchOutOfRange { line, target } => {
+				write!(f, "line {}: branch target ${:04X} out of range", line, target)
+			}
+			AsmError::BackwardOrg { line, address } => {
+				write!(f, "line {}: .org ${:04X} moves backwards", line, address)
+			}
+		}
+	}
+}
+
+impl std::error::Error for AsmError {}
+
+// The assembled bytes start at origin. Gaps left by .org are filled with zeroes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Program {
+	pub origin: u16,
+	pub bytes: Vec<u8>,
+	pub labels: BTreeMap<String, u16>,
+}
+
+// Assembles ca65 style source for the NES CPU, starting at DEFAULT_ORIGIN.
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+	Assembler::new(CpuVariant::Ricoh2A03).assemble(source)
+}
+
+// Assembles source for the tests, panicking with the error instead of returning it.
+#[macro_export]
+macro_rules! assemble {
+	( $( $line:expr ),* $(,)? ) => {
+		match $crate::assembler::assemble(&[$($line),*].join("\n")) {
+			Ok(program) => program.bytes,
+			Err(err) => panic!("{}", err),
+		}
+	};
+}
+
+pub struct Assembler {
+	pub variant: CpuVariant,
+	pub origin: u16,
+}
+
+// How the operand was written, before knowing which opcode it picks.
+#[derive(Clone, Debug)]
+enum Operand {
+	None,
+	Accumulator,
+	Immediate(String),
+	Direct { expr: String, index: Option<char>, force: Option<char> },
+	Indirect(String),
+	IndirectX(String),
+	IndirectY(String),
+	Pair(String, String),
+}
+
+#[derive(Clone, Debug)]
+enum Item {
+	Expr(String),
+	Text(String),
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+	Label(String),
+	Constant(String, String),
+	Org(String),
+	Byte(Vec<Item>),
+	Word(Vec<String>),
+	Instruction { mnemonic: String, operand: Operand },
+}
+
+impl Assembler {
+	pub fn new(variant: CpuVariant) -> Self {
+		Assembler { variant, origin: DEFAULT_ORIGIN }
+	}
+
+	// Two passes: the first sizes every statement, with forward references taken to be
+	// absolute addresses as ca65 does, the second emits bytes with all labels known.
+	pub fn assemble(&self, source: &str) -> Result<Program, AsmError> {
+		let mut statements = Vec::new();
+		for (number, text) in source.lines().enumerate() {
+			for statement in parse_line(text, number + 1)? {
+				statements.push((number + 1, statement));
+			}
+		}
+
+		let mut labels = BTreeMap::new();
+		let mut deferred = Vec::new();
+		let mut choices = Vec::new();
+		let mut pc = self.origin;
+		for (line, statement) in statements.iter() {
+			let line = *line;
+			match statement {
+				Statement::Label(name) => {
+					if labels.insert(name.clone(), pc).is_some() {
+						return Err(AsmError::DuplicateLabel { line, label: name.clone() });
+					}
+				}
+				Statement::Constant(name, expr) => {
+					match eval(expr, &labels, pc, line)? {
+						Some(value) => {
+							if labels.insert(name.clone(), value).is_some() {
+								return Err(AsmError::DuplicateLabel { line, label: name.clone() });
+							}
+						}
+						None => deferred.push((line, name, expr, pc)),
+					}
+				}
+				Statement::Org(expr) => {
+					let address = require(eval(expr, &labels, pc, line)?, expr, line)?;
+					if address < pc {
+						return Err(AsmError::BackwardOrg { line, address });
+					}
+					pc = address;
+				}
+				Statement::Byte(items) => {
+					for item in items {
+						pc = pc.wrapping_add(match item {
+							Item::Expr(_) => 1,
+							Item::Text(text) => text.len() as u16,
+						});
+					}
+				}
+				Statement::Word(items) => {
+					pc = pc.wrapping_add(2 * items.len() as u16);
+				}
+				Statement::Instruction { mnemonic, operand } => {
+					let def = self.select(mnemonic, operand, &labels, pc, line)?;
+					pc = pc.wrapping_add(def.len as u16);
+					choices.push(def);
+				}
+			}
+		}
+
+		// Constants defined in terms of later labels resolve once every label is known.
+		while !deferred.is_empty() {
+			let before = deferred.len();
+			let mut pending = Vec::new();
+			for (line, name, expr, pc) in deferred {
+				match eval(expr, &labels, pc, line)? {
+					Some(value) => {
+						labels.insert(name.clone(), value);
+					}
+					None => pending.push((line, name, expr, pc)),
+				}
+			}
+			if pending.len() == before {
+				let (line, _, expr, pc) = pending[0];
+				require(eval(expr, &labels, pc, line)?, expr, line)?;
+			}
+			deferred = pending;
+		}
+
+		let mut bytes = Vec::new();
+		let mut choices = choices.into_iter();
+		let mut pc = self.origin;
+		for (line, statement) in statements.iter() {
+			let line = *line;
+			let mut emit = |pc: &mut u16, byte: u8| {
+				bytes.push(byte);
+				*pc = pc.wrapping_add(1);
+			};
+			match statement {
+				Statement::Label(_) | Statement::Constant(..) => {}
+				Statement::Org(expr) => {
+					let address = require(eval(expr, &labels, pc, line)?, expr, line)?;
+					while pc != address {
+						emit(&mut pc, 0);
+					}
+				}
+				Statement::Byte(items) => {
+					for item in items {
+						match item {
+							Item::Expr(expr) => {
+								let value = require(eval(expr, &labels, pc, line)?, expr, line)?;
+								emit(&mut pc, byte(value, line)?);
+							}
+							Item::Text(text) => {
+								for b in text.bytes() {
+									emit(&mut pc, b);
+								}
+							}
+						}
+					}
+				}
+				Statement::Word(items) => {
+					for expr in items {
+						let value = require(eval(expr, &labels, pc, line)?, expr, line)?;
+						emit(&mut pc, value as u8);
+						emit(&mut pc, (value >> 8) as u8);
+					}
+				}
+				Statement::Instruction { operand, .. } => {
+					let def = choices.next().unwrap();
+					let address = pc;
+					emit(&mut pc, def.code);
+					for b in encode(def, operand, &labels, address, line)? {
+						emit(&mut pc, b);
+					}
+				}
+			}
+		}
+
+		Ok(Program { origin: self.origin, bytes, labels })
+	}
+
+	// Picks the opcode for a mnemonic and operand. Official opcodes win over the
+	// unofficial duplicates, otherwise the lowest opcode does.
+	fn select(&self, mnemonic: &str, operand: &Operand, labels: &BTreeMap<String, u16>, pc: u16, line: usize) -> Result<&'static OpCodeDef, AsmError> {
+		let table = self.variant.opcodes();
+		let candidates: Vec<&'static OpCodeDef> = table
+			.iter()
+			.filter(|def| def.name == mnemonic || ca65_mnemonic(def.name) == mnemonic)
+			.collect();
+		if candidates.is_empty() {
+			return Err(AsmError::UnknownMnemonic { line, mnemonic: mnemonic.to_string() });
+		}
+		let has = |mode: AddressingMode| candidates.iter().any(|def| def.mode == mode);
+		let zero_page = |expr: &str, force: Option<char>| -> Result<bool, AsmError> {
+			Ok(match force {
+				Some('z') => true,
+				Some(_) => false,
+				None => matches!(eval(expr, labels, pc, line)?, Some(value) if value < 0x100),
+			})
+		};
+
+		let modes: Vec<AddressingMode> = match operand {
+			Operand::None => vec![AddressingMode::Implied, AddressingMode::Accumulator],
+			Operand::Accumulator => vec![AddressingMode::Accumulator],
+			Operand::Immediate(_) => vec![AddressingMode::Immediate],
+			Operand::Direct { expr, index, force } => {
+				let zp = zero_page(expr, *force)?;
+				match index {
+					None if has(AddressingMode::Relative) => vec![AddressingMode::Relative],
+					None if zp => vec![AddressingMode::ZeroPage, AddressingMode::Absolute],
+					None => vec![AddressingMode::Absolute],
+					Some('X') if zp => vec![AddressingMode::ZeroPageX, AddressingMode::AbsoluteX],
+					Some('X') => vec![AddressingMode::AbsoluteX],
+					Some(_) if zp => vec![AddressingMode::ZeroPageY, AddressingMode::AbsoluteY],
+					Some(_) => vec![AddressingMode::AbsoluteY],
+				}
+			}
+			Operand::Indirect(expr) => {
+				match zero_page(expr, None)? {
+					true => vec![AddressingMode::ZeroPageIndirect, AddressingMode::Indirect],
+					false => vec![AddressingMode::Indirect],
+				}
+			}
+			Operand::IndirectX(_) => vec![AddressingMode::IndirectX, AddressingMode::AbsoluteIndirectX],
+			Operand::IndirectY(_) => vec![AddressingMode::IndirectY],
+			Operand::Pair(..) => vec![AddressingMode::ZeroPageRelative],
+		};
+
+		for mode in modes {
+			let mut matching = candidates.iter().filter(|def| def.mode == mode);
+			let first = matching.clone().next();
+			if let Some(def) = matching.find(|def| def.official).or(first) {
+				return Ok(def);
+			}
+		}
+		Err(AsmError::UnsupportedMode { line, mnemonic: mnemonic.to_string() })
+	}
+}
+
+fn encode(def: &OpCodeDef, operand: &Operand, labels: &BTreeMap<String, u16>, pc: u16, line: usize) -> Result<Vec<u8>, AsmError> {
+	let value = |expr: &String| require(eval(expr, labels, pc, line)?, expr, line);
+	let next = pc.wrapping_add(def.len as u16);
+	let offset = |target: u16| -> Result<u8, AsmError> {
+		let distance = target.wrapping_sub(next) as i16;
+		if !(-128..=127).contains(&distance) {
+			return Err(AsmError::BranchOutOfRange { line, target });
+		}
+		Ok(distance as u8)
+	};
+
+	let expr = match operand {
+		Operand::None | Operand::Accumulator => return Ok(vec![]),
+		Operand::Pair(zp, target) => return Ok(vec![byte(value(zp)?, line)?, offset(value(target)?)?]),
+		Operand::Immediate(expr)
+		| Operand::Indirect(expr)
+		| Operand::IndirectX(expr)
+		| Operand::IndirectY(expr)
+		| Operand::Direct { expr, .. } => expr,
+	};
+	let value = value(expr)?;
+	match def.mode {
+		AddressingMode::Relative => Ok(vec![offset(value)?]),
+		_ if def.len == 2 => Ok(vec![byte(value, line)?]),
+		_ => Ok(vec![value as u8, (value >> 8) as u8]),
+	}
+}
+
+fn byte(value: u16, line: usize) -> Result<u8, AsmError> {
+	match value {
+		0..=0xFF => Ok(value as u8),
+		_ => Err(AsmError::ValueOutOfRange { line, value: value as i32 }),
+	}
+}
+
+fn require(value: Option<u16>, expr: &str, line: usize) -> Result<u16, AsmError> {
+	value.ok_or_else(|| {
+		let label = expr
+			.split(|c: char| !is_symbol_char(c))
+			.find(|part| part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+			.unwrap_or(expr);
+		AsmError::UndefinedLabel { line, label: label.to_string() }
+	})
+}
+
+fn is_symbol_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Evaluates sums and differences of numbers, labels and * for the current address.
+// A leading < or > takes the low or high byte. Unknown labels give None.
+fn eval(expr: &str, labels: &BTreeMap<String, u16>, pc: u16, line: usize) -> Result<Option<u16>, AsmError> {
+	let syntax = || AsmError::Syntax { line, text: expr.to_string() };
+	let expr = expr.trim();
+	let (selector, rest) = match expr.chars().next() {
+		Some(c @ ('<' | '>')) => (Some(c), expr[1..].trim_start()),
+		_ => (None, expr),
+	};
+
+	let mut total: i32 = 0;
+	let mut known = true;
+	let mut sign = 1;
+	let mut chars = rest.chars().peekable();
+	loop {
+		while chars.peek() == Some(&' ') {
+			chars.next();
+		}
+		let term: String = match chars.peek() {
+			Some('*') => {
+				chars.next();
+				"*".to_string()
+			}
+			Some('\'') => {
+				chars.next();
+				let c = chars.next().ok_or_else(syntax)?;
+				if chars.next() != Some('\'') {
+					return Err(syntax());
+				}
+				total += sign * c as i32;
+				String::new()
+			}
+			_ => {
+				let mut term = String::new();
+				while let Some(&c) = chars.peek() {
+					if is_symbol_char(c) || c == '$' || c == '%' {
+						term.push(c);
+						chars.next();
+					} else {
+						break;
+					}
+				}
+				if term.is_empty() {
+					return Err(syntax());
+				}
+				term
+			}
+		};
+
+		let value = if term.is_empty() {
+			Some(0)
+		} else if term == "*" {
+			Some(pc as i32)
+		} else if let Some(hex) = term.strip_prefix('$') {
+			Some(i32::from_str_radix(hex, 16).map_err(|_| syntax())?)
+		} else if let Some(bin) = term.strip_prefix('%') {
+			Some(i32::from_str_radix(bin, 2).map_err(|_| syntax())?)
+		} else if term.starts_with(|c: char| c.is_ascii_digit()) {
+			Some(term.parse::<i32>().map_err(|_| syntax())?)
+		} else {
+			labels.get(&term).map(|&value| value as i32)
+		};
+		match value {
+			Some(value) => total += sign * value,
+			None => known = false,
+		}
+
+		while chars.peek() == Some(&' ') {
+			chars.next();
+		}
+		sign = match chars.next() {
+			None => break,
+			Some('+') => 1,
+			Some('-') => -1,
+			Some(_) => return Err(syntax()),
+		};
+	}
+
+	if !known {
+		return Ok(None);
+	}
+	let total = match selector {
+		Some('<') => total & 0xFF,
+		Some(_) => (total >> 8) & 0xFF,
+		None => total,
+	};
+	if !(0..=0xFFFF).contains(&total) {
+		return Err(AsmError::ValueOutOfRange { line, value: total });
+	}
+	Ok(Some(total as u16))
+}
+
+// Cuts the comment off, keeping semicolons inside strings and character literals.
+fn strip_comment(text: &str) -> &str {
+	let mut quote = None;
+	for (i, c) in text.char_indices() {
+		match (quote, c) {
+			(None, ';') => return &text[..i],
+			(None, '"' | '\'') => quote = Some(c),
+			(Some(q), _) if q == c => quote = None,
+			_ => {}
+		}
+	}
+	text
+}
+
+// Splits a list on commas outside of strings.
+fn split_list(text: &str) -> Vec<String> {
+	let mut items = Vec::new();
+	let mut current = String::new();
+	let mut quote = None;
+	for c in text.chars() {
+		match (quote, c) {
+			(None, ',') => {
+				items.push(current.trim().to_string());
+				current.clear();
+				continue;
+			}
+			(None, '"' | '\'') => quote = Some(c),
+			(Some(q), _) if q == c => quote = None,
+			_ => {}
+		}
+		current.push(c);
+	}
+	items.push(current.trim().to_string());
+	items
+}
+
+fn parse_line(text: &str, line: usize) -> Result<Vec<Statement>, AsmError> {
+	let syntax = || AsmError::Syntax { line, text: text.trim().to_string() };
+	let mut rest = strip_comment(text).trim();
+	let mut statements = Vec::new();
+
+	// Any number of labels can lead the line.
+	loop {
+		let end = rest.find(|c: char| !is_symbol_char(c)).unwrap_or(rest.len());
+		let name = &rest[..end];
+		let after = rest[end..].trim_start();
+		if end == 0 || name.starts_with(|c: char| c.is_ascii_digit()) {
+			break;
+		}
+		if let Some(after) = after.strip_prefix(':') {
+			statements.push(Statement::Label(name.to_string()));
+			rest = after.trim_start();
+		} else if let Some(expr) = after.strip_prefix('=') {
+			statements.push(Statement::Constant(name.to_string(), expr.trim().to_string()));
+			return Ok(statements);
+		} else {
+			break;
+		}
+	}
+	if rest.is_empty() {
+		return Ok(statements);
+	}
+
+	let (word, operand) = match rest.find(char::is_whitespace) {
+		Some(i) => (&rest[..i], rest[i..].trim()),
+		None => (rest, ""),
+	};
+
+	if let Some(directive) = word.strip_prefix('.') {
+		let statement = match directive.to_ascii_lowercase().as_str() {
+			"org" => Statement::Org(operand.to_string()),
+			"byte" | "byt" | "db" => Statement::Byte(split_list(operand)
+				.into_iter()
+				.map(|item| match item.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+					Some(text) => Item::Text(text.to_string()),
+					None => Item::Expr(item),
+				})
+				.collect()),
+			"word" | "addr" | "dw" => Statement::Word(split_list(operand)),
+			_ => return Err(syntax()),
+		};
+		statements.push(statement);
+		return Ok(statements);
+	}
+
+	// The disassembler marks unofficial opcodes with a *, so accept it here too.
+	let mnemonic = word.trim_start_matches('*').to_ascii_uppercase();
+	if mnemonic.is_empty() || !mnemonic.chars().all(|c| c.is_ascii_alphanumeric()) {
+		return Err(syntax());
+	}
+	let operand = parse_operand(operand).ok_or_else(syntax)?;
+	statements.push(Statement::Instruction { mnemonic, operand });
+	Ok(statements)
+}
+
+fn parse_operand(text: &str) -> Option<Operand> {
+	if text.is_empty() {
+		return Some(Operand::None);
+	}
+	if text.eq_ignore_ascii_case("a") {
+		return Some(Operand::Accumulator);
+	}
+	if let Some(expr) = text.strip_prefix('#') {
+		return Some(Operand::Immediate(expr.trim().to_string()));
+	}
+
+	let parts = split_list(text);
+	let register = |part: &str| match part.to_ascii_uppercase().as_str() {
+		"X" => Some('X'),
+		"Y" => Some('Y'),
+		_ => None,
+	};
+
+	if text.starts_with('(') {
+		let upper = text.to_ascii_uppercase().replace(' ', "");
+		if upper.ends_with(",X)") {
+			let comma = text.rfind(',')?;
+			return Some(Operand::IndirectX(text[1..comma].trim().to_string()));
+		}
+		if upper.ends_with("),Y") {
+			let close = text.rfind(')')?;
+			return Some(Operand::IndirectY(text[1..close].trim().to_string()));
+		}
+		if upper.ends_with(')') {
+			return Some(Operand::Indirect(text[1..text.len() - 1].trim().to_string()));
+		}
+	}
+
+	match parts.as_slice() {
+		[expr] => Some(direct(expr, None)),
+		[expr, index] => match register(index) {
+			Some(index) => Some(direct(expr, Some(index))),
+			None => Some(Operand::Pair(expr.clone(), index.clone())),
+		},
+		_ => None,
+	}
+}
+
+// ca65 address size overrides, z: forces zero page and a: forces absolute.
+fn direct(expr: &str, index: Option<char>) -> Operand {
+	let lower = expr.to_ascii_lowercase();
+	let (force, expr) = if lower.starts_with("z:") || lower.starts_with("a:") {
+		(lower.chars().next(), &expr[2..])
+	} else {
+		(None, expr)
+	};
+	Operand::Direct { expr: expr.trim().to_string(), index, force }
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cpu::Cpu;
+	use crate::disasm::decode_range;
+	use crate::memory::Memory;
+
+	#[test]
+	fn test_addressing_modes() {
+		let bytes = crate::assemble!(
+			"  nop",
+			"  asl a",
+			"  lda #$42",
+			"  lda $10",
+			"  lda $10,x",
+			"  ldx $10,y",
+			"  lda $2000",
+			"  lda a:$0010",
+			"  lda $2000,x",
+			"  lda $10,y",
+			"  jmp ($FFFC)",
+			"  lda ($10,x)",
+			"  lda ($10),y",
+		);
+		assert_eq!(bytes, vec![
+			0xEA, 0x0A, 0xA9, 0x42, 0xA5, 0x10, 0xB5, 0x10, 0xB6, 0x10, 0xAD, 0x00, 0x20,
+			0xAD, 0x10, 0x00, 0xBD, 0x00, 0x20, 0xB9, 0x10, 0x00, 0x6C, 0xFC, 0xFF, 0xA1, 0x10, 0xB1, 0x10,
+		]);
+	}
+
+	#[test]
+	fn test_labels_and_directives() {
+		let program = assemble("
+			PPU = $2000
+			start:  ldx #<data          ; low byte of a forward label
+			loop:   dex
+			        bne loop
+			        jmp end
+			data:   .byte 1, $02, %11, \"AB\"
+			        .word start, PPU + 1
+			        .org $0620
+			end:    rts
+		").unwrap();
+		assert_eq!(program.labels["start"], 0x0600);
+		assert_eq!(program.labels["data"], 0x0608);
+		assert_eq!(program.labels["end"], 0x0620);
+		assert_eq!(&program.bytes[..17], &[
+			0xA2, 0x08, 0xCA, 0xD0, 0xFD, 0x4C, 0x20, 0x06,
+			0x01, 0x02, 0x03, b'A', b'B', 0x00, 0x06, 0x01, 0x20,
+		]);
+		assert_eq!(program.bytes.len(), 0x21);
+		assert_eq!(program.bytes[0x20], 0x60);
+	}
+
+	#[test]
+	fn test_unofficial_mnemonics() {
+		assert_eq!(crate::assemble!("lax $10", "sax $10", "*nop $10", "slo ($10),y", "sbc #$01"), vec![
+			0xA7, 0x10, 0x87, 0x10, 0x04, 0x10, 0x13, 0x10, 0xE9, 0x01,
+		]);
+		assert_eq!(crate::assemble!("nop #$01", "aax $10", "jam"), vec![0x80, 0x01, 0x87, 0x10, 0x02]);
+	}
+
+	#[test]
+	fn test_cmos_mnemonics() {
+		let program = Assembler::new(CpuVariant::Wdc65C02).assemble("
+			top: bra top
+			     lda ($10)
+			     jmp ($2000,x)
+			     stz $10
+			     bbr3 $10, top
+		").unwrap();
+		assert_eq!(program.bytes, vec![0x80, 0xFE, 0xB2, 0x10, 0x7C, 0x00, 0x20, 0x64, 0x10, 0x3F, 0x10, 0xF4]);
+	}
+
+	#[test]
+	fn test_errors() {
+		assert_eq!(assemble("  foo $10"), Err(AsmError::UnknownMnemonic { line: 1, mnemonic: "FOO".to_string() }));
+		assert_eq!(assemble("  stx $1000,x"), Err(AsmError::UnsupportedMode { line: 1, mnemonic: "STX".to_string() }));
+		assert_eq!(assemble("\n  jmp nowhere"), Err(AsmError::UndefinedLabel { line: 2, label: "nowhere".to_string() }));
+		assert_eq!(assemble("a: nop\na: nop"), Err(AsmError::DuplicateLabel { line: 2, label: "a".to_string() }));
+		assert_eq!(assemble("  lda #$100"), Err(AsmError::ValueOutOfRange { line: 1, value: 0x100 }));
+		assert_eq!(assemble("  bne $0700"), Err(AsmError::BranchOutOfRange { line: 1, target: 0x0700 }));
+		assert_eq!(assemble(".org $0500"), Err(AsmError::BackwardOrg { line: 1, address: 0x0500 }));
+	}
+
+	#[test]
+	fn test_disassembly_round_trip() {
+		for def in CpuVariant::Ricoh2A03.opcodes().iter() {
+			let mut cpu = Cpu::new();
+			cpu.write(0x0600, def.code);
+			cpu.write(0x0601, 0x12);
+			cpu.write(0x0602, 0x34);
+			let instruction = decode_range(&cpu, 0x0600..0x0601, CpuVariant::Ricoh2A03)[0];
+			let bytes = assemble(&instruction.to_string()).unwrap().bytes;
+			let reassembled = decode_range(&bytes_memory(&bytes), 0x0600..0x0601, CpuVariant::Ricoh2A03)[0];
+			// Unofficial duplicates assemble to their official twin, so only the syntax has to survive.
+			assert_eq!(reassembled.mnemonic, instruction.mnemonic, "opcode ${:02X}", def.code);
+			assert_eq!(reassembled.operand_text(), instruction.operand_text(), "opcode ${:02X}", def.code);
+		}
+	}
+
+	fn bytes_memory(bytes: &[u8]) -> Cpu {
+		let mut cpu = Cpu::new();
+		for (i, byte) in bytes.iter().enumerate() {
+			cpu.write(0x0600 + i as u16, *byte);
+		}
+		cpu
+	}
+}
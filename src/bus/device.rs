@@ -0,0 +1,333 @@
+use std::any::Any;
+use std::ops::RangeInclusive;
+
+use super::super::memory::RamInit;
+use super::super::state::{Snapshot, StateError, StateReader, StateWriter};
+
+// Something that sits on the CPU bus, like a PPU, an APU or a debug port a homebrew
+// game prints through. Devices see addresses with the mirroring mask of the range
+// they were attached at applied.
+pub trait Device: Any {
+	// Registers that change when read, like a status flag that clears, override this.
+	fn read(&mut self, addr: u16) -> u8 {
+		self.peek(addr)
+	}
+
+	fn write(&mut self, addr: u16, data: u8);
+
+	// What a read would return, without its side effects.
+	fn peek(&self, addr: u16) -> u8;
+
+	// Sets what a debugger asks for without the side effects of a write. Devices
+	// that can't ignore it.
+	fn poke(&mut self, _addr: u16, _data: u8) {}
+
+	// The bits a read actually drives onto the data bus. The others keep whatever
+	// was last on it, see Bus::open_bus.
+	fn driven(&self, _addr: u16) -> u8 {
+		0xFF
+	}
+
+	// Called when the console is switched on. Devices with RAM of their own, like the
+	// PPU's OAM and palette, fill it according to the policy.
+	fn power_on(&mut self, _init: RamInit) {}
+
+	// Devices with state of their own, registers or RAM, save it here so that it
+	// comes back with the rest of the machine. Load has to read all of what save wrote.
+	fn save(&self, _writer: &mut StateWriter) {}
+
+	fn load(&mut self, _reader: &mut StateReader) -> Result<(), StateError> {
+		Ok(())
+	}
+}
+
+// Lets each device go in a chunk of its own, so one that reads too little or too
+// much is caught.
+impl Snapshot for Box<dyn Device> {
+	fn save(&self, writer: &mut StateWriter) {
+		Device::save(self.as_ref(), writer);
+	}
+
+	fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+		Device::load(self.as_mut(), reader)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DeviceId(usize);
+
+// The built in parts of the bus are fields of it, everything else is attached.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum Target {
+	Ram,
+	Controllers,
+	Cartridge,
+	Device(DeviceId),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Region {
+	start: u16,
+	end: u16,
+	mask: u16,
+	target: Target,
+}
+
+// Which device answers at each address. Ranges attached later take precedence, so
+// a device can take over part of a range claimed earlier.
+pub struct AddressMap {
+	regions: Vec<Region>,
+	devices: Vec<Option<Box<dyn Device>>>,
+}
+
+impl AddressMap {
+	// The NES layout: 2KB of RAM mirrored up to $1FFF, the controllers at $4016 and
+	// $4017 and the cartridge from $4020. Nothing answers at $2000-$3FFF until a PPU
+	// is attached there, with a mask of $2007.
+	pub fn new() -> Self {
+		let mut map = AddressMap { regions: Vec::new(), devices: Vec::new() };
+		map.map(0x0000..=0x1FFF, 0x07FF, Target::Ram);
+		map.map(0x4016..=0x4017, 0xFFFF, Target::Controllers);
+		map.map(0x4020..=0xFFFF, 0xFFFF, Target::Cartridge);
+		map
+	}
+
+	fn map(&mut self, range: RangeInclusive<u16>, mask: u16, target: Target) {
+		self.regions.push(Region { start: *range.start(), end: *range.end(), mask, target });
+	}
+
+	pub fn attach(&mut self, range: RangeInclusive<u16>, mask: u16, device: Box<dyn Device>) -> DeviceId {
+		let id = DeviceId(self.devices.len());
+		self.devices.push(Some(device));
+		self.map(range, mask, Target::Device(id));
+		id
+	}
+
+	// Hands the device back. Whatever it covered up answers again.
+	pub fn detach(&mut self, id: DeviceId) -> Option<Box<dyn Device>> {
+		self.regions.retain(|region| region.target != Target::Device(id));
+		self.devices.get_mut(id.0)?.take()
+	}
+
+	pub fn device<T: Device>(&self, id: DeviceId) -> Option<&T> {
+		let device: &dyn Any = self.devices.get(id.0)?.as_deref()?;
+		device.downcast_ref()
+	}
+
+	pub fn device_mut<T: Device>(&mut self, id: DeviceId) -> Option<&mut T> {
+		let device: &mut dyn Any = self.devices.get_mut(id.0)?.as_deref_mut()?;
+		device.downcast_mut()
+	}
+
+	// What answers at an address, and the address it sees. None when nothing does.
+	pub(super) fn decode(&self, addr: u16) -> Option<(Target, u16)> {
+		self.regions
+			.iter()
+			.rev()
+			.find(|region| (region.start..=region.end).contains(&addr))
+			.map(|region| (region.target, addr & region.mask))
+	}
+
+	// The value read and the bits of it that were driven.
+	pub(super) fn read(&mut self, id: DeviceId, addr: u16) -> (u8, u8) {
+		self.devices[id.0].as_mut().map_or((0, 0), |device| (device.read(addr), device.driven(addr)))
+	}
+
+	pub(super) fn peek(&self, id: DeviceId, addr: u16) -> (u8, u8) {
+		self.devices[id.0].as_ref().map_or((0, 0), |device| (device.peek(addr), device.driven(addr)))
+	}
+
+	pub(super) fn write(&mut self, id: DeviceId, addr: u16, data: u8) {
+		if let Some(device) = &mut self.devices[id.0] {
+			device.write(addr, data);
+		}
+	}
+
+	pub(super) fn power_on(&mut self, init: RamInit) {
+		for device in self.devices.iter_mut().flatten() {
+			device.power_on(init);
+		}
+	}
+
+	pub(super) fn poke(&mut self, id: DeviceId, addr: u16, data: u8) {
+		if let Some(device) = &mut self.devices[id.0] {
+			device.poke(addr, data);
+		}
+	}
+
+	// The devices still attached, in the order they were attached.
+	pub(super) fn save(&self, writer: &mut StateWriter) {
+		writer.write_u32(self.devices.iter().flatten().count() as u32);
+		for device in self.devices.iter().flatten() {
+			writer.chunk(b"DEV ", device);
+		}
+	}
+
+	pub(super) fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+		let count = reader.read_u32()?;
+		if count as usize != self.devices.iter().flatten().count() {
+			return Err(StateError::Invalid { field: "device count", value: count as u64 });
+		}
+		for device in self.devices.iter_mut().flatten() {
+			reader.chunk(b"DEV ", device)?;
+		}
+		Ok(())
+	}
+}
+
+impl Default for AddressMap {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::bus::Bus;
+	use crate::cpu::{Cpu, Memory};
+
+	// The kind of port homebrew prints debug output through.
+	struct Uart {
+		output: String,
+	}
+
+	impl Device for Uart {
+		fn write(&mut self, _addr: u16, data: u8) {
+			self.output.push(data as char);
+		}
+
+		fn peek(&self, _addr: u16) -> u8 {
+			// Always ready to send.
+			0x80
+		}
+	}
+
+	// Eight registers mirrored over the whole range, like the PPU's.
+	struct Registers([u8; 8]);
+
+	impl Device for Registers {
+		fn write(&mut self, addr: u16, data: u8) {
+			self.0[addr as usize - 0x2000] = data;
+		}
+
+		fn peek(&self, addr: u16) -> u8 {
+			self.0[addr as usize - 0x2000]
+		}
+
+		fn save(&self, writer: &mut StateWriter) {
+			writer.write_bytes(&self.0);
+		}
+
+		fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+			self.0.copy_from_slice(reader.read_bytes(8)?);
+			Ok(())
+		}
+	}
+
+	// A flag that clears when read, like the vblank flag in PPUSTATUS.
+	struct Flag(bool);
+
+	impl Device for Flag {
+		fn read(&mut self, addr: u16) -> u8 {
+			let data = self.peek(addr);
+			self.0 = false;
+			data
+		}
+
+		fn write(&mut self, _addr: u16, data: u8) {
+			self.0 = data != 0;
+		}
+
+		fn peek(&self, _addr: u16) -> u8 {
+			(self.0 as u8) << 7
+		}
+	}
+
+	#[test]
+	fn test_peek_and_poke() {
+		let mut bus = Bus::new();
+		bus.attach(0x2002..=0x2002, 0xFFFF, Box::new(Flag(true)));
+		assert_eq!(bus.peek(0x2002), 0x80);
+		assert_eq!(bus.read(0x2002), 0x80);
+		assert_eq!(bus.peek(0x2002), 0x00);
+		// The default poke leaves device registers alone.
+		bus.poke(0x2002, 1);
+		assert_eq!(bus.read(0x2002), 0x00);
+
+		bus.joypad1.buttons = 0b0000_0010;
+		bus.write(0x4016, 1);
+		bus.poke(0x4016, 0);
+		assert_eq!(bus.peek(0x4016) & 1, 0);
+		bus.write(0x4016, 0);
+		assert_eq!(bus.peek(0x4016) & 1, 0);
+		assert_eq!(bus.peek(0x4016) & 1, 0);
+		assert_eq!(bus.read(0x4016) & 1, 0);
+		assert_eq!(bus.read(0x4016) & 1, 1);
+
+		bus.poke(0x0123, 0x45);
+		assert_eq!(bus.open_bus(), 0x01);
+		assert_eq!(bus.peek(0x0923), 0x45);
+		assert_eq!(bus.open_bus(), 0x01);
+	}
+
+	#[test]
+	fn test_debug_port() {
+		let mut cpu = Cpu::new();
+		let uart = cpu.bus.attach(0x4018..=0x4018, 0xFFFF, Box::new(Uart { output: String::new() }));
+		cpu.load(crate::assemble![
+			"        ldx #$00",
+			"loop:   lda text,x",
+			"        beq done",
+			"        sta $4018",
+			"        inx",
+			"        bne loop",
+			"done:   brk",
+			"text:   .byte \"hi!\", 0",
+		]);
+		cpu.reset();
+		cpu.run().unwrap();
+		assert_eq!(cpu.bus.device::<Uart>(uart).unwrap().output, "hi!");
+		assert_eq!(cpu.read(0x4018), 0x80);
+		assert!(cpu.bus.device::<Registers>(uart).is_none());
+	}
+
+	#[test]
+	fn test_mirroring_and_precedence() {
+		let mut bus = Bus::new();
+		let registers = bus.attach(0x2000..=0x3FFF, 0x2007, Box::new(Registers([0; 8])));
+		bus.write(0x3FFA, 0x12);
+		assert_eq!(bus.read(0x2002), 0x12);
+		assert_eq!(bus.device::<Registers>(registers).unwrap().0[2], 0x12);
+
+		// Covering a page of RAM, then giving it back.
+		bus.write(0x0300, 0x34);
+		let page = bus.attach(0x0300..=0x03FF, 0xFFFF, Box::new(Uart { output: String::new() }));
+		assert_eq!(bus.read(0x0300), 0x80);
+		assert_eq!(bus.read(0x0B00), 0x34);
+		assert!(bus.detach(page).is_some());
+		assert_eq!(bus.read(0x0300), 0x34);
+		assert!(bus.detach(page).is_none());
+	}
+
+	#[test]
+	fn test_save_state() {
+		let mut cpu = Cpu::new();
+		let registers = cpu.bus.attach(0x2000..=0x3FFF, 0x2007, Box::new(Registers([0; 8])));
+		cpu.bus.attach(0x4018..=0x4018, 0xFFFF, Box::new(Uart { output: String::new() }));
+		cpu.write(0x2003, 0x12);
+		let state = cpu.save_state();
+		cpu.write(0x2003, 0x34);
+		cpu.load_state(&state).unwrap();
+		assert_eq!(cpu.read(0x2003), 0x12);
+		assert_eq!(cpu.save_state(), state);
+
+		// The devices stay attached when a state is rejected.
+		assert_eq!(cpu.load_state(b"garbage"), Err(StateError::BadMagic));
+		assert_eq!(cpu.bus.device::<Registers>(registers).unwrap().0[3], 0x12);
+
+		// A machine with other devices attached can't take the state.
+		let mut other = Cpu::new();
+		assert_eq!(other.load_state(&state), Err(StateError::Invalid { field: "device count", value: 2 }));
+	}
+}
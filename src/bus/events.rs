@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessKind {
+	Read,
+	Write,
+	// Reads of the instruction stream, opcodes and operands.
+	Fetch,
+	// Accesses made by a DMA unit while the CPU is halted.
+	Dma,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BusEvent {
+	// The CPU cycle the access happened in.
+	pub cycle: u64,
+	pub address: u16,
+	pub value: u8,
+	pub kind: AccessKind,
+}
+
+enum Sink {
+	Buffer { events: VecDeque<BusEvent>, capacity: usize },
+	Callback(Box<dyn FnMut(&BusEvent)>),
+}
+
+// Records bus accesses for event viewers and tests. Whatever drives the bus
+// reports its accesses through Bus::record, which hands them to the log if one is
+// set. Without any ranges every access is recorded.
+pub struct EventLog {
+	ranges: Vec<RangeInclusive<u16>>,
+	sink: Sink,
+}
+
+impl EventLog {
+	// Keeps the most recent events, dropping the oldest once it holds capacity.
+	pub fn ring_buffer(capacity: usize) -> Self {
+		EventLog {
+			ranges: Vec::new(),
+			sink: Sink::Buffer { events: VecDeque::with_capacity(capacity), capacity },
+		}
+	}
+
+	pub fn callback<F: FnMut(&BusEvent) + 'static>(callback: F) -> Self {
+		EventLog { ranges: Vec::new(), sink: Sink::Callback(Box::new(callback)) }
+	}
+
+	// Only records accesses in the range, along with any other ranges watched.
+	pub fn watch(mut self, range: RangeInclusive<u16>) -> Self {
+		self.ranges.push(range);
+		self
+	}
+
+	pub fn record(&mut self, event: BusEvent) {
+		if !self.ranges.is_empty() && !self.ranges.iter().any(|range| range.contains(&event.address)) {
+			return;
+		}
+		match &mut self.sink {
+			Sink::Buffer { events, capacity } => {
+				if events.len() == *capacity {
+					events.pop_front();
+				}
+				if *capacity > 0 {
+					events.push_back(event);
+				}
+			}
+			Sink::Callback(callback) => callback(&event),
+		}
+	}
+
+	// The buffered events, oldest first. Always empty with a callback.
+	pub fn events(&self) -> impl Iterator<Item = &BusEvent> {
+		let events = match &self.sink {
+			Sink::Buffer { events, .. } => Some(events.iter()),
+			Sink::Callback(_) => None,
+		};
+		events.into_iter().flatten()
+	}
+
+	pub fn clear(&mut self) {
+		if let Sink::Buffer { events, .. } = &mut self.sink {
+			events.clear();
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	use super::*;
+	use crate::cpu::Cpu;
+
+	fn event(cycle: u64, address: u16, value: u8, kind: AccessKind) -> BusEvent {
+		BusEvent { cycle, address, value, kind }
+	}
+
+	#[test]
+	fn test_cpu_events() {
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assemble!["lda $10", "sta $4016", "ldx #$20", "jmp $0600"]);
+		cpu.reset();
+		cpu.bus.events = Some(EventLog::ring_buffer(16));
+		for _ in 0..4 {
+			cpu.step().unwrap();
+		}
+		let events: Vec<BusEvent> = cpu.bus.events.as_ref().unwrap().events().copied().collect();
+		assert_eq!(events, vec![
+			event(0, 0x0600, 0xA5, AccessKind::Fetch),
+			event(1, 0x0601, 0x10, AccessKind::Fetch),
+			event(2, 0x0010, 0x00, AccessKind::Read),
+			event(3, 0x0602, 0x8D, AccessKind::Fetch),
+			event(4, 0x0603, 0x16, AccessKind::Fetch),
+			event(5, 0x0604, 0x40, AccessKind::Fetch),
+			event(6, 0x4016, 0x00, AccessKind::Write),
+			// Immediate operands are fetched with the instruction.
+			event(7, 0x0605, 0xA2, AccessKind::Fetch),
+			event(8, 0x0606, 0x20, AccessKind::Fetch),
+			event(9, 0x0607, 0x4C, AccessKind::Fetch),
+			event(10, 0x0608, 0x00, AccessKind::Fetch),
+			event(11, 0x0609, 0x06, AccessKind::Fetch),
+		]);
+	}
+
+	#[test]
+	fn test_filters_and_sinks() {
+		let seen = Rc::new(RefCell::new(Vec::new()));
+		let sink = seen.clone();
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assemble!["loop: sta $4016", "lda $2002", "jmp loop"]);
+		cpu.reset();
+		let log = EventLog::callback(move |event| sink.borrow_mut().push(*event)).watch(0x4016..=0x4017);
+		cpu.bus.events = Some(log.watch(0x2000..=0x3FFF));
+		for _ in 0..6 {
+			cpu.step().unwrap();
+		}
+		let seen = seen.borrow();
+		assert_eq!(seen.len(), 4);
+		assert_eq!(seen[0], event(3, 0x4016, 0, AccessKind::Write));
+		assert_eq!(seen[1].address, 0x2002);
+		assert!(cpu.bus.events.as_ref().unwrap().events().next().is_none());
+
+		// A full buffer drops the oldest.
+		let mut log = EventLog::ring_buffer(2);
+		for cycle in 0..5 {
+			log.record(event(cycle, 0, 0, AccessKind::Dma));
+		}
+		let cycles: Vec<u64> = log.events().map(|event| event.cycle).collect();
+		assert_eq!(cycles, vec![3, 4]);
+		log.clear();
+		assert_eq!(log.events().count(), 0);
+	}
+}
@@ -1,23 +1,169 @@
-use super::memory::Memory;
+mod device;
+mod events;
+mod open_bus;
 
-const RAM_START: u16 = 0x0000;
-const RAM_END: u16 = 0x1FFF;
-const PPU_START: u16 = 0x2000;
-const PPU_END: u16 = 0x3FFF;
+use std::ops::RangeInclusive;
 
-const fn mirror_addr(addr: u16) -> u16 {
-	addr & 0b0000_0111_1111_1111
-}
+use super::memory::{Memory, RamInit, STREAM_PRG_RAM, STREAM_RAM};
+use super::joypad::Joypad;
+use super::cartridge::Cartridge;
+use super::cheats::Cheats;
+use super::region::Region;
+use super::state::{Snapshot, StateError, StateReader, StateWriter};
+
+pub use device::*;
+pub use events::*;
+pub use open_bus::*;
+
+const JOYPAD1: u16 = 0x4016;
+// The controller ports leave the top three bits of $4016 and $4017 undriven.
+const JOYPAD_DRIVEN: u8 = 0b0001_1111;
 
 pub struct Bus {
-	vram: [u8; 2048]
+	vram: [u8; 2048],
+	pub joypad1: Joypad,
+	pub joypad2: Joypad,
+	pub cartridge: Option<Cartridge>,
+	pub cheats: Cheats,
+	pub map: AddressMap,
+	// What the RAM holds at power on.
+	pub ram_init: RamInit,
+	// Which console's timing everything runs with.
+	pub region: Region,
+	pub events: Option<EventLog>,
+	// The last value on the data bus, which reads of undriven bits return.
+	open_bus: u8,
 }
 
 impl Bus {
 	pub fn new() -> Self{
-		Bus {
-			vram: [0; 2048]
+		Self::with_ram_init(RamInit::Zeros)
+	}
+
+	pub fn with_ram_init(ram_init: RamInit) -> Self {
+		let mut bus = Bus {
+			vram: [0; 2048],
+			joypad1: Joypad::new(),
+			joypad2: Joypad::new(),
+			cartridge: None,
+			cheats: Cheats::new(),
+			map: AddressMap::new(),
+			ram_init,
+			region: Region::Ntsc,
+			events: None,
+			open_bus: 0,
+		};
+		ram_init.fill(&mut bus.vram, STREAM_RAM);
+		bus
+	}
+
+	// Cartridges without a battery get their RAM filled like the console's, battery
+	// backed RAM keeps what it had. The region switches to the one the header names,
+	// set it afterwards to run the game on another console.
+	pub fn insert(&mut self, mut cartridge: Cartridge) {
+		if let Some(region) = cartridge.region {
+			self.region = region;
+		}
+		if !cartridge.battery {
+			self.ram_init.fill(&mut cartridge.prg_ram, STREAM_PRG_RAM);
+		}
+		self.cartridge = Some(cartridge);
+	}
+
+	// Puts a device on the bus at the range, see AddressMap.
+	pub fn attach(&mut self, range: RangeInclusive<u16>, mask: u16, device: Box<dyn Device>) -> DeviceId {
+		self.map.attach(range, mask, device)
+	}
+
+	pub fn detach(&mut self, id: DeviceId) -> Option<Box<dyn Device>> {
+		self.map.detach(id)
+	}
+
+	pub fn device<T: Device>(&self, id: DeviceId) -> Option<&T> {
+		self.map.device(id)
+	}
+
+	pub fn device_mut<T: Device>(&mut self, id: DeviceId) -> Option<&mut T> {
+		self.map.device_mut(id)
+	}
+
+	// Passes an access on to the event log, if there is one. The CPU reports every
+	// access it makes, and so should anything else that drives the bus.
+	pub fn record(&mut self, cycle: u64, address: u16, value: u8, kind: AccessKind) {
+		if let Some(events) = &mut self.events {
+			events.record(BusEvent { cycle, address, value, kind });
+		}
+	}
+
+	// The last value read or written. Reads from addresses nothing answers at return
+	// it, which some games and test ROMs depend on.
+	pub fn open_bus(&self) -> u8 {
+		self.open_bus
+	}
+
+	// The 2KB of internal RAM, without the mirrors.
+	pub fn ram(&self) -> &[u8] {
+		&self.vram
+	}
+
+	// What pressing the power button does to the bus. RAM comes up as ram_init says,
+	// except for battery backed RAM on the cartridge.
+	pub fn power_on(&mut self) {
+		self.ram_init.fill(&mut self.vram, STREAM_RAM);
+		if let Some(cart) = self.cartridge.as_mut().filter(|cart| !cart.battery) {
+			self.ram_init.fill(&mut cart.prg_ram, STREAM_PRG_RAM);
+		}
+		self.joypad1 = Joypad::new();
+		self.joypad2 = Joypad::new();
+		self.open_bus = 0;
+		self.map.power_on(self.ram_init);
+	}
+}
+
+impl Default for Bus {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Snapshot for Bus {
+	fn save(&self, writer: &mut StateWriter) {
+		writer.write_bytes(&self.vram);
+		self.joypad1.save(writer);
+		self.joypad2.save(writer);
+		// Only the cartridge's RAM, the ROM is the same every time it's loaded.
+		let prg_ram = self.cartridge.as_ref().map_or(&[][..], |cart| &cart.prg_ram);
+		writer.write_u32(prg_ram.len() as u32);
+		writer.write_bytes(prg_ram);
+		writer.write_u8(self.open_bus);
+		writer.write_u8(match self.region {
+			Region::Ntsc => 0,
+			Region::Pal => 1,
+			Region::Dendy => 2,
+		});
+		self.map.save(writer);
+	}
+
+	fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+		let vram = reader.read_bytes(self.vram.len())?;
+		self.vram.copy_from_slice(vram);
+		self.joypad1.load(reader)?;
+		self.joypad2.load(reader)?;
+
+		let len = reader.read_u32()?;
+		let prg_ram = self.cartridge.as_mut().map_or(&mut [][..], |cart| &mut cart.prg_ram);
+		if len as usize != prg_ram.len() {
+			return Err(StateError::Invalid { field: "PRG-RAM size", value: len as u64 });
 		}
+		prg_ram.copy_from_slice(reader.read_bytes(prg_ram.len())?);
+		self.open_bus = reader.read_u8()?;
+		self.region = match reader.read_u8()? {
+			0 => Region::Ntsc,
+			1 => Region::Pal,
+			2 => Region::Dendy,
+			value => return Err(StateError::Invalid { field: "region", value: value as u64 }),
+		};
+		self.map.load(reader)
 	}
 }
 
@@ -28,28 +174,149 @@ impl Bus {
 	}
 }
 
-impl Memory for Bus {
-	fn read(&self, addr: u16) -> u8 {
-		match addr {
-			RAM_START ..= RAM_END => {
-				self.vram[mirror_addr(addr) as usize]
-			}
-			PPU_START ..= PPU_END => {
-				todo!("PPU")
+impl Bus {
+	// What a read returns and which bits of it were driven, without side effects.
+	fn fetch(&self, addr: u16) -> (u8, u8) {
+		match self.map.decode(addr) {
+			Some((Target::Ram, addr)) => (self.vram[addr as usize], 0xFF),
+			Some((Target::Controllers, JOYPAD1)) => (self.joypad1.peek(), JOYPAD_DRIVEN),
+			Some((Target::Controllers, _)) => (self.joypad2.peek(), JOYPAD_DRIVEN),
+			Some((Target::Cartridge, addr)) => {
+				self.cartridge.as_ref().and_then(|cart| cart.read(addr)).map_or((0, 0), |data| (data, 0xFF))
 			}
-			_ => 0
+			Some((Target::Device(id), addr)) => self.map.peek(id, addr),
+			None => (0, 0),
 		}
 	}
 
+	fn with_open_bus(&self, addr: u16, (data, driven): (u8, u8)) -> u8 {
+		self.cheats.apply(addr, data & driven | self.open_bus & !driven)
+	}
+}
+
+impl Memory for Bus {
+	fn read(&mut self, addr: u16) -> u8 {
+		let fetched = match self.map.decode(addr) {
+			Some((Target::Controllers, JOYPAD1)) => (self.joypad1.read(), JOYPAD_DRIVEN),
+			Some((Target::Controllers, _)) => (self.joypad2.read(), JOYPAD_DRIVEN),
+			Some((Target::Device(id), addr)) => self.map.read(id, addr),
+			_ => self.fetch(addr),
+		};
+		self.open_bus = self.with_open_bus(addr, fetched);
+		self.open_bus
+	}
+
 	fn write(&mut self, addr: u16, data: u8) {
-		match addr {
-			RAM_START ..= RAM_END => {
-				self.vram[mirror_addr(addr) as usize] = data;
+		self.open_bus = data;
+		match self.map.decode(addr) {
+			Some((Target::Ram, addr)) => self.vram[addr as usize] = data,
+			// Both controllers share the strobe line, $4017 writes go to the APU.
+			Some((Target::Controllers, JOYPAD1)) => {
+				self.joypad1.write(data);
+				self.joypad2.write(data);
 			}
-			PPU_START ..= PPU_END => {
-				todo!("PPU");
+			Some((Target::Controllers, _)) => {}
+			Some((Target::Cartridge, addr)) => {
+				if let Some(cart) = &mut self.cartridge {
+					cart.write(addr, data);
+				}
 			}
-			_ => {}
+			Some((Target::Device(id), addr)) => self.map.write(id, addr, data),
+			None => {}
+		}
+	}
+
+	// What the CPU would read, cheats included, with the controllers left where they
+	// are and the open bus untouched.
+	fn peek(&self, addr: u16) -> u8 {
+		self.with_open_bus(addr, self.fetch(addr))
+	}
+
+	// Changes memory but doesn't strobe the controllers or touch the open bus.
+	fn poke(&mut self, addr: u16, data: u8) {
+		match self.map.decode(addr) {
+			Some((Target::Ram, addr)) => self.vram[addr as usize] = data,
+			Some((Target::Controllers, _)) | None => {}
+			Some((Target::Cartridge, addr)) => {
+				if let Some(cart) = &mut self.cartridge {
+					cart.write(addr, data);
+				}
+			}
+			Some((Target::Device(id), addr)) => self.map.poke(id, addr, data),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cartridge::Mirroring;
+
+	fn cartridge(battery: bool) -> Cartridge {
+		Cartridge {
+			prg_rom: vec![0; 0x4000],
+			chr_rom: Vec::new(),
+			prg_ram: vec![0x12; 0x2000],
+			mapper: 0,
+			mirroring: Mirroring::Horizontal,
+			battery,
+			region: None,
+		}
+	}
+
+	// Stands in for the PPU's OAM.
+	struct Oam([u8; 256]);
+
+	impl Device for Oam {
+		fn write(&mut self, _addr: u16, _data: u8) {}
+
+		fn peek(&self, addr: u16) -> u8 {
+			self.0[addr as usize & 0xFF]
+		}
+
+		fn power_on(&mut self, init: RamInit) {
+			init.fill(&mut self.0, crate::memory::STREAM_OAM);
 		}
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn test_power_on_ram() {
+		let mut bus = Bus::with_ram_init(RamInit::Pattern);
+		assert_eq!(&bus.ram()[..8], &[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+		bus.insert(cartridge(false));
+		assert_eq!(bus.peek(0x6004), 0xFF);
+
+		bus.ram_init = RamInit::Random(7);
+		let oam = bus.attach(0x2004..=0x2004, 0xFFFF, Box::new(Oam([0; 256])));
+		bus.power_on();
+		let ram = bus.ram().to_vec();
+		let prg_ram = bus.cartridge.as_ref().unwrap().prg_ram.clone();
+		let sprites = bus.device::<Oam>(oam).unwrap().0;
+		assert_ne!(&ram[..256], &prg_ram[..256]);
+		assert_ne!(&ram[..256], &sprites[..]);
+
+		// The same seed, the same contents.
+		bus.write(0x0000, 0x99);
+		bus.power_on();
+		assert_eq!(bus.ram(), &ram[..]);
+		assert_eq!(bus.device::<Oam>(oam).unwrap().0, sprites);
+
+		// Battery backed RAM keeps the save.
+		bus.insert(cartridge(true));
+		bus.power_on();
+		assert_eq!(bus.peek(0x6000), 0x12);
+	}
+
+	#[test]
+	fn test_region_from_cartridge() {
+		let mut bus = Bus::new();
+		bus.insert(cartridge(false));
+		assert_eq!(bus.region, Region::Ntsc);
+		bus.insert(Cartridge { region: Some(Region::Dendy), ..cartridge(false) });
+		assert_eq!(bus.region, Region::Dendy);
+		// Multi-region games run on whatever the console is.
+		bus.region = Region::Pal;
+		bus.insert(cartridge(false));
+		assert_eq!(bus.region, Region::Pal);
+	}
+}
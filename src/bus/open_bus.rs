@@ -0,0 +1,106 @@
+// How long the PPU's I/O latch holds a bit after it was last driven, about 600ms,
+// in CPU cycles. The exact time varies between consoles and with temperature.
+pub const PPU_LATCH_DECAY: u64 = 1_000_000;
+
+// A latch holding the last value driven onto a data bus, for devices whose
+// registers only drive some of the bits. The PPU's I/O latch is one, PPUSTATUS
+// only drives the top three bits and the rest read back from it. The charge leaks
+// away, so without a decay time set the bits never fade, with one each bit reads
+// as zero once it hasn't been driven for that long.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecayLatch {
+	pub decay: Option<u64>,
+	value: u8,
+	// When each bit was last driven, in whatever unit decay is in.
+	refreshed: [u64; 8],
+}
+
+impl DecayLatch {
+	pub fn new(decay: Option<u64>) -> Self {
+		DecayLatch { decay, value: 0, refreshed: [0; 8] }
+	}
+
+	// Drives the bits set in mask, leaving the others as they are.
+	pub fn drive(&mut self, value: u8, mask: u8, now: u64) {
+		self.value = self.value & !mask | value & mask;
+		for bit in 0..8 {
+			if mask & 1 << bit != 0 {
+				self.refreshed[bit] = now;
+			}
+		}
+	}
+
+	pub fn read(&self, now: u64) -> u8 {
+		let Some(decay) = self.decay else {
+			return self.value;
+		};
+		(0..8)
+			.filter(|&bit| now.saturating_sub(self.refreshed[bit]) < decay)
+			.fold(0, |value, bit| value | self.value & 1 << bit)
+	}
+}
+
+impl Default for DecayLatch {
+	fn default() -> Self {
+		Self::new(None)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::bus::{Bus, Device};
+	use crate::cpu::Memory;
+
+	#[test]
+	fn test_decay() {
+		let mut latch = DecayLatch::new(Some(PPU_LATCH_DECAY));
+		latch.drive(0xFF, 0xFF, 0);
+		latch.drive(0x00, 0xE0, 500_000);
+		assert_eq!(latch.read(900_000), 0x1F);
+		assert_eq!(latch.read(1_200_000), 0x00);
+		latch.drive(0xA5, 0x0F, 1_300_000);
+		assert_eq!(latch.read(1_300_000), 0x05);
+
+		let mut latch = DecayLatch::default();
+		latch.drive(0x42, 0xFF, 0);
+		assert_eq!(latch.read(u64::MAX), 0x42);
+	}
+
+	// Drives the top three bits only, like PPUSTATUS.
+	struct Status;
+
+	impl Device for Status {
+		fn write(&mut self, _addr: u16, _data: u8) {}
+
+		fn peek(&self, _addr: u16) -> u8 {
+			0b1010_0000
+		}
+
+		fn driven(&self, _addr: u16) -> u8 {
+			0b1110_0000
+		}
+	}
+
+	#[test]
+	fn test_open_bus() {
+		let mut bus = Bus::new();
+		bus.write(0x0010, 0x5A);
+		assert_eq!(bus.read(0x2002), 0x5A);
+		assert_eq!(bus.read(0x4018), 0x5A);
+		assert_eq!(bus.read(0x5000), 0x5A);
+
+		// The controllers only drive the low bits, $4016 usually reads $40 or $41.
+		bus.read(0x4000);
+		bus.joypad1.buttons = 0x01;
+		bus.write(0x4016, 1);
+		assert_eq!(bus.read(0x4016), 0x01);
+		bus.write(0x0040, 0x40);
+		assert_eq!(bus.read(0x4016), 0x41);
+
+		bus.attach(0x2000..=0x3FFF, 0x2007, Box::new(Status));
+		bus.write(0x0000, 0x1F);
+		assert_eq!(bus.read(0x2002), 0xBF);
+		assert_eq!(bus.open_bus(), 0xBF);
+	}
+}
@@ -0,0 +1,188 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::Cartridge;
+
+// Where battery-backed RAM lives between sessions. Hosts that keep saves somewhere
+// other than the file system, like a browser's local storage or a cloud sync
+// service, implement this themselves.
+pub trait SaveStorage {
+	// Returns None when there is no save yet.
+	fn load(&mut self) -> io::Result<Option<Vec<u8>>>;
+	fn store(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+// A .sav file holding the raw contents of the RAM, the same layout other emulators use.
+pub struct FileStorage {
+	pub path: PathBuf,
+}
+
+impl FileStorage {
+	pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+		FileStorage { path: path.into() }
+	}
+
+	// The save file next to the ROM, game.nes keeps its saves in game.sav.
+	pub fn beside(rom: &Path) -> Self {
+		Self::new(rom.with_extension("sav"))
+	}
+}
+
+impl SaveStorage for FileStorage {
+	fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+		match fs::read(&self.path) {
+			Ok(data) => Ok(Some(data)),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err),
+		}
+	}
+
+	// Goes through a temporary file, so a crash halfway leaves the old save intact.
+	fn store(&mut self, data: &[u8]) -> io::Result<()> {
+		let temp = self.path.with_extension("sav.tmp");
+		fs::write(&temp, data)?;
+		fs::rename(&temp, &self.path)
+	}
+}
+
+// Keeps a cartridge's battery-backed RAM in sync with its storage. The host calls
+// frame once per emulated frame, which writes the RAM out every so often when it
+// changed, and flush when shutting down. Cartridges without a battery are left
+// alone, so the host doesn't have to check.
+pub struct Battery<S: SaveStorage> {
+	pub storage: S,
+	interval: u32,
+	frames: u32,
+	// What the storage holds, to skip writes when nothing changed.
+	saved: Vec<u8>,
+}
+
+impl<S: SaveStorage> Battery<S> {
+	// Fills the RAM from the storage. Saves of the wrong size still load as far as
+	// they go, since some emulators pad or trim them.
+	pub fn attach(cartridge: &mut Cartridge, mut storage: S, interval: u32) -> io::Result<Self> {
+		if cartridge.battery {
+			if let Some(data) = storage.load()? {
+				let len = data.len().min(cartridge.prg_ram.len());
+				cartridge.prg_ram[..len].copy_from_slice(&data[..len]);
+			}
+		}
+		Ok(Battery {
+			storage,
+			interval: interval.max(1),
+			frames: 0,
+			saved: cartridge.prg_ram.clone(),
+		})
+	}
+
+	// Autosaves every interval frames. Returns whether the RAM was written out.
+	pub fn frame(&mut self, cartridge: &Cartridge) -> io::Result<bool> {
+		self.frames += 1;
+		if self.frames < self.interval {
+			return Ok(false);
+		}
+		self.frames = 0;
+		self.flush(cartridge)
+	}
+
+	pub fn flush(&mut self, cartridge: &Cartridge) -> io::Result<bool> {
+		if !cartridge.battery || cartridge.prg_ram == self.saved {
+			return Ok(false);
+		}
+		self.storage.store(&cartridge.prg_ram)?;
+		self.saved.clone_from(&cartridge.prg_ram);
+		Ok(true)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use super::super::Mirroring;
+
+	#[derive(Default)]
+	struct Stored {
+		data: Option<Vec<u8>>,
+		writes: usize,
+	}
+
+	impl SaveStorage for Stored {
+		fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+			Ok(self.data.clone())
+		}
+
+		fn store(&mut self, data: &[u8]) -> io::Result<()> {
+			self.data = Some(data.to_vec());
+			self.writes += 1;
+			Ok(())
+		}
+	}
+
+	fn cartridge(battery: bool) -> Cartridge {
+		Cartridge {
+			prg_rom: vec![0; 0x8000],
+			chr_rom: vec![0; 0x2000],
+			prg_ram: vec![0; 0x2000],
+			mapper: 0,
+			mirroring: Mirroring::Horizontal,
+			battery,
+			region: None,
+		}
+	}
+
+	#[test]
+	fn test_autosave() {
+		let mut cart = cartridge(true);
+		let storage = Stored { data: Some(vec![1, 2, 3]), writes: 0 };
+		let mut battery = Battery::attach(&mut cart, storage, 60).unwrap();
+		assert_eq!(cart.read(0x6002), Some(3));
+
+		cart.write(0x7000, 0x42);
+		for _ in 0..59 {
+			assert!(!battery.frame(&cart).unwrap());
+		}
+		assert!(battery.frame(&cart).unwrap());
+		assert_eq!(battery.storage.data.as_ref().unwrap()[0x1000], 0x42);
+
+		// Nothing changed since, so the next round and the final flush skip the write.
+		for _ in 0..60 {
+			battery.frame(&cart).unwrap();
+		}
+		assert!(!battery.flush(&cart).unwrap());
+		cart.write(0x6000, 0x99);
+		assert!(battery.flush(&cart).unwrap());
+		assert_eq!(battery.storage.writes, 2);
+	}
+
+	#[test]
+	fn test_without_battery() {
+		let mut cart = cartridge(false);
+		let storage = Stored { data: Some(vec![1, 2, 3]), writes: 0 };
+		let mut battery = Battery::attach(&mut cart, storage, 1).unwrap();
+		assert_eq!(cart.read(0x6000), Some(0));
+		cart.write(0x6000, 0x99);
+		assert!(!battery.frame(&cart).unwrap());
+		assert!(!battery.flush(&cart).unwrap());
+		assert_eq!(battery.storage.writes, 0);
+	}
+
+	#[test]
+	fn test_file_storage() {
+		let dir = std::env::temp_dir().join(format!("nes-rs-battery-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let mut storage = FileStorage::beside(&dir.join("game.nes"));
+		assert_eq!(storage.path, dir.join("game.sav"));
+		assert_eq!(storage.load().unwrap(), None);
+
+		let mut cart = cartridge(true);
+		let mut battery = Battery::attach(&mut cart, storage, 1).unwrap();
+		cart.write(0x6123, 0xAB);
+		battery.flush(&cart).unwrap();
+
+		let mut cart = cartridge(true);
+		Battery::attach(&mut cart, FileStorage::beside(&dir.join("game.nes")), 1).unwrap();
+		assert_eq!(cart.read(0x6123), Some(0xAB));
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
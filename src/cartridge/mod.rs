@@ -0,0 +1,255 @@
+mod battery;
+
+use std::fmt;
+
+use super::region::Region;
+
+pub use battery::*;
+
+const INES_MAGIC: &[u8; 4] = b"NES\x1A";
+const HEADER_LEN: usize = 16;
+const TRAINER_LEN: usize = 512;
+const PRG_BANK: usize = 16 * 1024;
+const CHR_BANK: usize = 8 * 1024;
+const PRG_RAM_BANK: usize = 8 * 1024;
+
+pub const PRG_RAM_START: u16 = 0x6000;
+pub const PRG_RAM_END: u16 = 0x7FFF;
+pub const PRG_ROM_START: u16 = 0x8000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mirroring {
+	Horizontal,
+	Vertical,
+	FourScreen,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CartridgeError {
+	BadMagic,
+	Truncated { expected: usize, found: usize },
+	UnsupportedMapper(u16),
+}
+
+impl fmt::Display for CartridgeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CartridgeError::BadMagic => write!(f, "not an iNES file"),
+			CartridgeError::Truncated { expected, found } => {
+				write!(f, "ROM is truncated, expected {} bytes but found {}", expected, found)
+			}
+			CartridgeError::UnsupportedMapper(mapper) => write!(f, "mapper {} is not supported", mapper),
+		}
+	}
+}
+
+impl std::error::Error for CartridgeError {}
+
+// The game pak as described by an iNES or NES 2.0 header. Only NROM boards are
+// wired up so far: up to 32KB of PRG-ROM at $8000, a 16KB one mirrored into both
+// halves, and optional PRG-RAM at $6000.
+#[derive(Clone)]
+pub struct Cartridge {
+	pub prg_rom: Vec<u8>,
+	pub chr_rom: Vec<u8>,
+	pub prg_ram: Vec<u8>,
+	pub mapper: u16,
+	pub mirroring: Mirroring,
+	// Whether the PRG-RAM keeps its contents with the power off.
+	pub battery: bool,
+	// The region the game was made for, None when the header doesn't say or the game
+	// runs on all of them.
+	pub region: Option<Region>,
+}
+
+impl Cartridge {
+	pub fn from_ines(data: &[u8]) -> Result<Cartridge, CartridgeError> {
+		if data.len() < HEADER_LEN || &data[0..4] != INES_MAGIC {
+			return Err(CartridgeError::BadMagic);
+		}
+		let header = &data[..HEADER_LEN];
+		let nes2 = header[7] & 0b0000_1100 == 0b0000_1000;
+
+		let mut mapper = (header[6] >> 4 | header[7] & 0xF0) as u16;
+		let (prg_len, chr_len, prg_ram_len, region);
+		if nes2 {
+			mapper |= ((header[8] & 0x0F) as u16) << 8;
+			prg_len = rom_size(header[4], header[9] & 0x0F, PRG_BANK);
+			chr_len = rom_size(header[5], header[9] >> 4, CHR_BANK);
+			// Volatile and battery-backed PRG-RAM are given separately, as shift counts.
+			prg_ram_len = ram_size(header[10] & 0x0F) + ram_size(header[10] >> 4);
+			region = Region::from_nes2(header[12]);
+		} else {
+			prg_len = Some(header[4] as usize * PRG_BANK);
+			chr_len = Some(header[5] as usize * CHR_BANK);
+			// Old headers leave this at zero to mean a single bank.
+			prg_ram_len = header[8].max(1) as usize * PRG_RAM_BANK;
+			// Hardly any dump sets the TV system bit, so a clear one says nothing.
+			region = (header[9] & 1 != 0).then_some(Region::Pal);
+		}
+
+		if mapper != 0 {
+			return Err(CartridgeError::UnsupportedMapper(mapper));
+		}
+
+		let prg_start = HEADER_LEN + if header[6] & 0b0100 != 0 { TRAINER_LEN } else { 0 };
+		// Sizes too big to add up can't be in the file either.
+		let chr_start = prg_len.and_then(|len| prg_start.checked_add(len));
+		let end = chr_start.zip(chr_len).and_then(|(start, len)| start.checked_add(len));
+		let (chr_start, expected) = match chr_start.zip(end) {
+			Some((chr_start, end)) if end <= data.len() => (chr_start, end),
+			_ => return Err(CartridgeError::Truncated { expected: end.unwrap_or(usize::MAX), found: data.len() }),
+		};
+
+		let mirroring = match header[6] & 0b1001 {
+			0b0000 => Mirroring::Horizontal,
+			0b0001 => Mirroring::Vertical,
+			_ => Mirroring::FourScreen,
+		};
+
+		Ok(Cartridge {
+			prg_rom: data[prg_start..chr_start].to_vec(),
+			chr_rom: data[chr_start..expected].to_vec(),
+			prg_ram: vec![0; prg_ram_len],
+			mapper,
+			mirroring,
+			battery: header[6] & 0b0010 != 0,
+			region,
+		})
+	}
+
+	// FNV-1a over the ROM contents, to tell games apart regardless of file name.
+	pub fn hash(&self) -> u64 {
+		self.prg_rom.iter().chain(self.chr_rom.iter()).fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+			(hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+		})
+	}
+
+	// Reads from $4020-$FFFF. None for addresses the board doesn't decode, which
+	// read as open bus.
+	pub fn read(&self, addr: u16) -> Option<u8> {
+		match addr {
+			PRG_RAM_START..=PRG_RAM_END if !self.prg_ram.is_empty() => {
+				Some(self.prg_ram[(addr - PRG_RAM_START) as usize % self.prg_ram.len()])
+			}
+			_ => self.prg_offset(addr).map(|offset| self.prg_rom[offset]),
+		}
+	}
+
+	// Where in the PRG-ROM a CPU address ends up, if it maps to the ROM at all.
+	pub fn prg_offset(&self, addr: u16) -> Option<usize> {
+		match addr {
+			PRG_ROM_START..=0xFFFF if !self.prg_rom.is_empty() => {
+				Some((addr - PRG_ROM_START) as usize % self.prg_rom.len())
+			}
+			_ => None,
+		}
+	}
+
+	pub fn write(&mut self, addr: u16, data: u8) {
+		if let PRG_RAM_START..=PRG_RAM_END = addr {
+			if !self.prg_ram.is_empty() {
+				let len = self.prg_ram.len();
+				self.prg_ram[(addr - PRG_RAM_START) as usize % len] = data;
+			}
+		}
+	}
+}
+
+// NES 2.0 sizes are counted in banks, unless the high nibble is all ones. Then
+// the low byte holds an exponent and a multiplier instead. None when the size
+// doesn't fit in a usize.
+fn rom_size(lsb: u8, msb: u8, bank: usize) -> Option<usize> {
+	if msb == 0x0F {
+		1usize.checked_shl((lsb >> 2) as u32)?.checked_mul((lsb & 0b11) as usize * 2 + 1)
+	} else {
+		((msb as usize) << 8 | lsb as usize).checked_mul(bank)
+	}
+}
+
+fn ram_size(shift: u8) -> usize {
+	match shift {
+		0 => 0,
+		shift => 64 << shift,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Builds an iNES image with the PRG banks filled with their own bank number.
+	fn ines(header: [u8; HEADER_LEN]) -> Vec<u8> {
+		let mut data = header.to_vec();
+		for bank in 0..header[4] {
+			data.extend(std::iter::repeat_n(bank, PRG_BANK));
+		}
+		data.extend(std::iter::repeat_n(0xCC, header[5] as usize * CHR_BANK));
+		data
+	}
+
+	#[test]
+	fn test_ines_header() {
+		let cart = Cartridge::from_ines(&ines(*b"NES\x1A\x01\x01\x03\0\0\0\0\0\0\0\0\0")).unwrap();
+		assert_eq!(cart.prg_rom.len(), PRG_BANK);
+		assert_eq!(cart.chr_rom.len(), CHR_BANK);
+		assert_eq!(cart.prg_ram.len(), PRG_RAM_BANK);
+		assert_eq!(cart.mirroring, Mirroring::Vertical);
+		assert!(cart.battery);
+		assert_eq!(cart.region, None);
+
+		// NES 2.0 with 2KB of volatile RAM and 8KB battery-backed.
+		let cart = Cartridge::from_ines(&ines(*b"NES\x1A\x02\x00\x00\x08\0\0\x75\0\0\0\0\0")).unwrap();
+		assert_eq!(cart.prg_rom.len(), 2 * PRG_BANK);
+		assert!(cart.chr_rom.is_empty());
+		assert_eq!(cart.prg_ram.len(), 2048 + 8192);
+		assert_eq!(cart.mirroring, Mirroring::Horizontal);
+		assert!(!cart.battery);
+		assert_eq!(cart.region, Some(Region::Ntsc));
+
+		let cart = Cartridge::from_ines(&ines(*b"NES\x1A\x01\0\0\x08\0\0\0\0\x03\0\0\0")).unwrap();
+		assert_eq!(cart.region, Some(Region::Dendy));
+		let cart = Cartridge::from_ines(&ines(*b"NES\x1A\x01\0\0\x08\0\0\0\0\x02\0\0\0")).unwrap();
+		assert_eq!(cart.region, None);
+		let cart = Cartridge::from_ines(&ines(*b"NES\x1A\x01\0\0\0\0\x01\0\0\0\0\0\0")).unwrap();
+		assert_eq!(cart.region, Some(Region::Pal));
+	}
+
+	#[test]
+	fn test_ines_errors() {
+		assert_eq!(Cartridge::from_ines(b"NES").err(), Some(CartridgeError::BadMagic));
+		let mut data = ines(*b"NES\x1A\x02\x01\x10\0\0\0\0\0\0\0\0\0");
+		assert_eq!(Cartridge::from_ines(&data).err(), Some(CartridgeError::UnsupportedMapper(1)));
+		data[6] = 0;
+		data.truncate(HEADER_LEN + PRG_BANK);
+		assert_eq!(Cartridge::from_ines(&data).err(), Some(CartridgeError::Truncated {
+			expected: HEADER_LEN + 2 * PRG_BANK + CHR_BANK,
+			found: HEADER_LEN + PRG_BANK,
+		}));
+
+		// NES 2.0 exponent sizes too big to add up, or even to compute.
+		let mut header = *b"NES\x1A\xFC\xFC\0\x08\0\xFF\0\0\0\0\0\0";
+		let truncated = Some(CartridgeError::Truncated { expected: usize::MAX, found: HEADER_LEN });
+		assert_eq!(Cartridge::from_ines(&header).err(), truncated);
+		header[4] = 0xFF;
+		assert_eq!(Cartridge::from_ines(&header).err(), truncated);
+	}
+
+	#[test]
+	fn test_nrom_mapping() {
+		let mut cart = Cartridge::from_ines(&ines(*b"NES\x1A\x01\x01\0\0\0\0\0\0\0\0\0\0")).unwrap();
+		cart.prg_rom[0x3FFC] = 0x34;
+		assert_eq!(cart.read(0xBFFC), Some(0x34));
+		assert_eq!(cart.read(0xFFFC), Some(0x34));
+
+		cart.write(0x6010, 0x55);
+		cart.write(0x8000, 0x66);
+		assert_eq!(cart.read(0x6010), Some(0x55));
+		assert_eq!(cart.read(0x8000), Some(0x00));
+		assert_eq!(cart.read(0x5000), None);
+
+		let cart = Cartridge::from_ines(&ines(*b"NES\x1A\x02\x01\0\0\0\0\0\0\0\0\0\0")).unwrap();
+		assert_eq!(cart.read(0x8000), Some(0));
+		assert_eq!(cart.read(0xC000), Some(1));
+	}
+}
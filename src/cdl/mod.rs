@@ -0,0 +1,264 @@
+use std::fmt;
+
+use super::bus::Bus;
+use super::cartridge::Cartridge;
+
+// Flags for each PRG-ROM byte, the same bits FCEUX uses.
+pub const CODE: u8 = 0b0000_0001;
+pub const DATA: u8 = 0b0000_0010;
+// Which 8KB slot of $8000-$FFFF the byte was last accessed through.
+pub const BANK_MASK: u8 = 0b0000_1100;
+// Code reached through JMP (ind), and data read through a (zp),Y style pointer.
+pub const INDIRECT_CODE: u8 = 0b0001_0000;
+pub const INDIRECT_DATA: u8 = 0b0010_0000;
+pub const PCM_DATA: u8 = 0b0100_0000;
+
+// Flags for each CHR-ROM byte.
+pub const CHR_RENDERED: u8 = 0b0000_0001;
+pub const CHR_READ: u8 = 0b0000_0010;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CdlError {
+	SizeMismatch { expected: usize, found: usize },
+}
+
+impl fmt::Display for CdlError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CdlError::SizeMismatch { expected, found } => {
+				write!(f, "code/data log is {} bytes but the ROM needs {}", found, expected)
+			}
+		}
+	}
+}
+
+impl std::error::Error for CdlError {}
+
+// Records which ROM bytes were executed and which were read as data, so a
+// disassembler can tell them apart. The file is one flag byte per PRG-ROM byte
+// followed by one per CHR-ROM byte, which is what FCEUX writes to .cdl files.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CodeDataLog {
+	pub prg: Vec<u8>,
+	pub chr: Vec<u8>,
+	// Set by an indirect jump, so the instruction it lands on is flagged.
+	indirect_jump: bool,
+}
+
+impl CodeDataLog {
+	pub fn new(cartridge: &Cartridge) -> Self {
+		CodeDataLog {
+			prg: vec![0; cartridge.prg_rom.len()],
+			chr: vec![0; cartridge.chr_rom.len()],
+			indirect_jump: false,
+		}
+	}
+
+	// Continues an earlier log, which has to be for a ROM of the same size.
+	pub fn from_bytes(cartridge: &Cartridge, data: &[u8]) -> Result<Self, CdlError> {
+		let mut log = CodeDataLog::new(cartridge);
+		let expected = log.prg.len() + log.chr.len();
+		if data.len() != expected {
+			return Err(CdlError::SizeMismatch { expected, found: data.len() });
+		}
+		let (prg, chr) = data.split_at(log.prg.len());
+		log.prg.copy_from_slice(prg);
+		log.chr.copy_from_slice(chr);
+		Ok(log)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		[&self.prg[..], &self.chr[..]].concat()
+	}
+
+	// Called by the CPU for every instruction it executes.
+	pub fn log_code(&mut self, bus: &Bus, address: u16, len: u8) {
+		let indirect = std::mem::take(&mut self.indirect_jump);
+		for i in 0..len as u16 {
+			let flags = if i == 0 && indirect { CODE | INDIRECT_CODE } else { CODE };
+			self.log_prg(bus, address.wrapping_add(i), flags);
+		}
+	}
+
+	pub fn log_indirect_jump(&mut self) {
+		self.indirect_jump = true;
+	}
+
+	// Called by the CPU for operands and other data it reads.
+	pub fn log_data(&mut self, bus: &Bus, address: u16, indirect: bool) {
+		self.log_prg(bus, address, if indirect { DATA | INDIRECT_DATA } else { DATA });
+	}
+
+	// For DMC sample fetches, once there is an APU to make them.
+	pub fn log_pcm(&mut self, bus: &Bus, address: u16) {
+		self.log_prg(bus, address, DATA | PCM_DATA);
+	}
+
+	// For the PPU, with the offset into CHR-ROM. Pattern fetches while rendering are
+	// rendered, $2007 reads are read.
+	pub fn log_chr(&mut self, offset: usize, flags: u8) {
+		if let Some(byte) = self.chr.get_mut(offset) {
+			*byte |= flags;
+		}
+	}
+
+	fn log_prg(&mut self, bus: &Bus, address: u16, flags: u8) {
+		let offset = bus.cartridge.as_ref().and_then(|cart| cart.prg_offset(address));
+		if let Some(byte) = offset.and_then(|offset| self.prg.get_mut(offset)) {
+			*byte = *byte & !BANK_MASK | flags | ((address >> 13 & 3) as u8) << 2;
+		}
+	}
+
+	pub fn coverage(&self) -> Coverage {
+		let count = |log: &[u8], flags: u8| log.iter().filter(|&&byte| byte & flags != 0).count();
+		Coverage {
+			prg_size: self.prg.len(),
+			code: count(&self.prg, CODE),
+			data: count(&self.prg, DATA),
+			prg_logged: count(&self.prg, CODE | DATA),
+			chr_size: self.chr.len(),
+			chr_rendered: count(&self.chr, CHR_RENDERED),
+			chr_read: count(&self.chr, CHR_READ),
+			chr_logged: count(&self.chr, CHR_RENDERED | CHR_READ),
+		}
+	}
+}
+
+// How much of the ROM the log has seen. Bytes used both as code and as data count
+// towards both.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Coverage {
+	pub prg_size: usize,
+	pub code: usize,
+	pub data: usize,
+	pub prg_logged: usize,
+	pub chr_size: usize,
+	pub chr_rendered: usize,
+	pub chr_read: usize,
+	pub chr_logged: usize,
+}
+
+impl fmt::Display for Coverage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let percent = |count: usize, size: usize| match size {
+			0 => 0.0,
+			size => count as f64 * 100.0 / size as f64,
+		};
+		writeln!(
+			f,
+			"PRG: {} code, {} data, {} of {} bytes logged ({:.1}%)",
+			self.code,
+			self.data,
+			self.prg_logged,
+			self.prg_size,
+			percent(self.prg_logged, self.prg_size),
+		)?;
+		write!(
+			f,
+			"CHR: {} rendered, {} read, {} of {} bytes logged ({:.1}%)",
+			self.chr_rendered,
+			self.chr_read,
+			self.chr_logged,
+			self.chr_size,
+			percent(self.chr_logged, self.chr_size),
+		)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cartridge::Mirroring;
+	use crate::cpu::Cpu;
+
+	// Runs a program from a 16KB NROM cartridge, mirrored at $8000 and $C000.
+	fn machine(program: &[&str]) -> Cpu {
+		let mut assembler = crate::assembler::Assembler::new(crate::cpu::CpuVariant::Ricoh2A03);
+		assembler.origin = 0xC000;
+		let program = assembler.assemble(&program.join("\n")).unwrap();
+		let mut prg_rom = vec![0; 0x4000];
+		prg_rom[..program.bytes.len()].copy_from_slice(&program.bytes);
+		let cartridge = Cartridge {
+			prg_rom,
+			chr_rom: vec![0; 0x2000],
+			prg_ram: Vec::new(),
+			mapper: 0,
+			mirroring: Mirroring::Vertical,
+			battery: false,
+			region: None,
+		};
+
+		let mut cpu = Cpu::new();
+		cpu.code_log = Some(CodeDataLog::new(&cartridge));
+		cpu.bus.insert(cartridge);
+		cpu.reset();
+		cpu.program_counter = 0xC000;
+		cpu
+	}
+
+	#[test]
+	fn test_code_and_data() {
+		let mut cpu = machine(&[
+			"        lda table,x",   // $C000
+			"        sta $00",       // $C003
+			"        lda #>table",   // $C005
+			"        sta $01",       // $C007
+			"        ldy #$01",      // $C009
+			"        lda ($00),y",   // $C00B
+			"        jmp ($C020)",   // $C00D
+			"table:  .byte $10, $14, $C0, $00", // $C010
+			".org $C014",
+			"target: inx",           // $C014
+			".org $C020",
+			"        .word target",
+		]);
+		for _ in 0..8 {
+			cpu.step().unwrap();
+		}
+		assert_eq!(cpu.program_counter, 0xC015);
+
+		let log = cpu.code_log.take().unwrap();
+		let slot = 2 << 2;
+		assert_eq!(&log.prg[0x0000..0x0003], &[CODE | slot; 3]);
+		assert_eq!(log.prg[0x0010], DATA | slot);
+		assert_eq!(log.prg[0x0011], DATA | INDIRECT_DATA | slot);
+		assert_eq!(log.prg[0x0012], 0);
+		assert_eq!(log.prg[0x0014], CODE | INDIRECT_CODE | slot);
+		assert_eq!(&log.prg[0x0020..0x0022], &[DATA | slot; 2]);
+
+		assert_eq!(log.coverage(), Coverage {
+			prg_size: 0x4000,
+			code: 17,
+			data: 4,
+			prg_logged: 21,
+			chr_size: 0x2000,
+			chr_rendered: 0,
+			chr_read: 0,
+			chr_logged: 0,
+		});
+		assert_eq!(log.coverage().to_string(), "\
+PRG: 17 code, 4 data, 21 of 16384 bytes logged (0.1%)
+CHR: 0 rendered, 0 read, 0 of 8192 bytes logged (0.0%)");
+	}
+
+	#[test]
+	fn test_file_layout() {
+		let cpu = machine(&["nop"]);
+		let cartridge = cpu.bus.cartridge.as_ref().unwrap();
+		let mut log = CodeDataLog::new(cartridge);
+		log.log_data(&cpu.bus, 0x8001, false);
+		log.log_code(&cpu.bus, 0xC001, 1);
+		log.log_chr(0x10, CHR_RENDERED);
+
+		let bytes = log.to_bytes();
+		assert_eq!(bytes.len(), 0x4000 + 0x2000);
+		// The last access decides the bank bits.
+		assert_eq!(bytes[1], CODE | DATA | 2 << 2);
+		assert_eq!(bytes[0x4010], CHR_RENDERED);
+		assert_eq!(CodeDataLog::from_bytes(cartridge, &bytes), Ok(log));
+		assert_eq!(CodeDataLog::from_bytes(cartridge, &bytes[1..]), Err(CdlError::SizeMismatch {
+			expected: 0x6000,
+			found: 0x5FFF,
+		}));
+	}
+}
@@ -0,0 +1,316 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Game Genie letters, in the order of the nibble each one stands for.
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CheatFormat {
+	GameGenie,
+	ProActionReplay,
+	Raw,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CheatError {
+	Invalid { code: String },
+	Syntax { line: usize, text: String },
+}
+
+impl fmt::Display for CheatError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CheatError::Invalid { code } => write!(f, "\"{}\" is not a cheat code", code),
+			CheatError::Syntax { line, text } => write!(f, "line {}: cannot parse \"{}\"", line, text),
+		}
+	}
+}
+
+impl std::error::Error for CheatError {}
+
+// Every supported format boils down to the same thing: reads from an address
+// return the value instead, as long as the byte that's really there matches the
+// compare value, if there is one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Cheat {
+	pub code: String,
+	pub name: String,
+	pub enabled: bool,
+	pub format: CheatFormat,
+	pub address: u16,
+	pub value: u8,
+	pub compare: Option<u8>,
+}
+
+impl Cheat {
+	// Picks the format from the code itself. Codes made up of only A and E are valid
+	// both as Game Genie and as hex and are taken as Game Genie codes.
+	pub fn parse(code: &str) -> Result<Cheat, CheatError> {
+		let code = code.trim();
+		if code.contains(':') {
+			Cheat::raw(code)
+		} else if code.bytes().all(|c| GAME_GENIE_LETTERS.contains(&c.to_ascii_uppercase())) {
+			Cheat::game_genie(code)
+		} else {
+			Cheat::pro_action_replay(code)
+		}
+	}
+
+	// Six letters patch a ROM byte, eight letters only when it holds the compare value.
+	pub fn game_genie(code: &str) -> Result<Cheat, CheatError> {
+		let code = code.trim().to_ascii_uppercase();
+		let n: Vec<u16> = code
+			.bytes()
+			.map(|c| GAME_GENIE_LETTERS.iter().position(|&letter| letter == c).map(|n| n as u16))
+			.collect::<Option<_>>()
+			.ok_or_else(|| invalid(&code))?;
+		if n.len() != 6 && n.len() != 8 {
+			return Err(invalid(&code));
+		}
+
+		let address = 0x8000
+			| (n[3] & 7) << 12
+			| (n[5] & 7) << 8
+			| (n[4] & 8) << 8
+			| (n[2] & 7) << 4
+			| (n[1] & 8) << 4
+			| (n[4] & 7)
+			| (n[3] & 8);
+		// The last letter holds the top bit of the value, or of the compare byte for
+		// the long form.
+		let last = n[n.len() - 1];
+		let value = (n[1] & 7) << 4 | (n[0] & 8) << 4 | (n[0] & 7) | (last & 8);
+		let compare = match n.len() {
+			8 => Some(((n[7] & 7) << 4 | (n[6] & 8) << 4 | (n[6] & 7) | (n[5] & 8)) as u8),
+			_ => None,
+		};
+		Ok(Cheat::new(code, CheatFormat::GameGenie, address, value as u8, compare))
+	}
+
+	// Freezes a RAM byte. Written as AAAAVV in hex, with the eight digit form
+	// 00AAAAVV some code lists use accepted too.
+	pub fn pro_action_replay(code: &str) -> Result<Cheat, CheatError> {
+		let code = code.trim().to_ascii_uppercase();
+		if !code.bytes().all(|c| c.is_ascii_hexdigit()) {
+			return Err(invalid(&code));
+		}
+		let digits = match code.len() {
+			6 => &code[..],
+			8 if code.starts_with("00") => &code[2..],
+			_ => return Err(invalid(&code)),
+		};
+		let (address, value) = match (u16::from_str_radix(&digits[..4], 16), u8::from_str_radix(&digits[4..], 16)) {
+			(Ok(address), Ok(value)) => (address, value),
+			_ => return Err(invalid(&code)),
+		};
+		Ok(Cheat::new(code, CheatFormat::ProActionReplay, address, value, None))
+	}
+
+	// addr:value or addr:value:compare, all in hex.
+	pub fn raw(code: &str) -> Result<Cheat, CheatError> {
+		let code = code.trim().to_ascii_uppercase();
+		let fields: Vec<&str> = code.split(':').map(|field| field.trim().trim_start_matches('$')).collect();
+		let byte = |field: &str| u8::from_str_radix(field, 16).ok();
+		let parsed = match fields[..] {
+			[address, value] => u16::from_str_radix(address, 16).ok().zip(byte(value)).map(|(a, v)| (a, v, None)),
+			[address, value, compare] => u16::from_str_radix(address, 16)
+				.ok()
+				.zip(byte(value))
+				.zip(byte(compare))
+				.map(|((a, v), c)| (a, v, Some(c))),
+			_ => None,
+		};
+		let (address, value, compare) = parsed.ok_or_else(|| invalid(&code))?;
+		Ok(Cheat::new(code, CheatFormat::Raw, address, value, compare))
+	}
+
+	fn new(code: String, format: CheatFormat, address: u16, value: u8, compare: Option<u8>) -> Cheat {
+		Cheat { code, name: String::new(), enabled: true, format, address, value, compare }
+	}
+}
+
+fn invalid(code: &str) -> CheatError {
+	CheatError::Invalid { code: code.to_string() }
+}
+
+// The cheats the bus applies to every read.
+#[derive(Clone, Default)]
+pub struct Cheats {
+	list: Vec<Cheat>,
+}
+
+impl Cheats {
+	pub fn new() -> Self {
+		Cheats { list: Vec::new() }
+	}
+
+	pub fn list(&self) -> &[Cheat] {
+		&self.list
+	}
+
+	// Returns the index of the new cheat.
+	pub fn add(&mut self, code: &str, name: &str) -> Result<usize, CheatError> {
+		let mut cheat = Cheat::parse(code)?;
+		cheat.name = name.to_string();
+		self.list.push(cheat);
+		Ok(self.list.len() - 1)
+	}
+
+	pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+		match index < self.list.len() {
+			true => Some(self.list.remove(index)),
+			false => None,
+		}
+	}
+
+	// Returns false if there is no such cheat.
+	pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+		match self.list.get_mut(index) {
+			Some(cheat) => {
+				cheat.enabled = enabled;
+				true
+			}
+			None => false,
+		}
+	}
+
+	// What a read from the address returns with the cheats applied.
+	pub fn apply(&self, address: u16, data: u8) -> u8 {
+		self.list
+			.iter()
+			.filter(|cheat| cheat.enabled && cheat.address == address)
+			.find(|cheat| cheat.compare.is_none_or(|compare| compare == data))
+			.map_or(data, |cheat| cheat.value)
+	}
+
+	// One cheat per line, a + or - for whether it's enabled, the code and the name.
+	// Blank lines and lines starting with # are skipped.
+	pub fn parse(text: &str) -> Result<Cheats, CheatError> {
+		let mut cheats = Cheats::new();
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let syntax = || CheatError::Syntax { line: number + 1, text: line.to_string() };
+			let enabled = match line.as_bytes()[0] {
+				b'+' => true,
+				b'-' => false,
+				_ => return Err(syntax()),
+			};
+			let (code, name) = line[1..].trim_start().split_once(' ').unwrap_or((&line[1..], ""));
+			let index = cheats.add(code, name.trim()).map_err(|_| syntax())?;
+			cheats.list[index].enabled = enabled;
+		}
+		Ok(cheats)
+	}
+
+	pub fn to_text(&self) -> String {
+		let mut text = String::new();
+		for cheat in self.list.iter() {
+			let enabled = if cheat.enabled { '+' } else { '-' };
+			match cheat.name.is_empty() {
+				true => text.push_str(&format!("{}{}\n", enabled, cheat.code)),
+				false => text.push_str(&format!("{}{} {}\n", enabled, cheat.code, cheat.name)),
+			}
+		}
+		text
+	}
+
+	// Where the cheats for a ROM live, named after Cartridge::hash so renaming the
+	// ROM doesn't lose them.
+	pub fn path(dir: &Path, rom_hash: u64) -> PathBuf {
+		dir.join(format!("{:016x}.cht", rom_hash))
+	}
+
+	// A missing file is an empty list.
+	pub fn load(path: &Path) -> io::Result<Cheats> {
+		match fs::read_to_string(path) {
+			Ok(text) => Cheats::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Cheats::new()),
+			Err(err) => Err(err),
+		}
+	}
+
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		fs::write(path, self.to_text())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cpu::{Cpu, Memory};
+
+	#[test]
+	fn test_game_genie() {
+		// Infinite lives in Super Mario Bros.
+		let cheat = Cheat::parse("sxiopo").unwrap();
+		assert_eq!(cheat.format, CheatFormat::GameGenie);
+		assert_eq!(cheat.code, "SXIOPO");
+		assert_eq!((cheat.address, cheat.value, cheat.compare), (0x91D9, 0xAD, None));
+
+		let cheat = Cheat::parse("YEUZUGAA").unwrap();
+		assert_eq!((cheat.address, cheat.value, cheat.compare), (0xACB3, 0x07, Some(0x00)));
+
+		assert!(Cheat::game_genie("SXIOP").is_err());
+		assert!(Cheat::game_genie("SXIOPB").is_err());
+	}
+
+	#[test]
+	fn test_other_formats() {
+		let cheat = Cheat::parse("07540F").unwrap();
+		assert_eq!(cheat.format, CheatFormat::ProActionReplay);
+		assert_eq!((cheat.address, cheat.value, cheat.compare), (0x0754, 0x0F, None));
+		assert_eq!(Cheat::parse("0007540F").unwrap().address, 0x0754);
+		assert!(Cheat::parse("1107540F").is_err());
+		assert_eq!(Cheat::parse("123é5"), Err(CheatError::Invalid { code: "123é5".to_string() }));
+		assert!(Cheat::parse("+12345").is_err());
+		assert!(Cheats::parse("+123é5 x").is_err());
+
+		let cheat = Cheat::parse("$075A:09").unwrap();
+		assert_eq!((cheat.format, cheat.address, cheat.value, cheat.compare), (CheatFormat::Raw, 0x075A, 0x09, None));
+		let cheat = Cheat::parse("c123:ea:20").unwrap();
+		assert_eq!((cheat.address, cheat.value, cheat.compare), (0xC123, 0xEA, Some(0x20)));
+		assert_eq!(Cheat::parse("12:34:56:78"), Err(CheatError::Invalid { code: "12:34:56:78".to_string() }));
+	}
+
+	#[test]
+	fn test_bus_reads() {
+		let mut cpu = Cpu::new();
+		cpu.write(0x0010, 0x20);
+		cpu.write(0x0011, 0x30);
+		cpu.bus.cheats.add("0010:99", "").unwrap();
+		cpu.bus.cheats.add("0011:77:31", "").unwrap();
+		assert_eq!(cpu.read(0x0010), 0x99);
+		// The compare byte doesn't match what's in memory.
+		assert_eq!(cpu.read(0x0011), 0x30);
+		cpu.write(0x0011, 0x31);
+		assert_eq!(cpu.read(0x0011), 0x77);
+
+		// The cheat only covers reads, the RAM itself is untouched.
+		cpu.bus.cheats.set_enabled(0, false);
+		assert_eq!(cpu.read(0x0010), 0x20);
+	}
+
+	#[test]
+	fn test_cheat_file() {
+		let text = "# Super Mario Bros.\n+SXIOPO Infinite lives\n-075A:09\n";
+		let mut cheats = Cheats::parse(text).unwrap();
+		assert_eq!(cheats.list().len(), 2);
+		assert_eq!(cheats.list()[0].name, "Infinite lives");
+		assert!(!cheats.list()[1].enabled);
+		assert_eq!(cheats.to_text(), "+SXIOPO Infinite lives\n-075A:09\n");
+		assert_eq!(Cheats::parse("SXIOPO").err(), Some(CheatError::Syntax { line: 1, text: "SXIOPO".to_string() }));
+
+		let dir = std::env::temp_dir();
+		let path = Cheats::path(&dir, 0x0123_4567_89ab_cdef ^ std::process::id() as u64);
+		assert_eq!(Cheats::load(&path).unwrap().list().len(), 0);
+		cheats.remove(1);
+		cheats.save(&path).unwrap();
+		assert_eq!(Cheats::load(&path).unwrap().to_text(), "+SXIOPO Infinite lives\n");
+		fs::remove_file(&path).unwrap();
+	}
+}
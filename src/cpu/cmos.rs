@@ -0,0 +1,269 @@
+use super::AddressingMode;
+use super::AddressingMode::*;
+use super::Category;
+use super::Category::*;
+use super::Cpu;
+use super::CpuVariant;
+use super::ops::{decode_table, patch_table, OpCodeDef, DEFINITIONS};
+
+// The WDC 65C02 keeps the official NMOS opcodes and fills the unofficial ones with
+// new instructions and NOPs of fixed length. Only the slots that differ are listed.
+pub static CMOS_OPCODES: [OpCodeDef; 256] = patch_table(decode_table(DEFINITIONS), CMOS_DEFINITIONS);
+
+crate::map!(CMOS_DEFINITIONS;
+	BRA,	0x80, "BRA", 2, 3, Relative,	Branch,	true,	Cpu::bra,
+	PHX,	0xDA, "PHX", 1, 3, Implied,	Stack,	true,	Cpu::phx,
+	PHY,	0x5A, "PHY", 1, 3, Implied,	Stack,	true,	Cpu::phy,
+	PLX,	0xFA, "PLX", 1, 4, Implied,	Stack,	true,	Cpu::plx,
+	PLY,	0x7A, "PLY", 1, 4, Implied,	Stack,	true,	Cpu::ply,
+	INC_A,	0x1A, "INC", 1, 2, Accumulator,	Internal,	true,	Cpu::inc_a,
+	DEC_A,	0x3A, "DEC", 1, 2, Accumulator,	Internal,	true,	Cpu::dec_a,
+	WAI,	0xCB, "WAI", 1, 3, Implied,	Internal,	true,	Cpu::wai,
+	STP,	0xDB, "STP", 1, 3, Implied,	Jam,	true,	Cpu::nop,
+
+	JMP2,	0x6C, "JMP", 3, 6, Indirect,	Jump,	true,	Cpu::jmp_cmos,
+	JMP3,	0x7C, "JMP", 3, 6, AbsoluteIndirectX,	Jump,	true,	Cpu::jmp_indexed,
+
+	ASL5,	0x1E, "ASL", 3, 6, AbsoluteX,	ReadModifyWrite,	true,	Cpu::asl_m,
+	ROL_M4,	0x3E, "ROL", 3, 6, AbsoluteX,	ReadModifyWrite,	true,	Cpu::rol_m,
+	LSR_M4,	0x5E, "LSR", 3, 6, AbsoluteX,	ReadModifyWrite,	true,	Cpu::lsr_m,
+	ROR_M4,	0x7E, "ROR", 3, 6, AbsoluteX,	ReadModifyWrite,	true,	Cpu::ror_m,
+
+	BIT3,	0x89, "BIT", 2, 2, Immediate,	Read,	true,	Cpu::bit_immediate,
+	BIT4,	0x34, "BIT", 2, 4, ZeroPageX,	Read,	true,	Cpu::bit,
+	BIT5,	0x3C, "BIT", 3, 4, AbsoluteX,	Read,	true,	Cpu::bit,
+
+	ORA9,	0x12, "ORA", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::ora,
+	AND9,	0x32, "AND", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::and,
+	EOR9,	0x52, "EOR", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::eor,
+	ADC9,	0x72, "ADC", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::adc,
+	STA8,	0x92, "STA", 2, 5, ZeroPageIndirect,	Write,	true,	Cpu::sta,
+	LDA9,	0xB2, "LDA", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::lda,
+	CMP9,	0xD2, "CMP", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::cmp,
+	SBC10,	0xF2, "SBC", 2, 5, ZeroPageIndirect,	Read,	true,	Cpu::sbc,
+
+	STZ1,	0x64, "STZ", 2, 3, ZeroPage,	Write,	true,	Cpu::stz,
+	STZ2,	0x74, "STZ", 2, 4, ZeroPageX,	Write,	true,	Cpu::stz,
+	STZ3,	0x9C, "STZ", 3, 4, Absolute,	Write,	true,	Cpu::stz,
+	STZ4,	0x9E, "STZ", 3, 5, AbsoluteX,	Write,	true,	Cpu::stz,
+
+	TSB1,	0x04, "TSB", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::tsb,
+	TSB2,	0x0C, "TSB", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::tsb,
+	TRB1,	0x14, "TRB", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::trb,
+	TRB2,	0x1C, "TRB", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::trb,
+
+	RMB0,	0x07, "RMB0", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<0>,
+	RMB1,	0x17, "RMB1", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<1>,
+	RMB2,	0x27, "RMB2", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<2>,
+	RMB3,	0x37, "RMB3", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<3>,
+	RMB4,	0x47, "RMB4", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<4>,
+	RMB5,	0x57, "RMB5", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<5>,
+	RMB6,	0x67, "RMB6", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<6>,
+	RMB7,	0x77, "RMB7", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rmb::<7>,
+
+	SMB0,	0x87, "SMB0", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<0>,
+	SMB1,	0x97, "SMB1", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<1>,
+	SMB2,	0xA7, "SMB2", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<2>,
+	SMB3,	0xB7, "SMB3", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<3>,
+	SMB4,	0xC7, "SMB4", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<4>,
+	SMB5,	0xD7, "SMB5", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<5>,
+	SMB6,	0xE7, "SMB6", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<6>,
+	SMB7,	0xF7, "SMB7", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::smb::<7>,
+
+	BBR0,	0x0F, "BBR0", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<0>,
+	BBR1,	0x1F, "BBR1", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<1>,
+	BBR2,	0x2F, "BBR2", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<2>,
+	BBR3,	0x3F, "BBR3", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<3>,
+	BBR4,	0x4F, "BBR4", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<4>,
+	BBR5,	0x5F, "BBR5", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<5>,
+	BBR6,	0x6F, "BBR6", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<6>,
+	BBR7,	0x7F, "BBR7", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbr::<7>,
+
+	BBS0,	0x8F, "BBS0", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<0>,
+	BBS1,	0x9F, "BBS1", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<1>,
+	BBS2,	0xAF, "BBS2", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<2>,
+	BBS3,	0xBF, "BBS3", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<3>,
+	BBS4,	0xCF, "BBS4", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<4>,
+	BBS5,	0xDF, "BBS5", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<5>,
+	BBS6,	0xEF, "BBS6", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<6>,
+	BBS7,	0xFF, "BBS7", 3, 5, ZeroPageRelative,	Branch,	true,	Cpu::bbs::<7>;
+
+	NOP_IMMEDIATE,	[0x02, 0x22, 0x42, 0x62, 0x82, 0xC2, 0xE2], "NOP", 2, 2, Immediate,	Read,	false,	Cpu::nop,
+	NOP_ZERO_PAGE,	[0x44], "NOP", 2, 3, ZeroPage,	Read,	false,	Cpu::nop,
+	NOP_ZERO_PAGE_X,	[0x54, 0xD4, 0xF4], "NOP", 2, 4, ZeroPageX,	Read,	false,	Cpu::nop,
+	NOP_LONG,	[0x5C], "NOP", 3, 8, Absolute,	Read,	false,	Cpu::nop_long,
+	NOP_ABSOLUTE,	[0xDC, 0xFC], "NOP", 3, 4, Absolute,	Read,	false,	Cpu::nop,
+	NOP_SINGLE,	[
+		0x03, 0x13, 0x23, 0x33, 0x43, 0x53, 0x63, 0x73, 0x83, 0x93, 0xA3, 0xB3, 0xC3, 0xD3, 0xE3, 0xF3,
+		0x0B, 0x1B, 0x2B, 0x3B, 0x4B, 0x5B, 0x6B, 0x7B, 0x8B, 0x9B, 0xAB, 0xBB, 0xEB, 0xFB
+	], "NOP", 1, 1, Implied,	Internal,	false,	Cpu::nop_single
+);
+
+impl Cpu {
+	// BRA - Branch Always
+	// Adds the relative displacement to the program counter to cause a branch to a new location.
+	pub fn bra(&mut self, _mode: &AddressingMode) {
+		self.branch_if(true);
+	}
+
+	// PHX - Push X Register
+	// Pushes a copy of the X register on to the stack.
+	pub fn phx(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.push(self.register_x);
+	}
+
+	// PHY - Push Y Register
+	// Pushes a copy of the Y register on to the stack.
+	pub fn phy(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.push(self.register_y);
+	}
+
+	// PLX - Pull X Register
+	// Pulls an 8 bit value from the stack and into the X register. The zero and negative flags are set as appropriate.
+	pub fn plx(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.peek_stack();
+		self.register_x = self.pop();
+		self.set_zero_neg_flags(self.register_x);
+	}
+
+	// PLY - Pull Y Register
+	// Pulls an 8 bit value from the stack and into the Y register. The zero and negative flags are set as appropriate.
+	pub fn ply(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.peek_stack();
+		self.register_y = self.pop();
+		self.set_zero_neg_flags(self.register_y);
+	}
+
+	// INC - Increment Accumulator
+	// A,Z,N = A+1
+	pub fn inc_a(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.register_a = self.register_a.wrapping_add(1);
+		self.set_zero_neg_flags(self.register_a);
+	}
+
+	// DEC - Decrement Accumulator
+	// A,Z,N = A-1
+	pub fn dec_a(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.register_a = self.register_a.wrapping_sub(1);
+		self.set_zero_neg_flags(self.register_a);
+	}
+
+	// WAI - Wait for Interrupt
+	// Stops executing instructions until an interrupt or reset comes in.
+	pub fn wai(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.discard_operand();
+		self.waiting = true;
+	}
+
+	// JMP - Jump
+	// The 65C02 fixed the page wrap bug of the indirect jump, at the cost of an extra cycle.
+	pub fn jmp_cmos(cpu: &mut Cpu, _mode: &AddressingMode) {
+		let addr = cpu.fetch_u16();
+		cpu.bus_read(cpu.program_counter.wrapping_sub(1));
+		let lo = cpu.data_read(addr, false) as u16;
+		let hi = cpu.data_read(addr.wrapping_add(1), false) as u16;
+		if let Some(log) = &mut cpu.code_log {
+			log.log_indirect_jump();
+		}
+		cpu.program_counter = hi << 8 | lo;
+	}
+
+	// JMP - Jump
+	// Jumps to the address stored at the operand plus the X register.
+	pub fn jmp_indexed(cpu: &mut Cpu, _mode: &AddressingMode) {
+		let addr = cpu.fetch_u16().wrapping_add(cpu.register_x as u16);
+		cpu.bus_read(cpu.program_counter.wrapping_sub(1));
+		let lo = cpu.data_read(addr, false) as u16;
+		let hi = cpu.data_read(addr.wrapping_add(1), false) as u16;
+		if let Some(log) = &mut cpu.code_log {
+			log.log_indirect_jump();
+		}
+		cpu.program_counter = hi << 8 | lo;
+	}
+
+	// BIT - Bit Test
+	// The immediate form only affects the zero flag.
+	pub fn bit_immediate(&mut self, mode: &AddressingMode) {
+		let data = self.read_operand(mode);
+		self.status.set_zero(self.register_a & data == 0);
+	}
+
+	// STZ - Store Zero
+	// M = 0
+	pub fn stz(&mut self, mode: &AddressingMode) {
+		self.write_operand(mode, 0);
+	}
+
+	// TSB - Test and Set Bits
+	// Z = A & M, M = M | A
+	pub fn tsb(&mut self, mode: &AddressingMode) {
+		self.read_modify_write(mode, |cpu, data| {
+			cpu.status.set_zero(cpu.register_a & data == 0);
+			data | cpu.register_a
+		});
+	}
+
+	// TRB - Test and Reset Bits
+	// Z = A & M, M = M & !A
+	pub fn trb(&mut self, mode: &AddressingMode) {
+		self.read_modify_write(mode, |cpu, data| {
+			cpu.status.set_zero(cpu.register_a & data == 0);
+			data & !cpu.register_a
+		});
+	}
+
+	// RMB - Reset Memory Bit
+	// Clears a single bit of a zero page location.
+	pub fn rmb<const BIT: u8>(&mut self, mode: &AddressingMode) {
+		self.read_modify_write(mode, |_, data| data & !(1 << BIT));
+	}
+
+	// SMB - Set Memory Bit
+	// Sets a single bit of a zero page location.
+	pub fn smb<const BIT: u8>(&mut self, mode: &AddressingMode) {
+		self.read_modify_write(mode, |_, data| data | 1 << BIT);
+	}
+
+	// BBR - Branch on Bit Reset
+	// Branches if a single bit of a zero page location is clear.
+	pub fn bbr<const BIT: u8>(&mut self, _mode: &AddressingMode) {
+		let data = self.read_zero_page_twice();
+		self.branch_if(data & 1 << BIT == 0);
+	}
+
+	// BBS - Branch on Bit Set
+	// Branches if a single bit of a zero page location is set.
+	pub fn bbs<const BIT: u8>(&mut self, _mode: &AddressingMode) {
+		let data = self.read_zero_page_twice();
+		self.branch_if(data & 1 << BIT != 0);
+	}
+
+	fn read_zero_page_twice(&mut self) -> u8 {
+		let addr = self.fetch() as u16;
+		let data = self.bus_read(addr);
+		self.bus_read(addr);
+		data
+	}
+
+	// The reserved single byte opcodes finish in a single cycle.
+	pub fn nop_single(&mut self, _mode: &AddressingMode) {}
+
+	// $5C reads its absolute operand and then keeps the bus busy for a few more cycles.
+	pub fn nop_long(&mut self, mode: &AddressingMode) {
+		let addr = self.get_operand_address(mode, Category::Read);
+		for _ in 0..5 {
+			self.bus_read(addr);
+		}
+	}
+
+	pub(super) fn is_cmos(&self) -> bool {
+		self.variant == CpuVariant::Wdc65C02
+	}
+}
@@ -0,0 +1,25 @@
+// Every bus access the CPU makes takes exactly one cycle, dummy accesses included.
+// Recording them gives the address, value and direction of every cycle, the way
+// the SingleStepTests suite lists them, see single_step.rs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CycleKind {
+	Read,
+	Write,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BusCycle {
+	pub address: u16,
+	pub value: u8,
+	pub kind: CycleKind,
+}
+
+impl BusCycle {
+	pub fn read(address: u16, value: u8) -> Self {
+		BusCycle { address, value, kind: CycleKind::Read }
+	}
+
+	pub fn write(address: u16, value: u8) -> Self {
+		BusCycle { address, value, kind: CycleKind::Write }
+	}
+}
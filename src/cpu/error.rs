@@ -0,0 +1,33 @@
+use std::fmt;
+
+// What the CPU should do when it runs into an unofficial opcode or a KIL.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UndefinedPolicy {
+	// Execute it the way the hardware does, KIL jams the CPU until reset.
+	Emulate,
+	// Refuse to execute it and report a CpuError from step.
+	Error,
+	// Panic with the CpuError, handy for test harnesses.
+	Panic,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CpuError {
+	Jammed { opcode: u8, address: u16 },
+	UndefinedOpcode { opcode: u8, address: u16 },
+}
+
+impl fmt::Display for CpuError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CpuError::Jammed { opcode, address } => {
+				write!(f, "CPU jammed by opcode ${:02X} at ${:04X}", opcode, address)
+			}
+			CpuError::UndefinedOpcode { opcode, address } => {
+				write!(f, "undefined opcode ${:02X} at ${:04X}", opcode, address)
+			}
+		}
+	}
+}
+
+impl std::error::Error for CpuError {}
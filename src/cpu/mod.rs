@@ -1,15 +1,34 @@
 mod status;
+mod error;
+mod cycle;
 pub mod ops;
+pub mod cmos;
+#[cfg(test)]
+mod single_step;
 
-use status::*;
+pub use status::*;
+pub use error::*;
+pub use cycle::*;
 
 pub use super::memory::Memory;
 pub use super::bus::Bus;
+use super::bus::AccessKind;
+pub use super::state::StateError;
+use super::cdl::CodeDataLog;
+use super::state::{Snapshot, StateReader, StateWriter};
 pub use ops::*;
+pub use cmos::CMOS_OPCODES;
 
 pub const STACK: u16 = 0x0100;
 pub const DEFAULT_STACK: u8 = 0xfd;
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
+// The value ANE and LXA OR the accumulator with varies between chips and even
+// with temperature. 0xEE matches the most commonly observed behaviour.
+pub const DEFAULT_MAGIC: u8 = 0xEE;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum AddressingMode {
 	Immediate,
 	ZeroPage,
@@ -21,6 +40,38 @@ pub enum AddressingMode {
 	IndirectX,
 	IndirectY,
 	Implied,
+	Accumulator,
+	Relative,
+	Indirect,
+	ZeroPageIndirect,
+	AbsoluteIndirectX,
+	ZeroPageRelative,
+}
+
+impl AddressingMode {
+	// Whether the operand is reached through a pointer in zero page.
+	pub fn is_indirect(&self) -> bool {
+		matches!(self, AddressingMode::IndirectX | AddressingMode::IndirectY | AddressingMode::ZeroPageIndirect)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CpuVariant {
+	// The NES CPU. A 6502 with the decimal mode circuitry cut off.
+	Ricoh2A03,
+	// A stock NMOS 6502 with working binary-coded decimal arithmetic.
+	Nmos6502,
+	// The CMOS 65C02 with the WDC and Rockwell additions.
+	Wdc65C02,
+}
+
+impl CpuVariant {
+	pub fn opcodes(&self) -> &'static [OpCodeDef; 256] {
+		match self {
+			CpuVariant::Ricoh2A03 | CpuVariant::Nmos6502 => &OPCODES,
+			CpuVariant::Wdc65C02 => &CMOS_OPCODES,
+		}
+	}
 }
 
 pub struct Cpu {
@@ -31,6 +82,15 @@ pub struct Cpu {
 	pub program_counter: u16,
 	pub stack_pointer: u8,
 	pub bus: Bus,
+	pub jammed: bool,
+	pub waiting: bool,
+	pub variant: CpuVariant,
+	pub undefined_policy: UndefinedPolicy,
+	pub ane_magic: u8,
+	pub lxa_magic: u8,
+	pub cycles: u64,
+	pub cycle_log: Option<Vec<BusCycle>>,
+	pub code_log: Option<CodeDataLog>,
 }
 
 impl Cpu {
@@ -42,25 +102,80 @@ impl Cpu {
 			status: CpuStatus(0),
 			program_counter: 0,
 			stack_pointer: DEFAULT_STACK,
-			bus: Bus::new()
+			bus: Bus::new(),
+			jammed: false,
+			waiting: false,
+			variant: CpuVariant::Ricoh2A03,
+			undefined_policy: UndefinedPolicy::Emulate,
+			ane_magic: DEFAULT_MAGIC,
+			lxa_magic: DEFAULT_MAGIC,
+			cycles: 0,
+			cycle_log: None,
+			code_log: None,
 		}
 	}
 
+	// Errors can only come up with UndefinedPolicy::Error, in which case this panics.
 	pub fn interpret(&mut self, program: Vec<u8>) {
 		self.load(program);
 		self.reset();
-		self.run();
+		if let Err(error) = self.run() {
+			panic!("{}", error);
+		}
 	}
 
-	pub fn run(&mut self) {
-		let mut opcode = self.read(self.program_counter);
-		while opcode != 0x00 {
-			self.program_counter += 1;
-			let def = ops::get_instruction_def(opcode);
-			def.execute(self);
-			self.program_counter += (def.len - 1) as u16;
-			opcode = self.read(self.program_counter);
+	// Runs until a BRK is reached, the CPU jams or a WAI stops it. After an interrupt
+	// wakes it up, run carries on from there.
+	pub fn run(&mut self) -> Result<(), CpuError> {
+		while !self.jammed && !self.waiting && self.peek(self.program_counter) != 0x00 {
+			self.step()?;
 		}
+		Ok(())
+	}
+
+	pub fn step(&mut self) -> Result<(), CpuError> {
+		let address = self.program_counter;
+		let opcode = self.peek(address);
+		let def = &self.variant.opcodes()[opcode as usize];
+
+		if self.waiting {
+			return Ok(());
+		}
+
+		if self.jammed {
+			return match self.undefined_policy {
+				UndefinedPolicy::Error => Err(CpuError::Jammed { opcode, address }),
+				_ => Ok(()),
+			};
+		}
+
+		if !def.official {
+			let error = match def.category {
+				Category::Jam => CpuError::Jammed { opcode, address },
+				_ => CpuError::UndefinedOpcode { opcode, address },
+			};
+			match self.undefined_policy {
+				UndefinedPolicy::Emulate => {}
+				UndefinedPolicy::Error => {
+					self.jammed = def.category == Category::Jam;
+					return Err(error);
+				}
+				UndefinedPolicy::Panic => panic!("{}", error),
+			}
+		}
+
+		// A jammed CPU stops fetching instructions, so the PC stays on the KIL.
+		if def.category == Category::Jam {
+			self.jammed = true;
+			return Ok(());
+		}
+
+		if let Some(log) = &mut self.code_log {
+			log.log_code(&self.bus, address, def.len);
+		}
+		self.fetch();
+		def.execute(self);
+		Ok(())
 	}
 
 	pub fn load(&mut self, program: Vec<u8>) {
@@ -77,28 +192,133 @@ impl Cpu {
 		self.status = CpuStatus(0b100100);
 		self.stack_pointer = DEFAULT_STACK;
 		self.program_counter = 0x0600;
+		self.jammed = false;
+		self.waiting = false;
+	}
+
+	// Resets the way the console does, starting at the address in the reset vector.
+	// Without a cartridge nothing answers there, so raw programs stay at $0600.
+	pub fn reset_from_vector(&mut self) {
+		self.reset();
+		if self.bus.cartridge.is_some() {
+			self.program_counter = self.peek_u16(0xFFFC);
+		}
+	}
+
+	// Non-maskable interrupt, which the PPU raises at the start of vblank. The host
+	// calls this between steps. Wakes up a CPU stopped by WAI.
+	pub fn nmi(&mut self) {
+		self.waiting = false;
+		if !self.jammed {
+			self.interrupt(NMI_VECTOR);
+		}
+	}
+
+	// Interrupt request, ignored while the I flag is set. WAI still wakes up then and
+	// carries on with the next instruction.
+	pub fn irq(&mut self) {
+		self.waiting = false;
+		if !self.jammed && !self.status.get_interrupt() {
+			self.interrupt(IRQ_VECTOR);
+		}
+	}
+
+	// Same sequence as BRK, except that the opcode fetch is thrown away and the
+	// pushed flags have B clear.
+	fn interrupt(&mut self, vector: u16) {
+		self.bus_read(self.program_counter);
+		self.bus_read(self.program_counter);
+		self.push_u16(self.program_counter);
+		let mut flags = *self.status;
+		flags &= !0b0001_0000;
+		flags |= 0b0010_0000;
+		self.push(flags);
+		self.status.set_interrupt(true);
+		if self.is_cmos() {
+			self.status.set_decimal(false);
+		}
+		let lo = self.data_read(vector, false) as u16;
+		let hi = self.data_read(vector + 1, false) as u16;
+		self.program_counter = hi << 8 | lo;
+	}
+
+	// Reads from the bus on behalf of the running program, which costs a cycle.
+	fn bus_read(&mut self, addr: u16) -> u8 {
+		self.bus_access(addr, AccessKind::Read)
+	}
+
+	fn bus_access(&mut self, addr: u16, kind: AccessKind) -> u8 {
+		let data = self.bus.read(addr);
+		self.bus.record(self.cycles, addr, data, kind);
+		self.cycles += 1;
+		if let Some(log) = &mut self.cycle_log {
+			log.push(BusCycle::read(addr, data));
+		}
+		data
+	}
+
+	// Reads an operand or some other data, as opposed to an instruction byte or a
+	// dummy read, which is what the code/data log wants to know about.
+	fn data_read(&mut self, addr: u16, indirect: bool) -> u8 {
+		if let Some(log) = &mut self.code_log {
+			log.log_data(&self.bus, addr, indirect);
+		}
+		self.bus_read(addr)
+	}
+
+	fn bus_write(&mut self, addr: u16, data: u8) {
+		self.bus.write(addr, data);
+		self.bus.record(self.cycles, addr, data, AccessKind::Write);
+		self.cycles += 1;
+		if let Some(log) = &mut self.cycle_log {
+			log.push(BusCycle::write(addr, data));
+		}
+	}
+
+	fn fetch(&mut self) -> u8 {
+		let data = self.bus_access(self.program_counter, AccessKind::Fetch);
+		self.program_counter = self.program_counter.wrapping_add(1);
+		data
+	}
+
+	fn fetch_u16(&mut self) -> u16 {
+		let lo = self.fetch() as u16;
+		let hi = self.fetch() as u16;
+		hi << 8 | lo
+	}
+
+	// Single byte instructions still read the byte after the opcode and throw it away.
+	fn discard_operand(&mut self) {
+		self.bus_read(self.program_counter);
 	}
 
 	pub fn branch_if(&mut self, condition: bool) {
+		let jmp = self.fetch() as i8;
 		if condition {
-			let jmp = self.read(self.program_counter) as i8;
-			self.program_counter =
-				self.program_counter
-				.wrapping_add(1)
-				.wrapping_add(jmp as u16);
+			self.bus_read(self.program_counter);
+			let target = self.program_counter.wrapping_add(jmp as u16);
+			if target & 0xFF00 != self.program_counter & 0xFF00 {
+				self.bus_read(self.program_counter & 0xFF00 | target & 0x00FF);
+			}
+			self.program_counter = target;
 		}
 	}
 
 	fn pop(&mut self) -> u8 {
 		self.stack_pointer = self.stack_pointer.wrapping_add(1);
-		self.read((STACK as u16) + self.stack_pointer as u16)
+		self.bus_read(STACK + self.stack_pointer as u16)
 	}
 
 	fn push(&mut self, data: u8) {
-		self.write((STACK as u16) + self.stack_pointer as u16, data);
+		self.bus_write(STACK + self.stack_pointer as u16, data);
 		self.stack_pointer = self.stack_pointer.wrapping_sub(1)
 	}
 
+	// The CPU reads the top of the stack before it gets around to incrementing the pointer.
+	fn peek_stack(&mut self) {
+		self.bus_read(STACK + self.stack_pointer as u16);
+	}
+
 	fn push_u16(&mut self, data: u16) {
 		let hi = (data >> 8) as u8;
 		let lo = (data & 0xff) as u8;
@@ -112,62 +332,182 @@ impl Cpu {
 		hi << 8 | lo
 	}
 
-	fn get_operand_address(&self, mode: &AddressingMode) -> u16 {
+	// Fetches the operand and resolves its address. Indexed modes read from the
+	// address before the page carry is fixed; reads skip that cycle when no carry
+	// happens, writes and read-modify-writes always take it.
+	fn get_operand_address(&mut self, mode: &AddressingMode, category: Category) -> u16 {
+		let always_fix = category != Category::Read;
 		match mode {
-			AddressingMode::Immediate => self.program_counter,
-			AddressingMode::ZeroPage => self.read(self.program_counter) as u16,
-			AddressingMode::Absolute => self.read_u16(self.program_counter),
+			AddressingMode::Immediate => {
+				let addr = self.program_counter;
+				self.program_counter = self.program_counter.wrapping_add(1);
+				addr
+			}
+			AddressingMode::ZeroPage => self.fetch() as u16,
+			AddressingMode::Absolute => self.fetch_u16(),
 			AddressingMode::ZeroPageX => {
-				let pos = self.read(self.program_counter);
+				let pos = self.fetch();
+				self.bus_read(pos as u16);
 				pos.wrapping_add(self.register_x) as u16
 			}
 			AddressingMode::ZeroPageY => {
-				let pos = self.read(self.program_counter);
+				let pos = self.fetch();
+				self.bus_read(pos as u16);
 				pos.wrapping_add(self.register_y) as u16
 			}
 			AddressingMode::AbsoluteX => {
-				let base = self.read_u16(self.program_counter);
-				base.wrapping_add(self.register_x as u16)
+				let base = self.fetch_u16();
+				self.index_address(base, self.register_x, always_fix)
 			}
 			AddressingMode::AbsoluteY => {
-				let base = self.read_u16(self.program_counter);
-				base.wrapping_add(self.register_y as u16)
+				let base = self.fetch_u16();
+				self.index_address(base, self.register_y, always_fix)
 			}
 			AddressingMode::IndirectX => {
-				let base = self.read(self.program_counter);
-				let ptr: u8 = (base as u8).wrapping_add(self.register_x);
-				let lo = self.read(ptr as u16);
-				let hi = self.read(ptr.wrapping_add(1) as u16);
+				let base = self.fetch();
+				self.bus_read(base as u16);
+				let ptr: u8 = base.wrapping_add(self.register_x);
+				let lo = self.bus_read(ptr as u16);
+				let hi = self.bus_read(ptr.wrapping_add(1) as u16);
 				(hi as u16) << 8 | (lo as u16)
 			}
 			AddressingMode::IndirectY => {
-				let base = self.read(self.program_counter);
-				let lo = self.read(base as u16);
-				let hi = self.read((base as u8).wrapping_add(1) as u16);
+				let base = self.fetch();
+				let lo = self.bus_read(base as u16);
+				let hi = self.bus_read(base.wrapping_add(1) as u16);
 				let deref_base = (hi as u16) << 8 | (lo as u16);
-				deref_base.wrapping_add(self.register_y as u16)
+				self.index_address(deref_base, self.register_y, always_fix)
+			}
+			AddressingMode::ZeroPageIndirect => {
+				let base = self.fetch();
+				let lo = self.bus_read(base as u16);
+				let hi = self.bus_read(base.wrapping_add(1) as u16);
+				(hi as u16) << 8 | (lo as u16)
 			}
-			AddressingMode::Implied => {
+			AddressingMode::Implied
+			| AddressingMode::Accumulator
+			| AddressingMode::Relative
+			| AddressingMode::Indirect
+			| AddressingMode::AbsoluteIndirectX
+			| AddressingMode::ZeroPageRelative => {
 				panic!("Unsupported address mode");
 			}
 		}
 	}
 
+	// The 65C02 reads the last operand byte again instead of the unfixed address.
+	fn index_address(&mut self, base: u16, index: u8, always_fix: bool) -> u16 {
+		let addr = base.wrapping_add(index as u16);
+		if always_fix || addr & 0xFF00 != base & 0xFF00 {
+			if self.is_cmos() {
+				self.bus_read(self.program_counter.wrapping_sub(1));
+			} else {
+				self.bus_read(base & 0xFF00 | addr & 0x00FF);
+			}
+		}
+		addr
+	}
+
+	fn read_operand(&mut self, mode: &AddressingMode) -> u8 {
+		let addr = self.get_operand_address(mode, Category::Read);
+		match mode {
+			// Immediate operands are part of the instruction, not data.
+			AddressingMode::Immediate => self.bus_access(addr, AccessKind::Fetch),
+			_ => self.data_read(addr, mode.is_indirect()),
+		}
+	}
+
+	fn write_operand(&mut self, mode: &AddressingMode, data: u8) {
+		let addr = self.get_operand_address(mode, Category::Write);
+		self.bus_write(addr, data);
+	}
+
+	// SHA, SHX, SHY and TAS store their value ANDed with the high byte of the base
+	// address plus one. When indexing crosses a page, that same value also replaces
+	// the high byte of the address that ends up being written to.
+	fn unstable_store(&mut self, mode: &AddressingMode, data: u8) {
+		let (base, index) = match mode {
+			AddressingMode::AbsoluteX => (self.fetch_u16(), self.register_x),
+			AddressingMode::AbsoluteY => (self.fetch_u16(), self.register_y),
+			AddressingMode::IndirectY => {
+				let ptr = self.fetch();
+				let lo = self.bus_read(ptr as u16);
+				let hi = self.bus_read(ptr.wrapping_add(1) as u16);
+				((hi as u16) << 8 | (lo as u16), self.register_y)
+			}
+			_ => panic!("Unsupported address mode"),
+		};
+		let addr = self.index_address(base, index, true);
+		let data = data & ((base >> 8) as u8).wrapping_add(1);
+		if addr & 0xFF00 != base & 0xFF00 {
+			self.bus_write((data as u16) << 8 | addr & 0x00FF, data);
+		} else {
+			self.bus_write(addr, data);
+		}
+	}
+
+	fn read_modify_write(&mut self, mode: &AddressingMode, operation: fn(&mut Cpu, u8) -> u8) -> u8 {
+		let addr = self.get_operand_address(mode, Category::ReadModifyWrite);
+		self.modify(addr, mode.is_indirect(), operation)
+	}
+
+	// The 65C02 only takes the extra indexing cycle on shifts and rotates when a page is crossed.
+	fn shift_memory(&mut self, mode: &AddressingMode, operation: fn(&mut Cpu, u8) -> u8) -> u8 {
+		let category = match self.is_cmos() {
+			true => Category::Read,
+			false => Category::ReadModifyWrite,
+		};
+		let addr = self.get_operand_address(mode, category);
+		self.modify(addr, mode.is_indirect(), operation)
+	}
+
+	// NMOS chips write the unmodified value back before the result, the 65C02 reads it twice instead.
+	fn modify(&mut self, addr: u16, indirect: bool, operation: fn(&mut Cpu, u8) -> u8) -> u8 {
+		let data = self.data_read(addr, indirect);
+		if self.is_cmos() {
+			self.bus_read(addr);
+		} else {
+			self.bus_write(addr, data);
+		}
+		let result = operation(self, data);
+		self.bus_write(addr, result);
+		result
+	}
+
+	fn decimal_mode(&self) -> bool {
+		self.variant != CpuVariant::Ricoh2A03 && self.status.get_decimal()
+	}
+
 	fn set_zero_neg_flags(&mut self, result: u8) {
 		self.status.set_zero(result == 0);
 		self.status.set_negative(result & 0b1000_0000 != 0);
 	}
 }
 
+impl Default for Cpu {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl Memory for Cpu {
-	fn read(&self, addr: u16) -> u8 {
+	fn read(&mut self, addr: u16) -> u8 {
 		self.bus.read(addr)
 	}
 
 	fn write(&mut self, addr: u16, data: u8) {
 		self.bus.write(addr, data);
 	}
-	fn read_u16(&self, pos: u16) -> u16 {
+
+	fn peek(&self, addr: u16) -> u8 {
+		self.bus.peek(addr)
+	}
+
+	fn poke(&mut self, addr: u16, data: u8) {
+		self.bus.poke(addr, data);
+	}
+
+	fn read_u16(&mut self, pos: u16) -> u16 {
 		self.bus.read_u16(pos)
 	}
 
@@ -176,21 +516,118 @@ impl Memory for Cpu {
 	}
 }
 
+impl Cpu {
+	// The logs and the cheats aren't machine state, so they are neither saved
+	// nor replaced by loading.
+	pub fn save_state(&self) -> Vec<u8> {
+		let mut writer = StateWriter::new();
+		writer.chunk(b"CPU ", self);
+		writer.chunk(b"BUS ", &self.bus);
+		writer.finish()
+	}
+
+	// Loads into a fresh machine first, so a broken state leaves this one untouched.
+	// The state only covers the cartridge's RAM, so the fresh one gets a copy of it.
+	// Attached devices can't be copied, they move over and load in place, last of
+	// everything. A state that's only broken in a device's data can leave the
+	// devices before it loaded.
+	pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+		let mut cpu = Cpu::new();
+		cpu.bus.ram_init = self.bus.ram_init;
+		cpu.bus.cartridge = self.bus.cartridge.clone();
+		cpu.bus.map = std::mem::take(&mut self.bus.map);
+		let result = StateReader::new(data).and_then(|mut reader| {
+			reader.chunk(b"CPU ", &mut cpu)?;
+			reader.chunk(b"BUS ", &mut cpu.bus)?;
+			reader.finish()
+		});
+		if let Err(err) = result {
+			self.bus.map = std::mem::take(&mut cpu.bus.map);
+			return Err(err);
+		}
+		cpu.cycle_log = self.cycle_log.take();
+		cpu.code_log = self.code_log.take();
+		cpu.bus.events = self.bus.events.take();
+		cpu.bus.cheats = std::mem::take(&mut self.bus.cheats);
+		*self = cpu;
+		Ok(())
+	}
+}
+
+impl Snapshot for Cpu {
+	fn save(&self, writer: &mut StateWriter) {
+		writer.write_u8(self.register_a);
+		writer.write_u8(self.register_x);
+		writer.write_u8(self.register_y);
+		writer.write_u8(self.status.0);
+		writer.write_u16(self.program_counter);
+		writer.write_u8(self.stack_pointer);
+		writer.write_bool(self.jammed);
+		writer.write_bool(self.waiting);
+		writer.write_u8(match self.variant {
+			CpuVariant::Ricoh2A03 => 0,
+			CpuVariant::Nmos6502 => 1,
+			CpuVariant::Wdc65C02 => 2,
+		});
+		writer.write_u8(match self.undefined_policy {
+			UndefinedPolicy::Emulate => 0,
+			UndefinedPolicy::Error => 1,
+			UndefinedPolicy::Panic => 2,
+		});
+		writer.write_u8(self.ane_magic);
+		writer.write_u8(self.lxa_magic);
+		writer.write_u64(self.cycles);
+	}
+
+	fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+		self.register_a = reader.read_u8()?;
+		self.register_x = reader.read_u8()?;
+		self.register_y = reader.read_u8()?;
+		self.status = CpuStatus(reader.read_u8()?);
+		self.program_counter = reader.read_u16()?;
+		self.stack_pointer = reader.read_u8()?;
+		self.jammed = reader.read_bool("jammed")?;
+		self.waiting = reader.read_bool("waiting")?;
+		self.variant = match reader.read_u8()? {
+			0 => CpuVariant::Ricoh2A03,
+			1 => CpuVariant::Nmos6502,
+			2 => CpuVariant::Wdc65C02,
+			value => return Err(StateError::Invalid { field: "CPU variant", value: value as u64 }),
+		};
+		self.undefined_policy = match reader.read_u8()? {
+			0 => UndefinedPolicy::Emulate,
+			1 => UndefinedPolicy::Error,
+			2 => UndefinedPolicy::Panic,
+			value => return Err(StateError::Invalid { field: "undefined opcode policy", value: value as u64 }),
+		};
+		self.ane_magic = reader.read_u8()?;
+		self.lxa_magic = reader.read_u8()?;
+		self.cycles = reader.read_u64()?;
+		Ok(())
+	}
+}
+
 impl Cpu {
 	pub fn aac(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.register_a &= data;
 		self.set_zero_neg_flags(self.register_a);
 		self.status.set_carry(self.status.get_negative());
 	}
 
 	pub fn aax(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		self.write(addr, self.register_a & self.register_x);
+		self.write_operand(mode, self.register_a & self.register_x);
 	}
 
 	pub fn add_a_carry(&mut self, data: u8) {
+		if self.decimal_mode() {
+			self.add_a_carry_decimal(data);
+		} else {
+			self.add_a_carry_binary(data);
+		}
+	}
+
+	fn add_a_carry_binary(&mut self, data: u8) {
 		let sum = self.register_a as u16 + data as u16 + self.status.get_carry() as u16;
 
 		let carry = sum > 0xff;
@@ -204,12 +641,83 @@ impl Cpu {
 		self.set_zero_neg_flags(self.register_a);
 	}
 
+	// NMOS chips add each nibble separately and correct it, but compute Z from the
+	// binary sum and N and V from the sum before the high nibble gets corrected.
+	fn add_a_carry_decimal(&mut self, data: u8) {
+		let a = self.register_a as i16;
+		let b = data as i16;
+		let carry = self.status.get_carry() as i16;
+
+		self.status.set_zero((a + b + carry) as u8 == 0);
+
+		let mut lo = (a & 0x0F) + (b & 0x0F) + carry;
+		if lo >= 0x0A {
+			lo = ((lo + 0x06) & 0x0F) + 0x10;
+		}
+
+		let signed = (self.register_a as i8 as i16 & !0x0F) + (data as i8 as i16 & !0x0F) + lo;
+		self.status.set_negative(signed & 0x80 != 0);
+		self.status.set_overflow(!(-128..=127).contains(&signed));
+
+		let mut sum = (a & 0xF0) + (b & 0xF0) + lo;
+		if sum >= 0xA0 {
+			sum += 0x60;
+		}
+		self.status.set_carry(sum >= 0x100);
+		self.register_a = sum as u8;
+
+		// The 65C02 sets N and Z from the corrected result.
+		if self.is_cmos() {
+			self.set_zero_neg_flags(self.register_a);
+		}
+	}
+
+	// The 65C02 spends an extra cycle on decimal corrections to get valid flags.
+	fn decimal_fixup_cycle(&mut self) {
+		if self.decimal_mode() && self.is_cmos() {
+			self.discard_operand();
+		}
+	}
+
+	// Flags always come from the binary subtraction, only the result is decimal corrected.
+	pub fn sub_a_carry(&mut self, data: u8) {
+		let a = self.register_a as i16;
+		let b = data as i16;
+		let carry = self.status.get_carry() as i16;
+
+		self.add_a_carry_binary(!data);
+
+		if self.decimal_mode() && self.is_cmos() {
+			// The 65C02 corrects the binary difference instead, and sets N and Z from it.
+			let lo = (a & 0x0F) - (b & 0x0F) + carry - 1;
+			let mut result = a - b + carry - 1;
+			if result < 0 {
+				result -= 0x60;
+			}
+			if lo < 0 {
+				result -= 0x06;
+			}
+			self.register_a = result as u8;
+			self.set_zero_neg_flags(self.register_a);
+		} else if self.decimal_mode() {
+			let mut lo = (a & 0x0F) - (b & 0x0F) + carry - 1;
+			if lo < 0 {
+				lo = ((lo - 0x06) & 0x0F) - 0x10;
+			}
+			let mut result = (a & 0xF0) - (b & 0xF0) + lo;
+			if result < 0 {
+				result -= 0x60;
+			}
+			self.register_a = result as u8;
+		}
+	}
+
 	// ADC - Add with Carry
 	// A,Z,C,N = A+M+C
 	// This instruction adds the contents of a memory location to the accumulator together with the carry bit. If overflow occurs the carry bit is set, this enables multiple byte addition to be performed.
 	pub fn adc(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
+		self.decimal_fixup_cycle();
 		self.add_a_carry(data);
 	}
 
@@ -217,61 +725,76 @@ impl Cpu {
 	// A,Z,N = A&M
 	// A logical AND is performed, bit by bit, on the accumulator contents using the contents of a byte of memory.
 	pub fn and(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.register_a &= data;
 		self.set_zero_neg_flags(self.register_a);
 	}
 
 	// ARR - AND byte with accumulator
 	// AND byte with accumulator, then rotate one bit right in accumulator and check bit 5 and 6.
+	// In decimal mode NMOS chips correct each nibble of the rotated value, with the carry coming from the high nibble.
 	pub fn arr(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
-
-		self.register_a &= data;
-		self.set_zero_neg_flags(self.register_a);
-
-		self.ror_a(mode);
+		let data = self.read_operand(mode) & self.register_a;
+		let mut result = data >> 1 | (self.status.get_carry() as u8) << 7;
+		self.set_zero_neg_flags(result);
 
-		let result = self.register_a;
 		let b5 = (result >> 5) & 1;
 		let b6 = (result >> 6) & 1;
-		self.status.set_carry(b5 == 1);
 		self.status.set_overflow(b5 ^ b6 == 1);
-		self.set_zero_neg_flags(result);
+
+		if self.decimal_mode() {
+			let (hi, lo) = (data >> 4, data & 0x0F);
+			if lo + (lo & 1) > 5 {
+				result = result & 0xF0 | result.wrapping_add(0x06) & 0x0F;
+			}
+			let carry = hi + (hi & 1) > 5;
+			if carry {
+				result = result.wrapping_add(0x60);
+			}
+			self.status.set_carry(carry);
+		} else {
+			self.status.set_carry(b6 == 1);
+		}
+
+		self.register_a = result;
+	}
+
+	// ASL - Arithmetic Shift Left
+	// A,Z,C,N = M*2 or M,Z,C,N = M*2
+	// This operation shifts all the bits of the accumulator or memory contents one bit left. Bit 0 is set to 0 and bit 7 is placed in the carry flag. The effect of this operation is to multiply the memory contents by 2 (ignoring 2's complement considerations), setting the carry if the result will not fit in 8 bits.
+	pub fn asl_a(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.register_a = self.asl_value(self.register_a);
 	}
 
 	// ASL - Arithmetic Shift Left
 	// A,Z,C,N = M*2 or M,Z,C,N = M*2
 	// This operation shifts all the bits of the accumulator or memory contents one bit left. Bit 0 is set to 0 and bit 7 is placed in the carry flag. The effect of this operation is to multiply the memory contents by 2 (ignoring 2's complement considerations), setting the carry if the result will not fit in 8 bits.
-	pub fn asl(&mut self, _mode: &AddressingMode) {
-		self.asl_ext();
+	pub fn asl_m(&mut self, mode: &AddressingMode) {
+		self.shift_memory(mode, Cpu::asl_value);
 	}
 
-	pub fn asl_ext(&mut self) -> u8 {
-		let mut data = self.register_a;
+	pub fn asl_value(&mut self, mut data: u8) -> u8 {
 		self.status.set_carry(data >> 7 == 1);
 		data <<= 1;
-		self.register_a = data;
-		self.set_zero_neg_flags(self.register_a);
+		self.set_zero_neg_flags(data);
 		data
 	}
 
 	// ASR
 	// AND byte with accumulator, then shift right one bit in accumu-lator.
 	pub fn asr(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.register_a &= data;
 		self.set_zero_neg_flags(self.register_a);
-		self.lsr_a(mode);
+		self.register_a = self.lsr_value(self.register_a);
 	}
 
 	// TXA - Transfer X to Accumulator
 	// A = X
 	// Copies the current contents of the X register into the accumulator and sets the zero and negative flags as appropriate.
 	pub fn txa(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_a = self.register_x;
 		self.set_zero_neg_flags(self.register_a);
 	}
@@ -280,6 +803,7 @@ impl Cpu {
 	// S = X
 	// Copies the current contents of the X register into the stack register.
 	pub fn txs(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.stack_pointer = self.register_x;
 	}
 
@@ -287,34 +811,32 @@ impl Cpu {
 	// A = Y
 	// Copies the current contents of the Y register into the accumulator and sets the zero and negative flags as appropriate.
 	pub fn tya(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_a = self.register_y;
 		self.set_zero_neg_flags(self.register_a);
 	}
 
-	pub fn xas(&mut self, _mode: &AddressingMode) {
-		let data = self.register_a & self.register_x;
-		self.stack_pointer = data;
-		let addr = self.read_u16(self.program_counter) + self.register_y as u16;
-
-		let data = ((addr >> 8) as u8 + 1) & self.stack_pointer;
-		self.write(addr, data);
+	// XAS (TAS)
+	// AND X register with accumulator and store result in stack pointer, then AND stack pointer with the high byte of the target address of the argument + 1 and store it in memory.
+	pub fn xas(&mut self, mode: &AddressingMode) {
+		self.stack_pointer = self.register_a & self.register_x;
+		self.unstable_store(mode, self.stack_pointer);
 	}
 
-	// XAA - ???
-	// This opcode results in UB. Its effects were replicated below;
-	// It's works by writing X register to A, setting zero negative flags on register a, and anding it with a single argument.
+	// XAA (ANE)
+	// A = (A | magic) & X & M
+	// This opcode is unstable, the magic constant depends on the chip. It can be changed through ane_magic.
 	pub fn xaa(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
-		self.register_a = self.register_x;
+		let data = self.read_operand(mode);
+		self.register_a = (self.register_a | self.ane_magic) & self.register_x & data;
 		self.set_zero_neg_flags(self.register_a);
-		self.register_a &= data;
 	}
 
 	// TSX - Transfer Stack Pointer to X
 	// X = S
 	// Copies the current contents of the stack register into the X register and sets the zero and negative flags as appropriate.
 	pub fn tsx(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_x = self.stack_pointer;
 		self.set_zero_neg_flags(self.register_x);
 	}
@@ -323,6 +845,7 @@ impl Cpu {
 	// Y = A
 	// Copies the current contents of the accumulator into the Y register and sets the zero and negative flags as appropriate.
 	pub fn tay(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_y = self.register_a;
 		self.set_zero_neg_flags(self.register_y);
 	}
@@ -331,48 +854,42 @@ impl Cpu {
 	// X = A
 	// Copies the current contents of the accumulator into the X register and sets the zero and negative flags as appropriate.
 	pub fn tax(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_x = self.register_a;
 		self.set_zero_neg_flags(self.register_x);
 	}
 
-	// SYA
-	// AND Y register with the high byte of the target address of the argument
-	pub fn sya(&mut self, _mode: &AddressingMode) {
-		let addr = self.read_u16(self.program_counter) + self.register_x as u16;
-		let data = self.register_y & ((addr >> 8) as u8 + 1);
-		self.write(addr, data);
+	// SYA (SHY)
+	// AND Y register with the high byte of the target address of the argument + 1
+	pub fn sya(&mut self, mode: &AddressingMode) {
+		self.unstable_store(mode, self.register_y);
 	}
 
-	// SXA
-	// AND X register with the high byte of the target address of the argument
-	pub fn sxa(&mut self, _mode: &AddressingMode) {
-		let addr = self.read_u16(self.program_counter) + self.register_y as u16;
-		let data = self.register_x & ((addr >> 8) as u8 + 1);
-		self.write(addr, data);
+	// SXA (SHX)
+	// AND X register with the high byte of the target address of the argument + 1
+	pub fn sxa(&mut self, mode: &AddressingMode) {
+		self.unstable_store(mode, self.register_x);
 	}
 
 	// STY - Store Y Register
 	// M = Y
 	// Stores the contents of the Y register into memory.
 	pub fn sty(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		self.write(addr, self.register_y);
+		self.write_operand(mode, self.register_y);
 	}
 
 	// STX - Store X Register
 	// M = X
 	// Stores the contents of the X register into memory.
 	pub fn stx(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		self.write(addr, self.register_x);
+		self.write_operand(mode, self.register_x);
 	}
 
 	// STA - Store Accumulator
 	// M = A
 	// Stores the contents of the accumulator into memory.
 	pub fn sta(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		self.write(addr, self.register_a);
+		self.write_operand(mode, self.register_a);
 	}
 
 	// SRE
@@ -385,8 +902,8 @@ impl Cpu {
 
 	// SLO
 	// Shift left one bit in memory, then OR accumulator with memory.
-	pub fn slo(&mut self, _mode: &AddressingMode) {
-		let data = self.asl_ext();
+	pub fn slo(&mut self, mode: &AddressingMode) {
+		let data = self.shift_memory(mode, Cpu::asl_value);
 		self.register_a |= data;
 		self.set_zero_neg_flags(self.register_a);
 	}
@@ -395,6 +912,7 @@ impl Cpu {
 	// I = 1
 	// Set the interrupt disable flag to one.
 	pub fn sei(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_interrupt(true);
 	}
 
@@ -402,6 +920,7 @@ impl Cpu {
 	// D = 1
 	// Set the decimal mode flag to one.
 	pub fn sed(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_decimal(true);
 	}
 
@@ -409,6 +928,7 @@ impl Cpu {
 	// C = 1
 	// Set the carry flag to one.
 	pub fn sec(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_carry(true);
 	}
 
@@ -416,31 +936,25 @@ impl Cpu {
 	// A,Z,C,N = A-M-(1-C)
 	// This instruction subtracts the contents of a memory location to the accumulator together with the not of the carry bit. If overflow occurs the carry bit is clear, this enables multiple byte subtraction to be performed.
 	pub fn sbc(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
-		let data = ((data as i8).wrapping_neg().wrapping_sub(1)) as u8;
-		let sum = self.register_a as u16 + data as u16 + self.status.get_carry() as u16;
-
-		let carry = sum > 0xff;
-		self.status.set_carry(carry);
-
-		let result = sum as u8;
-		let overflow = (data ^ result) & (result ^ self.register_a) & 0x80 != 0;
-		self.status.set_overflow(overflow);
-
-		self.register_a = result;
-		self.set_zero_neg_flags(self.register_a);
+		let data = self.read_operand(mode);
+		self.decimal_fixup_cycle();
+		self.sub_a_carry(data);
 	}
 
 	// RTS - Return from Subroutine
 	// The RTS instruction is used at the end of a subroutine to return to the calling routine. It pulls the program counter (minus one) from the stack.
 	pub fn rts(&mut self, _mode: &AddressingMode) {
-		self.program_counter = self.pop_u16() + 1;
+		self.discard_operand();
+		self.peek_stack();
+		self.program_counter = self.pop_u16();
+		self.fetch();
 	}
 
 	// RTI - Return from Interrupt
 	// The RTI instruction is used at the end of an interrupt processing routine. It pulls the processor flags from the stack followed by the program counter.
 	pub fn rti(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.peek_stack();
 		*self.status = self.pop();
 		self.status.set_break_min(false);
 		self.status.set_break_max(true);
@@ -455,15 +969,16 @@ impl Cpu {
 	}
 
 	pub fn ror_m_ext(&mut self, mode: &AddressingMode) -> u8 {
-		let addr = self.get_operand_address(mode);
-		let mut data = self.read(addr);
+		self.shift_memory(mode, Cpu::ror_value)
+	}
+
+	pub fn ror_value(&mut self, mut data: u8) -> u8 {
 		let carry = self.status.get_carry();
 		self.status.set_carry(data & 1 == 1);
 		data >>= 1;
 		if carry {
 			data |= 0b1000_0000;
 		}
-		self.write(addr, data);
 		self.set_zero_neg_flags(data);
 		data
 	}
@@ -471,15 +986,8 @@ impl Cpu {
 	// ROR - Rotate Right
 	// Move each of the bits in either A or M one place to the right. Bit 7 is filled with the current value of the carry flag whilst the old bit 0 becomes the new carry flag value.
 	pub fn ror_a(&mut self, _mode: &AddressingMode) {
-		let mut data = self.register_a;
-		let carry = self.status.get_carry();
-		self.status.set_carry(data & 1 == 1);
-		data >>= 1;
-		if carry {
-			data |= 0b1000_0000;
-		}
-		self.register_a = data;
-		self.set_zero_neg_flags(self.register_a);
+		self.discard_operand();
+		self.register_a = self.ror_value(self.register_a);
 	}
 
 	// ROR - Rotate Right
@@ -489,15 +997,16 @@ impl Cpu {
 	}
 
 	pub fn rol_m_ext(&mut self, mode: &AddressingMode) -> u8 {
-		let addr = self.get_operand_address(mode);
-		let mut data = self.read(addr);
+		self.shift_memory(mode, Cpu::rol_value)
+	}
+
+	pub fn rol_value(&mut self, mut data: u8) -> u8 {
 		let carry = self.status.get_carry();
 		self.status.set_carry(data >> 7 == 1);
 		data <<= 1;
 		if carry {
 			data |= 1;
 		}
-		self.write(addr, data);
 		self.set_zero_neg_flags(data);
 		data
 	}
@@ -505,15 +1014,8 @@ impl Cpu {
 	// ROL - Rotate Left
 	// Move each of the bits in either A or M one place to the left. Bit 0 is filled with the current value of the carry flag whilst the old bit 7 becomes the new carry flag value.
 	pub fn rol_a(&mut self, _mode: &AddressingMode) {
-		let mut data = self.register_a;
-		let carry = self.status.get_carry();
-		self.status.set_carry(data >> 7 == 1);
-		data <<= 1;
-		if carry {
-			data |= 1;
-		}
-		self.register_a = data;
-		self.set_zero_neg_flags(self.register_a);
+		self.discard_operand();
+		self.register_a = self.rol_value(self.register_a);
 	}
 
 	// ROL - Rotate Left
@@ -533,6 +1035,8 @@ impl Cpu {
 	// PLP - Pull Processor Status
 	// Pulls an 8 bit value from the stack and into the processor flags. The flags will take on new states as determined by the value pulled.
 	pub fn plp(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.peek_stack();
 		*self.status = self.pop();
 		self.status.set_break(true);
 	}
@@ -540,6 +1044,8 @@ impl Cpu {
 	// PLA - Pull Accumulator
 	// Pulls an 8 bit value from the stack and into the accumulator. The zero and negative flags are set as appropriate.
 	pub fn pla(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
+		self.peek_stack();
 		self.register_a = self.pop();
 		self.set_zero_neg_flags(self.register_a);
 	}
@@ -547,6 +1053,7 @@ impl Cpu {
 	// PHP - Push Processor Status
 	// Pushes a copy of the status flags on to the stack.
 	pub fn php(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		let mut flags = *self.status;
 		flags |= 0b0011_0000;
 		self.push(flags);
@@ -555,33 +1062,36 @@ impl Cpu {
 	// PHA - Push Accumulator
 	// Pushes a copy of the accumulator on to the stack.
 	pub fn pha(&mut self, _mode: &AddressingMode) {
-		self.write((crate::cpu::STACK as u16) + self.stack_pointer as u16, self.register_a);
-		self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+		self.discard_operand();
+		self.push(self.register_a);
 	}
 
 	// ORA - Logical Inclusive OR
 	// A,Z,N = A|M
 	// An inclusive OR is performed, bit by bit, on the accumulator contents using the contents of a byte of memory.
 	pub fn ora(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.register_a |= data;
 		self.set_zero_neg_flags(self.register_a);
 	}
 
 	// NOP - No operation
-	// Doesn't perform any operation
-	pub fn nop(&mut self, _mode: &AddressingMode) {}
+	// Doesn't perform any operation. The unofficial variants with an operand still read it.
+	pub fn nop(&mut self, mode: &AddressingMode) {
+		match mode {
+			AddressingMode::Implied => self.discard_operand(),
+			_ => {
+				self.read_operand(mode);
+			}
+		}
+	}
 
 	// LSR - Logical Shift Right
 	// A,C,Z,N = A/2 or M,C,Z,N = M/2
 	// Each of the bits in A or M is shift one place to the right. The bit that was in bit 0 is shifted into the carry flag. Bit 7 is set to zero.
 	pub fn lsr_a(&mut self, _mode: &AddressingMode) {
-		let mut data = self.register_a;
-		self.status.set_carry(data & 1 == 1);
-		data >>= 1;
-		self.register_a = data;
-		self.set_zero_neg_flags(self.register_a);
+		self.discard_operand();
+		self.register_a = self.lsr_value(self.register_a);
 	}
 
 	// LSR - Logical Shift Right
@@ -592,11 +1102,12 @@ impl Cpu {
 	}
 
 	pub fn lsr_m_ext(&mut self, mode: &AddressingMode) -> u8 {
-		let addr = self.get_operand_address(mode);
-		let mut data = self.read(addr);
+		self.shift_memory(mode, Cpu::lsr_value)
+	}
+
+	pub fn lsr_value(&mut self, mut data: u8) -> u8 {
 		self.status.set_carry(data & 1 == 1);
 		data >>= 1;
-		self.write(addr, data);
 		self.set_zero_neg_flags(data);
 		data
 	}
@@ -605,8 +1116,7 @@ impl Cpu {
 	// Y,Z,N = M
 	// Loads a byte of memory into the Y register setting the zero and negative flags as appropriate.
 	pub fn ldy(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let value = self.read(addr);
+		let value = self.read_operand(mode);
 
 		self.register_y = value;
 		self.set_zero_neg_flags(self.register_y);
@@ -616,8 +1126,7 @@ impl Cpu {
 	// X,Z,N = M
 	// Loads a byte of memory into the X register setting the zero and negative flags as appropriate.
 	pub fn ldx(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let value = self.read(addr);
+		let value = self.read_operand(mode);
 
 		self.register_x = value;
 		self.set_zero_neg_flags(self.register_x);
@@ -627,16 +1136,14 @@ impl Cpu {
 	// A,Z,N = M
 	// Loads a byte of memory into the accumulator setting the zero and negative flags as appropriate.
 	pub fn lda(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let value = self.read(addr);
+		let value = self.read_operand(mode);
 
 		self.register_a = value;
 		self.set_zero_neg_flags(self.register_a);
 	}
 
 	pub fn lax(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.register_x = data;
 		self.register_a = self.register_x;
 		self.set_zero_neg_flags(self.register_a);
@@ -645,8 +1152,7 @@ impl Cpu {
 	// LAR
 	// AND memory with stack pointer, transfer result to accumulator, X register and stack pointer.
 	pub fn lar(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr) & self.stack_pointer;
+		let data = self.read_operand(mode) & self.stack_pointer;
 		self.register_a = data;
 		self.register_x = data;
 		self.stack_pointer = data;
@@ -656,12 +1162,15 @@ impl Cpu {
 	// JSR - Jump to Subroutine
 	// The JSR instruction pushes the address (minus one) of the return point on to the stack and then sets the program counter to the target memory address.
 	pub fn jsr(&mut self, _mode: &AddressingMode) {
-		self.push_u16(self.program_counter + 2 - 1);
-		self.program_counter = self.read_u16(self.program_counter);
+		let lo = self.fetch() as u16;
+		self.peek_stack();
+		self.push_u16(self.program_counter);
+		let hi = self.bus_read(self.program_counter) as u16;
+		self.program_counter = hi << 8 | lo;
 	}
 
 	pub fn jmp_absolute(cpu: &mut Cpu, _mode: &AddressingMode) {
-		cpu.program_counter = cpu.read_u16(cpu.program_counter);
+		cpu.program_counter = cpu.fetch_u16();
 	}
 
 	// JMP - Jump
@@ -669,37 +1178,28 @@ impl Cpu {
 	// Note: This instruction was buggy on the original hardware. This implementation accounts for that.
 	pub fn jmp(cpu: &mut Cpu, _mode: &AddressingMode) {
 		cpu.program_counter = {
-			let addr = cpu.read_u16(cpu.program_counter);
-			if addr & 0x00FF == 0x00FF {
-				let lo = cpu.read(addr);
-				let hi = cpu.read(addr & 0xFF00);
-				(hi as u16) << 8 | (lo as u16)
-			} else {
-				cpu.read_u16(addr)
+			let addr = cpu.fetch_u16();
+			let lo = cpu.data_read(addr, false);
+			let hi = cpu.data_read(addr & 0xFF00 | (addr as u8).wrapping_add(1) as u16, false);
+			if let Some(log) = &mut cpu.code_log {
+				log.log_indirect_jump();
 			}
+			(hi as u16) << 8 | (lo as u16)
 		}
 	}
 
 	// ISC - INC subtract
 	// Increase memory by one, then subtract memory from accu-mulator (with borrow)
 	pub fn isc(&mut self, mode: &AddressingMode) {
-		let data = (self.inc_ret(mode) as i8).wrapping_neg().wrapping_sub(1) as u8;
-		let sum = self.register_a as u16 + data as u16 + self.status.get_carry() as u16;
-		let carry = sum > 0xff;
-
-		self.status.set_carry(carry);
-		let result = sum as u8;
-
-		self.status.set_overflow((data ^ result) & (result ^ self.register_a) & 0x80 != 0);
-
-		self.register_a = result;
-		self.set_zero_neg_flags(self.register_a);
+		let data = self.inc_ret(mode);
+		self.sub_a_carry(data);
 	}
 
 	// INY - Increment Y Register
 	// Y,Z,N = Y+1
 	// Adds one to the Y register setting the zero and negative flags as appropriate.
 	pub fn iny(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_y = self.register_y.overflowing_add(1).0;
 		self.set_zero_neg_flags(self.register_y);
 	}
@@ -708,6 +1208,7 @@ impl Cpu {
 	// X,Z,N = X + 1
 	// Adds one to the X register setting the zero and negative flags as appropriate.
 	pub fn inx(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_x = self.register_x.overflowing_add(1).0;
 		self.set_zero_neg_flags(self.register_x);
 	}
@@ -723,20 +1224,18 @@ impl Cpu {
 	// M,Z,N = M+1
 	// Adds one to the value held at a specified memory location setting the zero and negative flags as appropriate.
 	pub fn inc_ret(&mut self, mode: &AddressingMode) -> u8 {
-		let addr = self.get_operand_address(mode);
-		let mut data = self.read(addr);
-		data = data.wrapping_add(1);
-		self.write(addr, data);
-		self.set_zero_neg_flags(data);
-		data
+		self.read_modify_write(mode, |cpu, data| {
+			let data = data.wrapping_add(1);
+			cpu.set_zero_neg_flags(data);
+			data
+		})
 	}
 
 	// EOR - Exclusive OR
 	// A,Z,N = A^M
 	// An exclusive OR is performed, bit by bit, on the accumulator contents using the contents of a byte of memory.
 	pub fn eor(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.register_a ^= data;
 		self.set_zero_neg_flags(self.register_a);
 	}
@@ -745,6 +1244,7 @@ impl Cpu {
 	// Y,Z,N = Y-1
 	// Subtracts one from the Y register setting the zero and negative flags as appropriate.
 	pub fn dey(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_y = self.register_y.overflowing_sub(1).0;
 		self.set_zero_neg_flags(self.register_y);
 	}
@@ -753,6 +1253,7 @@ impl Cpu {
 	// X,Z,N = X-1
 	// Subtracts one from the X register setting the zero and negative flags as appropriate.
 	pub fn dex(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.register_x = self.register_x.overflowing_sub(1).0;
 		self.set_zero_neg_flags(self.register_x);
 	}
@@ -761,26 +1262,22 @@ impl Cpu {
 	// M,Z,N = M-1
 	// Subtracts one from the value held at a specified memory location setting the zero and negative flags as appropriate.
 	pub fn dec(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let mut data = self.read(addr);
-		data = data.wrapping_sub(1);
-		self.write(addr, data);
-		self.set_zero_neg_flags(data);
+		self.dec_ret(mode);
+	}
+
+	pub fn dec_ret(&mut self, mode: &AddressingMode) -> u8 {
+		self.read_modify_write(mode, |cpu, data| {
+			let data = data.wrapping_sub(1);
+			cpu.set_zero_neg_flags(data);
+			data
+		})
 	}
 
 	// DCP
-	// Subtract one from memory.
+	// Subtract one from memory, then compare the result with the accumulator.
 	pub fn dcp(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let mut data = self.read(addr);
-
-		data = data.wrapping_sub(1);
-		self.write(addr, data);
-
-		if data <= self.register_a {
-			self.status.set_carry(true);
-		}
-
+		let data = self.dec_ret(mode);
+		self.status.set_carry(self.register_a >= data);
 		self.set_zero_neg_flags(self.register_a.wrapping_sub(data));
 	}
 
@@ -788,8 +1285,7 @@ impl Cpu {
 	// Z,C,N = Y-M
 	// This instruction compares the contents of the Y register with another memory held value and sets the zero and carry flags as appropriate.
 	pub fn cpy(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.status.set_carry(self.register_y >= data);
 		self.status.set_zero(self.register_y == data);
 		self.set_zero_neg_flags(self.register_y.wrapping_sub(data));
@@ -799,8 +1295,7 @@ impl Cpu {
 	// Z,C,N = X-M
 	// This instruction compares the contents of the X register with another memory held value and sets the zero and carry flags as appropriate.
 	pub fn cpx(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.status.set_carry(self.register_x >= data);
 		self.status.set_zero(self.register_x == data);
 		self.set_zero_neg_flags(self.register_x.wrapping_sub(data));
@@ -810,8 +1305,7 @@ impl Cpu {
 	// Z,C,N = A-M
 	// This instruction compares the contents of the accumulator with another memory held value and sets the zero and carry flags as appropriate.
 	pub fn cmp(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.status.set_carry(self.register_a >= data);
 		self.status.set_zero(self.register_a == data);
 		self.set_zero_neg_flags(self.register_a.wrapping_sub(data));
@@ -821,6 +1315,7 @@ impl Cpu {
 	// V = 0
 	// Clears the overflow flag.
 	pub fn clv(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_overflow(false);
 	}
 
@@ -828,6 +1323,7 @@ impl Cpu {
 	// I = 0
 	// Clears the interrupt disable flag allowing normal interrupt requests to be serviced.
 	pub fn cli(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_interrupt(false);
 	}
 
@@ -835,15 +1331,34 @@ impl Cpu {
 	// CLD - Clear Decimal Mode
 	// D = 0
 	pub fn cld(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_decimal(false);
 	}
 
 	// CLC - Clear Carry Flag
 	// C = 0
 	pub fn clc(&mut self, _mode: &AddressingMode) {
+		self.discard_operand();
 		self.status.set_carry(false);
 	}
 
+	// BRK - Force Interrupt
+	// The BRK instruction forces the generation of an interrupt request. The program counter and processor status are pushed on the stack then the IRQ interrupt vector at $FFFE/F is loaded into the PC and the break flag in the status set to one.
+	pub fn brk(&mut self, _mode: &AddressingMode) {
+		self.fetch();
+		self.push_u16(self.program_counter);
+		let mut flags = *self.status;
+		flags |= 0b0011_0000;
+		self.push(flags);
+		self.status.set_interrupt(true);
+		if self.is_cmos() {
+			self.status.set_decimal(false);
+		}
+		let lo = self.data_read(IRQ_VECTOR, false) as u16;
+		let hi = self.data_read(IRQ_VECTOR + 1, false) as u16;
+		self.program_counter = hi << 8 | lo;
+	}
+
 	// BVS - Branch if Overflow Set
 	// If the overflow flag is set then add the relative displacement to the program counter to cause a branch to a new location.
 	pub fn bvs(&mut self, _mode: &AddressingMode) {
@@ -878,8 +1393,7 @@ impl Cpu {
 	// A & M, N = M7, V = M6
 	// This instruction is used to test if one or more bits are set in a target memory location. The mask pattern in A is ANDed with the value in memory to set or clear the zero flag, but the result is not kept. Bits 7 and 6 of the value from memory are copied into the N and V flags.
 	pub fn bit(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		self.status.set_zero(self.register_a & data == 0);
 		self.status.set_negative(data & 0b1000_0000 > 0);
 		self.status.set_overflow(data & 0b0100_0000 > 0);
@@ -907,52 +1421,44 @@ impl Cpu {
 	// AXS - AND X register with accumulator
 	// Perform a logical AND on X register with accumulator and store result in X register, then subtract byte from X register (without borrow).
 	pub fn axs(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 		let xa = self.register_x & self.register_a;
 		let result = xa.wrapping_sub(data);
 
-		if data <= xa {
-			self.status.set_carry(true);
-		}
+		self.status.set_carry(data <= xa);
 
 		self.register_x = result;
 		self.set_zero_neg_flags(self.register_x);
 	}
 
-	// AXA - AND X register with accumulator
-	// Perform a logical AND on x register with accumulator, then AND result with 7 and store in memory.
-	pub fn axa_in(&mut self, _mode: &AddressingMode) {
-		let pos: u8 = self.read(self.program_counter);
-		let addr = self.read_u16(pos as u16) + self.register_y as u16;
-		let data = self.register_a & self.register_x & 0b0000_0111;
-		self.write(addr, data);
+	// AXA (SHA) - AND X register with accumulator
+	// Perform a logical AND on x register with accumulator, then AND result with the high byte of the target address of the argument + 1 and store in memory.
+	pub fn axa_in(&mut self, mode: &AddressingMode) {
+		self.unstable_store(mode, self.register_a & self.register_x);
 	}
 
-	// AXA - AND X register with accumulator
-	// Perform a logical AND on x register with accumulator, then AND result with 7 and store in memory.
-	pub fn axa_ab(&mut self, _mode: &AddressingMode) {
-		let addr = self.read_u16(self.program_counter) + self.register_y as u16;
-		let data = self.register_a & self.register_x & 0b0000_0111;
-		self.write(addr, data);
+	// AXA (SHA) - AND X register with accumulator
+	// Perform a logical AND on x register with accumulator, then AND result with the high byte of the target address of the argument + 1 and store in memory.
+	pub fn axa_ab(&mut self, mode: &AddressingMode) {
+		self.unstable_store(mode, self.register_a & self.register_x);
 	}
 
-	// AND byte with accumulator
-	// Perform a logical AND on byte with accumulator, then transfer accumulator to X register.
+	// ATX (LXA, LAX #imm)
+	// A,X = (A | magic) & M
+	// This opcode is unstable, the magic constant depends on the chip. It can be changed through lxa_magic.
 	pub fn atx(&mut self, mode: &AddressingMode) {
-		let addr = self.get_operand_address(mode);
-		let data = self.read(addr);
+		let data = self.read_operand(mode);
 
-		self.register_a &= data;
+		self.register_a = (self.register_a | self.lxa_magic) & data;
+		self.register_x = self.register_a;
 		self.set_zero_neg_flags(self.register_a);
-
-		self.tax(mode);
 	}
 }
 
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::assembler::Assembler;
 
 	#[test]
 	fn test_aac() {
@@ -1013,6 +1519,99 @@ mod test {
 		assert_eq!(cpu.register_a, 0b0100_0000)
 	}
 
+	#[test]
+	fn test_arr() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(vec![0xa9, 0xff, AAR.code, 0xff]);
+		assert_eq!(cpu.register_a, 0x7f);
+		assert!(cpu.status.get_carry());
+		assert!(!cpu.status.get_overflow());
+	}
+
+	#[test]
+	fn test_arr_decimal() {
+		let mut cpu = Cpu::new();
+		cpu.variant = CpuVariant::Nmos6502;
+		cpu.interpret(vec![SED.code, 0xa9, 0xff, AAR.code, 0xff]);
+		assert_eq!(cpu.register_a, 0xd5);
+		assert!(cpu.status.get_carry());
+		assert!(!cpu.status.get_negative());
+	}
+
+	#[test]
+	fn test_2a03_ignores_decimal() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(vec![SED.code, 0xa9, 0x09, ADC1.code, 0x01]);
+		assert_eq!(cpu.register_a, 0x0a);
+	}
+
+	fn to_bcd(value: u8) -> u8 {
+		((value / 10) << 4) | (value % 10)
+	}
+
+	#[test]
+	fn test_decimal_arithmetic() {
+		let mut cpu = Cpu::new();
+		cpu.variant = CpuVariant::Nmos6502;
+		for a in 0..100u8 {
+			for b in 0..100u8 {
+				for carry in [false, true] {
+					let clc_or_sec = if carry { SEC.code } else { CLC.code };
+
+					cpu.interpret(vec![SED.code, clc_or_sec, 0xa9, to_bcd(a), ADC1.code, to_bcd(b)]);
+					let sum = a as u16 + b as u16 + carry as u16;
+					assert_eq!(cpu.register_a, to_bcd((sum % 100) as u8));
+					assert_eq!(cpu.status.get_carry(), sum >= 100);
+
+					cpu.interpret(vec![SED.code, clc_or_sec, 0xa9, to_bcd(a), SBC1.code, to_bcd(b)]);
+					let difference = a as i16 - b as i16 - !carry as i16;
+					assert_eq!(cpu.register_a, to_bcd(difference.rem_euclid(100) as u8));
+					assert_eq!(cpu.status.get_carry(), difference >= 0);
+				}
+			}
+		}
+	}
+
+	// Bruce Clark's decimal mode test from the 6502.org tutorial, the program Klaus
+	// Dormann's 6502_decimal_test is built on. It predicts every ADC and SBC result,
+	// invalid BCD included, using binary arithmetic only, and leaves 0 in ERROR when
+	// the chip agrees with all of them.
+	#[test]
+	fn test_decimal_mode_program() {
+		const ERROR: u16 = 0x10;
+		const CMOS: u16 = 0x21;
+		let source = include_str!("../../tests/data/decimal_test.s");
+		for variant in [CpuVariant::Nmos6502, CpuVariant::Wdc65C02] {
+			let program = Assembler::new(variant).assemble(source).unwrap().bytes;
+			assert!(program.len() <= 0x200);
+			let mut cpu = Cpu::new();
+			cpu.variant = variant;
+			cpu.load(program);
+			cpu.reset();
+			cpu.write(CMOS, (variant == CpuVariant::Wdc65C02) as u8);
+			cpu.run().unwrap();
+			assert_eq!(cpu.program_counter, 0x0603, "{:?}", variant);
+			assert_eq!(cpu.read(ERROR), 0, "{:?}", variant);
+		}
+	}
+
+	#[test]
+	fn test_decimal_nmos_flags() {
+		let mut cpu = Cpu::new();
+		cpu.variant = CpuVariant::Nmos6502;
+		cpu.interpret(vec![SED.code, 0xa9, 0x99, ADC1.code, 0x01]);
+		assert_eq!(cpu.register_a, 0x00);
+		assert!(cpu.status.get_carry());
+		assert!(cpu.status.get_negative());
+		assert!(!cpu.status.get_zero());
+		assert!(!cpu.status.get_overflow());
+
+		cpu.interpret(vec![SED.code, SEC.code, 0xa9, 0x00, SBC1.code, 0x01]);
+		assert_eq!(cpu.register_a, 0x99);
+		assert!(!cpu.status.get_carry());
+		assert!(cpu.status.get_negative());
+	}
+
 	#[test]
 	fn test_txa_move_x_to_a() {
 		let mut cpu = Cpu::new();
@@ -1210,11 +1809,11 @@ mod test {
 	#[test]
 	fn test_jmp() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0x6C, 10, 0x00]);
-		assert_eq!(cpu.program_counter, 2);
+		cpu.interpret(vec![0x6C, 0x03, 0x06, 0x10, 0x06]);
+		assert_eq!(cpu.program_counter, 0x0610);
 
 		cpu.interpret(vec![0x4C, 10, 0x00]);
-		assert_eq!(cpu.program_counter, 12);
+		assert_eq!(cpu.program_counter, 10);
 	}
 
 	#[test]
@@ -1345,70 +1944,70 @@ mod test {
 	#[test]
 	fn test_bvs_clear() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![BVS.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![BVS.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0)
 	}
 
 	#[test]
 	fn test_bvs_set() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0x50, 0x69, 0x50, BVS.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0x50, 0x69, 0x50, BVS.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bvc_clear() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![BVC.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![BVC.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bvc_set() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0x50, 0x69, 0x50, BVC.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0x50, 0x69, 0x50, BVC.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0x50 + 0x50)
 	}
 
 	#[test]
 	fn test_bpl_negative() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0b1000_0000, BPL.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0b1000_0000, BPL.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0b1000_0000)
 	}
 
 	#[test]
 	fn test_bpl_positive() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0b0000_0000, BPL.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0b0000_0000, BPL.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bne_zero() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0, BNE.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0, BNE.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0)
 	}
 
 	#[test]
 	fn test_bne_not_zero() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 1, BNE.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 1, BNE.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bmi_negative() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0b1000_0000, BMI.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0b1000_0000, BMI.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bmi_positive() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0b0000_0000, BMI.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0b0000_0000, BMI.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0)
 	}
 
@@ -1416,42 +2015,42 @@ mod test {
 	#[test]
 	fn test_beq_zero() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0, BEQ.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0, BEQ.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_beq_not_zero() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 1, BEQ.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 1, BEQ.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 1)
 	}
 
 	#[test]
 	fn test_bcs_clear() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![BCS.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![BCS.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0)
 	}
 
 	#[test]
 	fn test_bcs_set() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0xff, 0x69, 1, BCS.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0xff, 0x69, 1, BCS.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bcc_clear() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![BCC.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![BCC.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 4)
 	}
 
 	#[test]
 	fn test_bcc_set() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0xff, 0x69, 1, BCC.code, 0x00, 0x00, 0xa9, 4, 0x00]);
+		cpu.interpret(vec![0xa9, 0xff, 0x69, 1, BCC.code, 0x01, 0x00, 0xa9, 4, 0x00]);
 		assert_eq!(cpu.register_a, 0)
 	}
 
@@ -1465,15 +2064,61 @@ mod test {
 	#[test]
 	fn test_axa_indirect() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0b1100_0110, 0xa2, 0b0110_0011, 0x93, 0x00]);
-		assert!(cpu.bus.vram_contains(0b0000_0010));
+		cpu.write_u16(0x10, 0x0120);
+		cpu.interpret(vec![0xa9, 0b1100_0110, 0xa2, 0b0110_0011, 0x93, 0x10]);
+		assert_eq!(cpu.read(0x0120), 0b0000_0010);
 	}
 
 	#[test]
 	fn test_axa_absolute() {
 		let mut cpu = Cpu::new();
-		cpu.interpret(vec![0xa9, 0b1100_0110, 0xa2, 0b0110_0011, AXA1.code, 0x00]);
-		assert!(cpu.bus.vram_contains(0b0000_0010));
+		cpu.interpret(vec![0xa9, 0b1100_0110, 0xa2, 0b0110_0011, AXA1.code, 0x20, 0x01]);
+		assert_eq!(cpu.read(0x0120), 0b0000_0010);
+	}
+
+	#[test]
+	fn test_sxa_page_cross_corrupts_address() {
+		let mut cpu = Cpu::new();
+		cpu.write(0x0010, 0xAA);
+		cpu.write(0x0210, 0xAA);
+		cpu.interpret(vec![0xa2, 0x01, 0xa0, 0x20, SXA.code, 0xF0, 0x01]);
+		assert_eq!(cpu.read(0x0010), 0x00);
+		assert_eq!(cpu.read(0x0210), 0xAA);
+	}
+
+	#[test]
+	fn test_sya_no_page_cross() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(vec![0xa0, 0xFF, 0xa2, 0x01, SYA.code, 0x20, 0x03]);
+		assert_eq!(cpu.read(0x0321), 0x04);
+	}
+
+	#[test]
+	fn test_xas() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(vec![0xa9, 0b1111_0011, 0xa2, 0b0011_1111, XAS.code, 0x00, 0x01]);
+		assert_eq!(cpu.stack_pointer, 0b0011_0011);
+		assert_eq!(cpu.read(0x0100), 0b0000_0010);
+	}
+
+	#[test]
+	fn test_xaa_magic() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(vec![0xa2, 0xff, XAA.code, 0xff]);
+		assert_eq!(cpu.register_a, 0xEE);
+
+		cpu.ane_magic = 0xff;
+		cpu.interpret(vec![0xa2, 0x0f, XAA.code, 0xfc]);
+		assert_eq!(cpu.register_a, 0x0c);
+	}
+
+	#[test]
+	fn test_atx_magic() {
+		let mut cpu = Cpu::new();
+		cpu.lxa_magic = 0x00;
+		cpu.interpret(vec![ATX.code, 0xff]);
+		assert_eq!(cpu.register_a, 0);
+		assert!(cpu.status.get_zero());
 	}
 
 	#[test]
@@ -1483,4 +2128,560 @@ mod test {
 		assert_eq!(cpu.register_a, cpu.register_x);
 		assert_eq!(cpu.register_x, 0b1000_0010)
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn test_kil_jams() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(vec![0xa9, 1, KIL[0].code, 0xa9, 2, 0x00]);
+		assert!(cpu.jammed);
+		assert_eq!(cpu.register_a, 1);
+		assert_eq!(cpu.program_counter, 0x0602);
+
+		assert_eq!(cpu.step(), Ok(()));
+		assert_eq!(cpu.program_counter, 0x0602);
+
+		cpu.reset();
+		assert!(!cpu.jammed);
+	}
+
+	#[test]
+	fn test_undefined_policy_error() {
+		let mut cpu = Cpu::new();
+		cpu.undefined_policy = UndefinedPolicy::Error;
+		cpu.load(vec![LAX1.code, 0x00, KIL[1].code]);
+		cpu.reset();
+		assert_eq!(cpu.run(), Err(CpuError::UndefinedOpcode { opcode: 0xA7, address: 0x0600 }));
+
+		cpu.program_counter = 0x0602;
+		let jam = Err(CpuError::Jammed { opcode: 0x12, address: 0x0602 });
+		assert_eq!(cpu.step(), jam);
+		assert!(cpu.jammed);
+		assert_eq!(cpu.step(), jam);
+	}
+
+	#[test]
+	#[should_panic(expected = "CPU jammed by opcode $02 at $0600")]
+	fn test_undefined_policy_panic() {
+		let mut cpu = Cpu::new();
+		cpu.undefined_policy = UndefinedPolicy::Panic;
+		cpu.interpret(vec![KIL[0].code]);
+	}
+
+	fn run_cycles(program: Vec<u8>, setup: fn(&mut Cpu)) -> Cpu {
+		let mut cpu = Cpu::new();
+		cpu.load(program);
+		cpu.reset();
+		setup(&mut cpu);
+		cpu.cycle_log = Some(Vec::new());
+		cpu.step().unwrap();
+		cpu
+	}
+
+	#[test]
+	fn test_cycle_counts_match_table() {
+		for def in OPCODES.iter() {
+			if def.category == Category::Jam || def.category == Category::Branch {
+				continue;
+			}
+			let cpu = run_cycles(vec![def.code, 0x00, 0x00], |_| {});
+			assert_eq!(cpu.cycles, def.cycles as u64, "opcode ${:02X}", def.code);
+		}
+	}
+
+	#[test]
+	fn test_branch_cycles() {
+		let cpu = run_cycles(vec![BCS.code, 0x05], |_| {});
+		assert_eq!(cpu.cycles, 2);
+		assert_eq!(cpu.program_counter, 0x0602);
+
+		let cpu = run_cycles(vec![BCC.code, 0x05], |_| {});
+		assert_eq!(cpu.cycles, 3);
+		assert_eq!(cpu.program_counter, 0x0607);
+
+		let cpu = run_cycles(vec![BCC.code, 0x80], |_| {});
+		assert_eq!(cpu.program_counter, 0x0582);
+		assert_eq!(cpu.cycle_log.unwrap(), vec![
+			BusCycle::read(0x0600, 0x90),
+			BusCycle::read(0x0601, 0x80),
+			BusCycle::read(0x0602, 0x00),
+			BusCycle::read(0x0682, 0x00),
+		]);
+	}
+
+	// The cycle lists below give the address, value and direction of every bus access,
+	// dummy accesses included. More are checked by single_step.rs.
+	#[test]
+	fn test_read_page_cross_cycles() {
+		let cpu = run_cycles(vec![LDA5.code, 0xFF, 0x00], |cpu| {
+			cpu.register_x = 1;
+			cpu.write(0x0100, 0x42);
+		});
+		assert_eq!(cpu.register_a, 0x42);
+		assert_eq!(cpu.cycle_log.unwrap(), vec![
+			BusCycle::read(0x0600, 0xBD),
+			BusCycle::read(0x0601, 0xFF),
+			BusCycle::read(0x0602, 0x00),
+			BusCycle::read(0x0000, 0x00),
+			BusCycle::read(0x0100, 0x42),
+		]);
+	}
+
+	#[test]
+	fn test_write_dummy_read_cycles() {
+		let cpu = run_cycles(vec![STA4.code, 0x10, 0x00], |cpu| {
+			cpu.register_a = 0x33;
+			cpu.register_x = 1;
+		});
+		assert_eq!(cpu.cycle_log.unwrap(), vec![
+			BusCycle::read(0x0600, 0x9D),
+			BusCycle::read(0x0601, 0x10),
+			BusCycle::read(0x0602, 0x00),
+			BusCycle::read(0x0011, 0x00),
+			BusCycle::write(0x0011, 0x33),
+		]);
+	}
+
+	#[test]
+	fn test_read_modify_write_cycles() {
+		let cpu = run_cycles(vec![INC2.code, 0x10], |cpu| {
+			cpu.register_x = 5;
+			cpu.write(0x0015, 0x7F);
+		});
+		assert!(cpu.status.get_negative());
+		assert_eq!(cpu.cycle_log.unwrap(), vec![
+			BusCycle::read(0x0600, 0xF6),
+			BusCycle::read(0x0601, 0x10),
+			BusCycle::read(0x0010, 0x00),
+			BusCycle::read(0x0015, 0x7F),
+			BusCycle::write(0x0015, 0x7F),
+			BusCycle::write(0x0015, 0x80),
+		]);
+	}
+
+	#[test]
+	fn test_subroutine_cycles() {
+		let mut cpu = run_cycles(vec![JSR.code, 0x00, 0x07], |cpu| cpu.write(0x0700, RTS.code));
+		assert_eq!(cpu.program_counter, 0x0700);
+		assert_eq!(cpu.cycle_log.take().unwrap(), vec![
+			BusCycle::read(0x0600, 0x20),
+			BusCycle::read(0x0601, 0x00),
+			BusCycle::read(0x01FD, 0x00),
+			BusCycle::write(0x01FD, 0x06),
+			BusCycle::write(0x01FC, 0x02),
+			BusCycle::read(0x0602, 0x07),
+		]);
+
+		cpu.cycle_log = Some(Vec::new());
+		cpu.step().unwrap();
+		assert_eq!(cpu.program_counter, 0x0603);
+		assert_eq!(cpu.stack_pointer, DEFAULT_STACK);
+		assert_eq!(cpu.cycle_log.unwrap(), vec![
+			BusCycle::read(0x0700, 0x60),
+			BusCycle::read(0x0701, 0x00),
+			BusCycle::read(0x01FB, 0x00),
+			BusCycle::read(0x01FC, 0x02),
+			BusCycle::read(0x01FD, 0x06),
+			BusCycle::read(0x0602, 0x07),
+		]);
+	}
+
+	#[test]
+	fn test_decode_table() {
+		for (code, def) in OPCODES.iter().enumerate() {
+			assert_eq!(def.code as usize, code);
+		}
+		assert_eq!(OPCODES.iter().filter(|def| def.official).count(), 151);
+		assert_eq!(get_instruction_def(0x00).name, "BRK");
+		assert_eq!(get_instruction_def(0x1A).name, "NOP");
+		assert!(!get_instruction_def(0x1A).official);
+		assert_eq!(get_instruction_def(0xFE).category, Category::ReadModifyWrite);
+		assert_eq!(get_instruction_def(0x9D).category, Category::Write);
+	}
+
+	fn cmos(cpu: &mut Cpu) {
+		cpu.variant = CpuVariant::Wdc65C02;
+	}
+
+	#[test]
+	fn test_cmos_cycle_counts_match_table() {
+		for def in CMOS_OPCODES.iter() {
+			if def.category == Category::Jam || def.category == Category::Branch {
+				continue;
+			}
+			let cpu = run_cycles(vec![def.code, 0x00, 0x00], cmos);
+			assert_eq!(cpu.cycles, def.cycles as u64, "opcode ${:02X}", def.code);
+		}
+	}
+
+	#[test]
+	fn test_cmos_decode_table() {
+		for (code, def) in CMOS_OPCODES.iter().enumerate() {
+			assert_eq!(def.code as usize, code);
+		}
+		assert_eq!(CMOS_OPCODES[0x80].name, "BRA");
+		assert_eq!(CMOS_OPCODES[0xA9].name, "LDA");
+		assert!(CMOS_OPCODES.iter().all(|def| def.category != Category::Jam || def.code == 0xDB));
+	}
+
+	#[test]
+	fn test_cmos_bra() {
+		let cpu = run_cycles(vec![cmos::BRA.code, 0x05], cmos);
+		assert_eq!(cpu.program_counter, 0x0607);
+		assert_eq!(cpu.cycles, 3);
+	}
+
+	#[test]
+	fn test_cmos_phx_plx() {
+		let mut cpu = Cpu::new();
+		cpu.variant = CpuVariant::Wdc65C02;
+		cpu.load(vec![0xA2, 0x80, cmos::PHX.code, 0xA2, 0x00, cmos::PLX.code]);
+		cpu.reset();
+		cpu.run().unwrap();
+		assert_eq!(cpu.register_x, 0x80);
+		assert!(cpu.status.get_negative());
+		assert_eq!(cpu.stack_pointer, DEFAULT_STACK);
+	}
+
+	#[test]
+	fn test_cmos_stz() {
+		let cpu = run_cycles(vec![cmos::STZ1.code, 0x10], |cpu| {
+			cmos(cpu);
+			cpu.write(0x10, 0x55);
+		});
+		assert_eq!(cpu.peek(0x10), 0x00);
+	}
+
+	#[test]
+	fn test_cmos_tsb_trb() {
+		let cpu = run_cycles(vec![cmos::TSB1.code, 0x10], |cpu| {
+			cmos(cpu);
+			cpu.register_a = 0x0F;
+			cpu.write(0x10, 0xF0);
+		});
+		assert_eq!(cpu.peek(0x10), 0xFF);
+		assert!(cpu.status.get_zero());
+
+		let cpu = run_cycles(vec![cmos::TRB1.code, 0x10], |cpu| {
+			cmos(cpu);
+			cpu.register_a = 0x0F;
+			cpu.write(0x10, 0xFF);
+		});
+		assert_eq!(cpu.peek(0x10), 0xF0);
+		assert!(!cpu.status.get_zero());
+	}
+
+	#[test]
+	fn test_cmos_zero_page_indirect() {
+		let cpu = run_cycles(vec![cmos::LDA9.code, 0xFF], |cpu| {
+			cmos(cpu);
+			cpu.write(0xFF, 0x00);
+			cpu.write(0x00, 0x07);
+			cpu.write(0x0700, 0x42);
+		});
+		assert_eq!(cpu.register_a, 0x42);
+		assert_eq!(cpu.cycles, 5);
+	}
+
+	#[test]
+	fn test_cmos_bit_immediate() {
+		let cpu = run_cycles(vec![cmos::BIT3.code, 0xC0], |cpu| {
+			cmos(cpu);
+			cpu.register_a = 0x01;
+		});
+		assert!(cpu.status.get_zero());
+		assert!(!cpu.status.get_negative());
+		assert!(!cpu.status.get_overflow());
+	}
+
+	#[test]
+	fn test_cmos_inc_dec_accumulator() {
+		let cpu = run_cycles(vec![cmos::INC_A.code], |cpu| {
+			cmos(cpu);
+			cpu.register_a = 0xFF;
+		});
+		assert_eq!(cpu.register_a, 0x00);
+		assert!(cpu.status.get_zero());
+
+		let cpu = run_cycles(vec![cmos::DEC_A.code], cmos);
+		assert_eq!(cpu.register_a, 0xFF);
+		assert!(cpu.status.get_negative());
+	}
+
+	#[test]
+	fn test_cmos_jmp_indirect_crosses_page() {
+		let cpu = run_cycles(vec![cmos::JMP2.code, 0xFF, 0x06], |cpu| {
+			cmos(cpu);
+			cpu.write(0x06FF, 0x34);
+			cpu.write(0x0700, 0x12);
+		});
+		assert_eq!(cpu.program_counter, 0x1234);
+		assert_eq!(cpu.cycles, 6);
+	}
+
+	#[test]
+	fn test_cmos_jmp_indexed_indirect() {
+		let cpu = run_cycles(vec![cmos::JMP3.code, 0x00, 0x07], |cpu| {
+			cmos(cpu);
+			cpu.register_x = 2;
+			cpu.write(0x0702, 0x34);
+			cpu.write(0x0703, 0x12);
+		});
+		assert_eq!(cpu.program_counter, 0x1234);
+	}
+
+	#[test]
+	fn test_cmos_memory_bits() {
+		let cpu = run_cycles(vec![cmos::SMB7.code, 0x10], |cpu| {
+			cmos(cpu);
+			cpu.write(0x10, 0x01);
+		});
+		assert_eq!(cpu.peek(0x10), 0x81);
+
+		let cpu = run_cycles(vec![cmos::RMB0.code, 0x10], |cpu| {
+			cmos(cpu);
+			cpu.write(0x10, 0x81);
+		});
+		assert_eq!(cpu.peek(0x10), 0x80);
+
+		let cpu = run_cycles(vec![cmos::BBS7.code, 0x10, 0x05], |cpu| {
+			cmos(cpu);
+			cpu.write(0x10, 0x80);
+		});
+		assert_eq!(cpu.program_counter, 0x0608);
+		assert_eq!(cpu.cycles, 6);
+
+		let cpu = run_cycles(vec![cmos::BBR0.code, 0x10, 0x05], |cpu| {
+			cmos(cpu);
+			cpu.write(0x10, 0x01);
+		});
+		assert_eq!(cpu.program_counter, 0x0603);
+		assert_eq!(cpu.cycles, 5);
+	}
+
+	#[test]
+	fn test_cmos_stp_and_wai() {
+		let mut cpu = run_cycles(vec![cmos::STP.code], cmos);
+		assert!(cpu.jammed);
+		cpu.reset();
+		assert!(!cpu.jammed);
+
+		let mut cpu = run_cycles(vec![cmos::WAI.code, 0xE8], cmos);
+		assert!(cpu.waiting);
+		cpu.step().unwrap();
+		assert_eq!(cpu.register_x, 0);
+		cpu.reset();
+		assert!(!cpu.waiting);
+	}
+
+	#[test]
+	fn test_cmos_run_stops_at_wai() {
+		let mut cpu = Cpu::new();
+		cpu.variant = CpuVariant::Wdc65C02;
+		cpu.load(vec![INX.code, cmos::WAI.code, INX.code, BRK.code]);
+		cpu.reset();
+		cpu.run().unwrap();
+		assert!(cpu.waiting);
+		assert_eq!(cpu.register_x, 1);
+
+		cpu.irq();
+		cpu.run().unwrap();
+		assert_eq!(cpu.register_x, 2);
+		assert_eq!(cpu.program_counter, 0x0603);
+	}
+
+	#[test]
+	fn test_interrupts() {
+		let mut cpu = Cpu::new();
+		cpu.load(vec![CLI.code, INX.code, INX.code]);
+		cpu.reset();
+		cpu.step().unwrap();
+		cpu.cycle_log = Some(Vec::new());
+		cpu.nmi();
+		// Nothing answers at the vector without a cartridge, so both bytes read back
+		// the pushed flags from the open bus.
+		assert_eq!(cpu.program_counter, 0x2020);
+		assert_eq!(cpu.stack_pointer, DEFAULT_STACK - 3);
+		assert_eq!(cpu.read(0x01FD), 0x06);
+		assert_eq!(cpu.read(0x01FC), 0x01);
+		// B clear, the unused bit set, and I as CLI left it.
+		assert_eq!(cpu.read(0x01FB), 0b0010_0000);
+		assert!(cpu.status.get_interrupt());
+		assert_eq!(cpu.cycle_log.take().unwrap().len(), 7);
+
+		// IRQs wait for the I flag to clear.
+		let mut cpu = Cpu::new();
+		cpu.load(vec![INX.code]);
+		cpu.reset();
+		cpu.irq();
+		assert_eq!(cpu.program_counter, 0x0600);
+		cpu.status.set_interrupt(false);
+		cpu.irq();
+		assert_eq!(cpu.program_counter, 0x2020);
+	}
+
+	#[test]
+	fn test_cmos_interrupt_wakes_wai() {
+		let mut cpu = run_cycles(vec![cmos::WAI.code, 0xE8], cmos);
+		assert!(cpu.waiting);
+		// With I set the CPU just continues after the WAI.
+		cpu.irq();
+		assert!(!cpu.waiting);
+		cpu.step().unwrap();
+		assert_eq!(cpu.register_x, 1);
+	}
+
+	#[test]
+	fn test_cmos_read_modify_write_cycles() {
+		let cpu = run_cycles(vec![INC1.code, 0x10], |cpu| {
+			cmos(cpu);
+			cpu.write(0x10, 0x41);
+		});
+		assert_eq!(cpu.cycle_log.unwrap(), vec![
+			BusCycle::read(0x0600, 0xE6),
+			BusCycle::read(0x0601, 0x10),
+			BusCycle::read(0x0010, 0x41),
+			BusCycle::read(0x0010, 0x41),
+			BusCycle::write(0x0010, 0x42),
+		]);
+	}
+
+	#[test]
+	fn test_cmos_decimal_flags() {
+		let cpu = run_cycles(vec![ADC1.code, 0x01], |cpu| {
+			cmos(cpu);
+			cpu.status.set_decimal(true);
+			cpu.register_a = 0x99;
+		});
+		assert_eq!(cpu.register_a, 0x00);
+		assert!(cpu.status.get_zero());
+		assert!(!cpu.status.get_negative());
+		assert!(cpu.status.get_carry());
+		assert_eq!(cpu.cycles, 3);
+
+		let cpu = run_cycles(vec![SBC1.code, 0x01], |cpu| {
+			cmos(cpu);
+			cpu.status.set_decimal(true);
+			cpu.status.set_carry(true);
+			cpu.register_a = 0x00;
+		});
+		assert_eq!(cpu.register_a, 0x99);
+		assert!(cpu.status.get_negative());
+		assert!(!cpu.status.get_carry());
+	}
+
+	#[test]
+	fn test_cmos_brk_clears_decimal() {
+		let cpu = run_cycles(vec![BRK.code, 0x00], |cpu| {
+			cmos(cpu);
+			cpu.status.set_decimal(true);
+		});
+		assert!(!cpu.status.get_decimal());
+	}
+
+	#[test]
+	fn test_assembled_program() {
+		let mut cpu = Cpu::new();
+		cpu.interpret(crate::assemble!(
+			"        ldx #$05",
+			"        lda #$00",
+			"        clc",
+			"loop:   adc #$03",
+			"        dex",
+			"        bne loop",
+			"        sta $10",
+		));
+		assert_eq!(cpu.register_a, 15);
+		assert_eq!(cpu.read(0x10), 15);
+	}
+
+	fn machine_trace(cpu: &mut Cpu, steps: usize) -> Vec<(u16, u8, u8, u8, u8, u8, u64)> {
+		(0..steps).map(|_| {
+			cpu.step().unwrap();
+			(cpu.program_counter, cpu.register_a, cpu.register_x, cpu.register_y, cpu.status.0, cpu.stack_pointer, cpu.cycles)
+		}).collect()
+	}
+
+	#[test]
+	fn test_save_state_round_trip() {
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assemble!(
+			"loop:   inx",
+			"        txa",
+			"        adc $10",
+			"        sta $10",
+			"        sta $0200,x",
+			"        pha",
+			"        jsr sub",
+			"        pla",
+			"        jmp loop",
+			"sub:    rol $11",
+			"        rts",
+		));
+		cpu.reset();
+		machine_trace(&mut cpu, 100);
+		let state = cpu.save_state();
+
+		let expected = machine_trace(&mut cpu, 500);
+		let mut restored = Cpu::new();
+		restored.load_state(&state).unwrap();
+		assert_eq!(machine_trace(&mut restored, 500), expected);
+		assert_eq!(restored.save_state(), cpu.save_state());
+	}
+
+	#[test]
+	fn test_save_state_prg_ram() {
+		use crate::cartridge::{Cartridge, Mirroring};
+		let cartridge = Cartridge {
+			prg_rom: vec![0; 0x4000],
+			chr_rom: Vec::new(),
+			prg_ram: vec![0; 0x2000],
+			mapper: 0,
+			mirroring: Mirroring::Vertical,
+			battery: true,
+			region: None,
+		};
+		let mut cpu = Cpu::new();
+		cpu.bus.insert(cartridge.clone());
+		cpu.write(0x6000, 0x12);
+		cpu.write(0x7FFF, 0x34);
+		let state = cpu.save_state();
+
+		let mut restored = Cpu::new();
+		restored.bus.insert(cartridge);
+		restored.load_state(&state).unwrap();
+		assert_eq!(restored.read(0x6000), 0x12);
+		assert_eq!(restored.read(0x7FFF), 0x34);
+
+		// A state from a cartridge with RAM doesn't fit a machine without one.
+		let mut empty = Cpu::new();
+		assert_eq!(empty.load_state(&state), Err(StateError::Invalid { field: "PRG-RAM size", value: 0x2000 }));
+	}
+
+	#[test]
+	fn test_load_state_errors() {
+		let mut cpu = Cpu::new();
+		cpu.variant = CpuVariant::Wdc65C02;
+		cpu.cycles = 1234;
+		let state = cpu.save_state();
+		assert_eq!(&state[..8], crate::state::MAGIC);
+
+		let mut target = Cpu::new();
+		assert_eq!(target.load_state(b"garbage"), Err(StateError::BadMagic));
+
+		let mut newer = state.clone();
+		newer[8] = 99;
+		assert_eq!(target.load_state(&newer), Err(StateError::UnsupportedVersion { found: 99, supported: crate::state::VERSION }));
+
+		assert_eq!(target.load_state(&state[..state.len() - 1]), Err(StateError::Truncated));
+
+		let mut invalid = state.clone();
+		invalid[27] = 7;
+		assert_eq!(target.load_state(&invalid), Err(StateError::Invalid { field: "CPU variant", value: 7 }));
+		assert_eq!(target.variant, CpuVariant::Ricoh2A03);
+		assert_eq!(target.cycles, 0);
+
+		target.load_state(&state).unwrap();
+		assert_eq!(target.variant, CpuVariant::Wdc65C02);
+		assert_eq!(target.cycles, 1234);
+	}
+}
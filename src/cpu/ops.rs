@@ -1,12 +1,31 @@
 use super::AddressingMode;
 use super::AddressingMode::*;
 use super::Cpu;
+use Category::*;
+
+// Describes how an instruction touches the bus, which is what tracers and
+// disassemblers care about more than the exact operation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+	Internal,
+	Read,
+	Write,
+	ReadModifyWrite,
+	Branch,
+	Jump,
+	Stack,
+	Jam,
+}
 
+#[derive(Clone, Copy)]
 pub struct OpCodeDef {
 	pub code: u8,
+	pub name: &'static str,
 	pub len: u8,
 	pub cycles: u8,
 	pub mode: AddressingMode,
+	pub category: Category,
+	pub official: bool,
 	pub instruction: fn(&mut Cpu, &AddressingMode),
 }
 
@@ -17,319 +36,360 @@ impl OpCodeDef {
 	}
 }
 
+// Places every definition in the slot of its opcode. Compilation fails if an
+// opcode is defined twice or left out, so the table always covers all 256 codes.
+pub(crate) const fn decode_table(defs: &[OpCodeDef]) -> [OpCodeDef; 256] {
+	let mut table = [defs[0]; 256];
+	let mut filled = [false; 256];
+	let mut i = 0;
+	while i < defs.len() {
+		let code = defs[i].code as usize;
+		if filled[code] {
+			panic!("opcode defined more than once");
+		}
+		filled[code] = true;
+		table[code] = defs[i];
+		i += 1;
+	}
+	let mut code = 0;
+	while code < 256 {
+		if !filled[code] {
+			panic!("opcode missing from the decode table");
+		}
+		code += 1;
+	}
+	table
+}
+
+// Overrides slots of another table. Every unofficial opcode of the base table has
+// to be given a new meaning, since variants document the whole opcode space.
+pub(crate) const fn patch_table(mut table: [OpCodeDef; 256], defs: &[OpCodeDef]) -> [OpCodeDef; 256] {
+	let mut patched = [false; 256];
+	let mut i = 0;
+	while i < defs.len() {
+		let code = defs[i].code as usize;
+		if patched[code] {
+			panic!("opcode defined more than once");
+		}
+		patched[code] = true;
+		table[code] = defs[i];
+		i += 1;
+	}
+	let mut code = 0;
+	while code < 256 {
+		if !patched[code] && !table[code].official {
+			panic!("unofficial opcode left over from the base table");
+		}
+		code += 1;
+	}
+	table
+}
+
+// Single opcodes come first, each with its own constant. After the semicolon come
+// groups of opcodes that only differ in their code, like the unofficial NOPs, which
+// are listed once and get a constant holding all of their definitions.
 #[macro_export]
 macro_rules! map {
-	( $( $ident:ident, $op:expr, $len:expr, $cycles:expr, $mode:expr, $fn:expr ),* ) => {
-		pub const UNSUPPORTED: OpCodeDef = OpCodeDef {
-			code: 0,
-			len: 1,
-			cycles: 2,
-			mode: AddressingMode::Implied,
-			instruction: (|_,_|{}),
-		};
+	( $table:ident;
+		$( $ident:ident, $op:expr, $name:expr, $len:expr, $cycles:expr, $mode:expr, $category:expr, $official:expr, $fn:expr ),*;
+		$( $group:ident, [$( $code:expr ),*], $gname:expr, $glen:expr, $gcycles:expr, $gmode:expr, $gcategory:expr, $gofficial:expr, $gfn:expr ),*
+	) => {
 		$(
-			pub const $ident: OpCodeDef = OpCodeDef { code: $op, len: $len, cycles: $cycles, mode: $mode, instruction: $fn };
+			pub const $ident: OpCodeDef = OpCodeDef {
+				code: $op,
+				name: $name,
+				len: $len,
+				cycles: $cycles,
+				mode: $mode,
+				category: $category,
+				official: $official,
+				instruction: $fn
+			};
 		)*
 
-		pub const fn get_instruction_def<'a>(code: u8) -> &'a OpCodeDef {
-			match code {
-				$($op => &$ident,)*
-				_ => &UNSUPPORTED
-			}
-		}
+		$(
+			pub const $group: &[OpCodeDef] = &[$(
+				OpCodeDef {
+					code: $code,
+					name: $gname,
+					len: $glen,
+					cycles: $gcycles,
+					mode: $gmode,
+					category: $gcategory,
+					official: $gofficial,
+					instruction: $gfn
+				}
+			),*];
+		)*
+
+		pub const $table: &[OpCodeDef] = &[$($ident,)* $($(OpCodeDef { code: $code, ..$group[0] }),*),*];
 	};
 }
 
-map!(
-	TAX,	0xAA, 1, 2, Implied,	Cpu::tax,
-	TAY,	0xA8, 1, 2, Implied,	Cpu::tay,
-	INX,	0xE8, 1, 2, Implied,	Cpu::inx,
-	INY,	0xC8, 1, 2, Implied,	Cpu::iny,
-	DEX,	0xCA, 1, 2, Implied,	Cpu::dex,
-	DEY,	0x88, 1, 2, Implied,	Cpu::dey,
-	TXA,	0x8A, 1, 2, Implied,	Cpu::txa,
-	TYA,	0x98, 1, 2, Implied,	Cpu::tya,
-	BCC,	0x90, 2, 2, Implied,	Cpu::bcc,
-	BCS,	0xB0, 2, 2, Implied,	Cpu::bcs,
-	BEQ,	0xF0, 2, 2, Implied,	Cpu::beq,
-	BMI,	0x30, 2, 2, Implied,	Cpu::bmi,
-	BNE,	0xD0, 2, 2, Implied,	Cpu::bne,
-	BPL,	0x10, 2, 2, Implied,	Cpu::bpl,
-	BVC,	0x50, 2, 2, Implied,	Cpu::bvc,
-	BVS,	0x70, 2, 2, Implied,	Cpu::bvs,
-	CLC,	0x18, 1, 2, Implied,	Cpu::clc,
-	CLD,	0xD8, 1, 2, Implied,	Cpu::cld,
-	CLI,	0x58, 1, 2, Implied,	Cpu::cli,
-	CLV,	0xB8, 1, 2, Implied,	Cpu::clv,
-	RTS,	0x60, 1, 6, Implied,	Cpu::rts,
-	SEC,	0x38, 1, 2, Implied,	Cpu::sec,
-	SED,	0xF8, 1, 2, Implied,	Cpu::sed,
-	SEI,	0x78, 1, 2, Implied,	Cpu::sei,
-	TSX,	0xBA, 1, 2, Implied,	Cpu::tsx,
-	TXS,	0x9A, 1, 2, Implied,	Cpu::txs,
-	JSR,	0x20, 3, 6, Absolute,	Cpu::jsr,
-	PHA,	0x48, 1, 3, Implied,	Cpu::pha,
-	PLA,	0x68, 1, 4, Implied,	Cpu::pla,
-	PLP,	0x28, 1, 4, IndirectY,	Cpu::plp,
-	RTI,	0x40, 1, 6, Implied,	Cpu::rti,
-	PHP,	0x08, 1, 3, Implied,	Cpu::php,
-	AAR,	0x6B, 2, 2, Immediate,	Cpu::arr,
-	ASR,	0x4B, 2, 2, Immediate,	Cpu::asr,
-	ATX,	0xAB, 2, 2, Immediate,	Cpu::atx,
-	AXS,	0xCB, 2, 2, Immediate,	Cpu::axs,
-	LAR,	0xBB, 3, 4, Absolute,	Cpu::lar,
-	SXA,	0x9E, 3, 5, AbsoluteY,	Cpu::sxa,
-	SYA,	0x9C, 3, 5, AbsoluteX,	Cpu::sya,
-	XAA,	0x8B, 2, 2, Immediate,	Cpu::xaa,
-	XAS,	0x9B, 3, 5, AbsoluteY,	Cpu::xas,
-
-	NOP1,	0xEA, 1, 2, Implied,	Cpu::nop,
-	NOP2,	0x1A, 1, 2, Implied,	Cpu::nop,
-	NOP3,	0x3A, 1, 2, Implied,	Cpu::nop,
-	NOP4,	0x5A, 1, 2, Implied,	Cpu::nop,
-	NOP5,	0x7A, 1, 2, Implied,	Cpu::nop,
-	NOP6,	0xDA, 1, 2, Implied,	Cpu::nop,
-	NOP7,	0xFA, 1, 2, Implied,	Cpu::nop,
-
-	DOP1,	0x04, 2, 3, ZeroPage,	Cpu::nop,
-	DOP2,	0x14, 2, 4, ZeroPageX,	Cpu::nop,
-	DOP3,	0x34, 2, 4, ZeroPageX,	Cpu::nop,
-	DOP4,	0x44, 2, 3, ZeroPage,	Cpu::nop,
-	DOP5,	0x54, 2, 4, ZeroPageX,	Cpu::nop,
-	DOP6,	0x64, 2, 3, ZeroPage,	Cpu::nop,
-	DOP7,	0x74, 2, 4, ZeroPageX,	Cpu::nop,
-	DOP8,	0x80, 2, 2, Immediate,	Cpu::nop,
-	DOP9,	0x82, 2, 2, Immediate,	Cpu::nop,
-	DOP10,	0x89, 2, 2, Immediate,	Cpu::nop,
-	DOP11,	0xC2, 2, 2, Immediate,	Cpu::nop,
-	DOP12,	0xD4, 2, 4, ZeroPageX,	Cpu::nop,
-	DOP13,	0xE2, 2, 2, Immediate,	Cpu::nop,
-	DOP14,	0xF4, 2, 4, ZeroPageX,	Cpu::nop,
-
-	TOP1,	0x0C, 3, 4, Absolute,	Cpu::nop,
-	TOP2,	0x1C, 3, 4, AbsoluteX,	Cpu::nop,
-	TOP3,	0x3C, 3, 4, AbsoluteX,	Cpu::nop,
-	TOP4,	0x5C, 3, 4, AbsoluteX,	Cpu::nop,
-	TOP5,	0x7C, 3, 4, AbsoluteX,	Cpu::nop,
-	TOP6,	0xDC, 3, 4, AbsoluteX,	Cpu::nop,
-	TOP7,	0xFC, 3, 4, AbsoluteX,	Cpu::nop,
-
-	KIL1,	0x02, 0, 0, Implied,	Cpu::nop,
-	KIL2,	0x12, 0, 0, Implied,	Cpu::nop,
-	KIL3,	0x22, 0, 0, Implied,	Cpu::nop,
-	KIL4,	0x32, 0, 0, Implied,	Cpu::nop,
-	KIL5,	0x42, 0, 0, Implied,	Cpu::nop,
-	KIL6,	0x52, 0, 0, Implied,	Cpu::nop,
-	KIL7,	0x62, 0, 0, Implied,	Cpu::nop,
-	KIL8,	0x72, 0, 0, Implied,	Cpu::nop,
-	KIL9,	0x92, 0, 0, Implied,	Cpu::nop,
-	KIL10,	0xB2, 0, 0, Implied,	Cpu::nop,
-	KIL11,	0xD2, 0, 0, Implied,	Cpu::nop,
-	KIL12,	0xF2, 0, 0, Implied,	Cpu::nop,
-
-	LDA1,	0xA9, 2, 2, Immediate,	Cpu::lda,
-	LDA2,	0xA5, 2, 3, ZeroPage,	Cpu::lda,
-	LDA3,	0xB5, 2, 4, ZeroPageX,	Cpu::lda,
-	LDA4,	0xAD, 3, 4, Absolute,	Cpu::lda,
-	LDA5,	0xBD, 3, 4, AbsoluteX,	Cpu::lda,
-	LDA6,	0xB9, 3, 4, AbsoluteY,	Cpu::lda,
-	LDA7,	0xA1, 2, 6, IndirectX,	Cpu::lda,
-	LDA8,	0xB1, 2, 5, IndirectY,	Cpu::lda,
-
-	LDX1,	0xA2, 2, 2, Immediate,	Cpu::ldx,
-	LDX2,	0xA6, 2, 3, ZeroPage,	Cpu::ldx,
-	LDX3,	0xB6, 2, 4, ZeroPageY,	Cpu::ldx,
-	LDX4,	0xAE, 3, 4, Absolute,	Cpu::ldx,
-	LDX5,	0xBE, 3, 4, AbsoluteY,	Cpu::ldx,
-
-	LDY1,	0xA0, 2, 2, Immediate,	Cpu::ldy,
-	LDY2,	0xA4, 2, 3, ZeroPage,	Cpu::ldy,
-	LDY3,	0xB4, 2, 4, ZeroPageX,	Cpu::ldy,
-	LDY4,	0xAC, 3, 4, Absolute,	Cpu::ldy,
-	LDY5,	0xBC, 3, 4, AbsoluteX,	Cpu::ldy,
-
-	ASL1,	0x0A, 1, 2, Immediate,	Cpu::asl,
-	ASL2,	0x06, 2, 5, ZeroPage,	Cpu::asl,
-	ASL3,	0x16, 2, 6, ZeroPageX,	Cpu::asl,
-	ASL4,	0x0E, 3, 6, Absolute,	Cpu::asl,
-	ASL5,	0x1E, 3, 7, AbsoluteX,	Cpu::asl,
-
-	AND1,	0x29, 2, 2, Immediate,	Cpu::and,
-	AND2,	0x25, 2, 3, ZeroPage,	Cpu::and,
-	AND3,	0x35, 2, 4, ZeroPageX,	Cpu::and,
-	AND4,	0x2D, 3, 4, Absolute,	Cpu::and,
-	AND5,	0x3D, 3, 4, AbsoluteX, 	Cpu::and,
-	AND6,	0x39, 3, 4, AbsoluteY, 	Cpu::and,
-	AND7,	0x21, 2, 6, IndirectX, 	Cpu::and,
-	AND8,	0x31, 2, 5, IndirectY, 	Cpu::and,
-
-	EOR1,	0x49, 2, 2, Immediate,	Cpu::eor,
-	EOR2,	0x45, 2, 3, ZeroPage,	Cpu::eor,
-	EOR3,	0x55, 2, 4, ZeroPageX,	Cpu::eor,
-	EOR4,	0x4D, 3, 4, Absolute,	Cpu::eor,
-	EOR5,	0x5D, 3, 4, AbsoluteX, 	Cpu::eor,
-	EOR6,	0x59, 3, 4, AbsoluteY, 	Cpu::eor,
-	EOR7,	0x41, 2, 6, IndirectX, 	Cpu::eor,
-	EOR8,	0x51, 2, 5, IndirectY, 	Cpu::eor,
-
-	STA1,	0x85, 2, 3, ZeroPage,	Cpu::sta,
-	STA2,	0x95, 2, 4, ZeroPageX,	Cpu::sta,
-	STA3,	0x8D, 3, 4, Absolute,	Cpu::sta,
-	STA4,	0x9D, 3, 5, AbsoluteX, 	Cpu::sta,
-	STA5,	0x99, 3, 5, AbsoluteY, 	Cpu::sta,
-	STA6,	0x81, 2, 6, IndirectX, 	Cpu::sta,
-	STA7,	0x91, 2, 6, IndirectY, 	Cpu::sta,
-
-	ADC1,	0x69, 2, 2, Immediate,	Cpu::adc,
-	ADC2,	0x65, 2, 3, ZeroPage,	Cpu::adc,
-	ADC3,	0x75, 2, 4, ZeroPageX,	Cpu::adc,
-	ADC4,	0x6D, 3, 4, Absolute,	Cpu::adc,
-	ADC5,	0x7D, 3, 4, AbsoluteX, 	Cpu::adc,
-	ADC6,	0x79, 3, 4, AbsoluteY, 	Cpu::adc,
-	ADC7,	0x61, 2, 6, IndirectX, 	Cpu::adc,
-	ADC8,	0x71, 2, 5, IndirectY, 	Cpu::adc,
-
-	SBC1,	0xE9, 2, 2, Immediate,	Cpu::sbc,
-	SBC2,	0xE5, 2, 3, ZeroPage,	Cpu::sbc,
-	SBC3,	0xF5, 2, 4, ZeroPageX,	Cpu::sbc,
-	SBC4,	0xED, 3, 4, Absolute,	Cpu::sbc,
-	SBC5,	0xFD, 3, 4, AbsoluteX, 	Cpu::sbc,
-	SBC6,	0xF9, 3, 4, AbsoluteY, 	Cpu::sbc,
-	SBC7,	0xE1, 2, 6, IndirectX, 	Cpu::sbc,
-	SBC8,	0xF1, 2, 5, IndirectY, 	Cpu::sbc,
-	SBC9,	0xEB, 2, 2, Immediate,	Cpu::sbc,
-
-	BIT1,	0x24, 2, 3, ZeroPage, 	Cpu::bit,
-	BIT2,	0x2C, 3, 4, Absolute, 	Cpu::bit,
-
-	CMP1,	0xC9, 2, 2, Immediate,	Cpu::cmp,
-	CMP2,	0xC5, 2, 3, ZeroPage,	Cpu::cmp,
-	CMP3,	0xD5, 2, 4, ZeroPageX,	Cpu::cmp,
-	CMP4,	0xCD, 3, 4, Absolute,	Cpu::cmp,
-	CMP5,	0xDD, 3, 4, AbsoluteX,	Cpu::cmp,
-	CMP6,	0xD9, 3, 4, AbsoluteY,	Cpu::cmp,
-	CMP7,	0xC1, 2, 6, IndirectX,	Cpu::cmp,
-	CMP8,	0xD1, 2, 5, IndirectY,	Cpu::cmp,
-
-	CPX1,	0xE0, 2, 2, Immediate,	Cpu::cpx,
-	CPX2,	0xE4, 2, 3, ZeroPage,	Cpu::cpx,
-	CPX3,	0xEC, 3, 4, Absolute,	Cpu::cpx,
-
-	CPY1,	0xC0, 2, 2, Immediate,	Cpu::cpy,
-	CPY2,	0xC4, 2, 3, ZeroPage,	Cpu::cpy,
-	CPY3,	0xCC, 3, 4, Absolute,	Cpu::cpy,
-
-	JMP1,	0x4C, 3, 3, Absolute,	Cpu::jmp_absolute,
-	JMP2,	0x6C, 3, 5, Immediate,	Cpu::jmp,
-
-	DEC1,	0xC6, 2, 5, ZeroPage,	Cpu::dec,
-	DEC2,	0xD6, 2, 6, ZeroPageX,	Cpu::dec,
-	DEC3,	0xCE, 3, 6, Absolute,	Cpu::dec,
-	DEC4,	0xDE, 3, 7, AbsoluteX,	Cpu::dec,
-
-	INC1,	0xE6, 2, 5, ZeroPage,	Cpu::inc,
-	INC2,	0xF6, 2, 6, ZeroPageX,	Cpu::inc,
-	INC3,	0xEE, 3, 6, Absolute,	Cpu::inc,
-	INC4,	0xFE, 3, 7, AbsoluteX,	Cpu::inc,
-
-	STX1,	0x86, 2, 3, ZeroPage,	Cpu::stx,
-	STX2,	0x96, 2, 4, ZeroPageY,	Cpu::stx,
-	STX3,	0x8E, 3, 4, Absolute,	Cpu::stx,
-
-	STY1,	0x84, 2, 3, ZeroPage,	Cpu::sty,
-	STY2,	0x94, 2, 4, ZeroPageX,	Cpu::sty,
-	STY3,	0x8C, 3, 4, Absolute,	Cpu::sty,
-
-	LSR_A,	0x4A, 1, 2, Implied,	Cpu::lsr_a,
-	LSR_M1,	0x46, 2, 5, ZeroPage,	Cpu::lsr_m,
-	LSR_M2,	0x56, 2, 6, ZeroPageX,	Cpu::lsr_m,
-	LSR_M3,	0x4E, 3, 6, Absolute,	Cpu::lsr_m,
-	LSR_M4,	0x5E, 3, 7, AbsoluteX,	Cpu::lsr_m,
-
-	ORA1,	0x09, 2, 2, Immediate,	Cpu::ora,
-	ORA2,	0x05, 2, 3, ZeroPage,	Cpu::ora,
-	ORA3,	0x15, 2, 4, ZeroPageX,	Cpu::ora,
-	ORA4,	0x0D, 3, 4, Absolute,	Cpu::ora,
-	ORA5,	0x1D, 3, 4, AbsoluteX,	Cpu::ora,
-	ORA6,	0x19, 3, 4, AbsoluteY,	Cpu::ora,
-	ORA7,	0x01, 2, 6, IndirectX,	Cpu::ora,
-	ORA8,	0x11, 2, 5, IndirectY,	Cpu::ora,
-
-	ROL_A,	0x2A, 1, 2, Implied,	Cpu::rol_a,
-	ROL_M1,	0x26, 2, 5, ZeroPage,	Cpu::rol_m,
-	ROL_M2,	0x36, 2, 6, ZeroPageX,	Cpu::rol_m,
-	ROL_M3,	0x2E, 3, 6, Absolute,	Cpu::rol_m,
-	ROL_M4,	0x3E, 3, 7, AbsoluteX,	Cpu::rol_m,
-
-	ROR_A,	0x6A, 1, 2, Implied,	Cpu::ror_a,
-	ROR_M1,	0x66, 2, 5, ZeroPage,	Cpu::ror_m,
-	ROR_M2,	0x76, 2, 6, ZeroPageX,	Cpu::ror_m,
-	ROR_M3,	0x6E, 3, 6, Absolute,	Cpu::ror_m,
-	ROR_M4,	0x7E, 3, 7, AbsoluteX,	Cpu::ror_m,
-
-	AAC1,	0x0B, 2, 2, Immediate, 	Cpu::aac,
-	AAC2,	0x2B, 2, 2, Immediate, 	Cpu::aac,
-
-	AAX1,	0x87, 2, 3, ZeroPage,	Cpu::aax,
-	AAX2,	0x97, 2, 4, ZeroPageY,	Cpu::aax,
-	AAX3,	0x83, 2, 6, IndirectX,	Cpu::aax,
-	AAX4,	0x8F, 3, 4, Absolute,	Cpu::aax,
-
-	AXA1,	0x9F, 3, 5, AbsoluteY,	Cpu::axa_ab,
-	AXA2,	0x93, 2, 6, IndirectY,	Cpu::axa_in,
-
-	DCP1,	0xC7, 2, 5, ZeroPage,	Cpu::dcp,
-	DCP2,	0xD7, 2, 6, ZeroPageX,	Cpu::dcp,
-	DCP3,	0xCF, 3, 6, Absolute,	Cpu::dcp,
-	DCP4,	0xDF, 3, 7, AbsoluteX,	Cpu::dcp,
-	DCP5,	0xDB, 3, 7, AbsoluteY,	Cpu::dcp,
-	DCP6,	0xC3, 2, 8, IndirectX,	Cpu::dcp,
-	DCP7,	0xD3, 2, 8, IndirectY,	Cpu::dcp,
-
-	ISC1,	0xE7, 2, 5, ZeroPage,	Cpu::isc,
-	ISC2,	0xF7, 2, 6, ZeroPageX,	Cpu::isc,
-	ISC3,	0xEF, 3, 6, Absolute,	Cpu::isc,
-	ISC4,	0xFF, 3, 7, AbsoluteX,	Cpu::isc,
-	ISC5,	0xFB, 3, 7, AbsoluteY,	Cpu::isc,
-	ISC6,	0xE3, 2, 8, IndirectX,	Cpu::isc,
-	ISC7,	0xF3, 2, 8, IndirectY,	Cpu::isc,
-
-	LAX1,	0xA7, 2, 3, ZeroPage,	Cpu::lax,
-	LAX2,	0xB7, 2, 4, ZeroPageY,	Cpu::lax,
-	LAX3,	0xAF, 3, 4, Absolute,	Cpu::lax,
-	LAX4,	0xBF, 3, 4, AbsoluteY,	Cpu::lax,
-	LAX5,	0xA3, 2, 6, IndirectX,	Cpu::lax,
-	LAX6,	0xB3, 2, 5, IndirectY,	Cpu::lax,
-
-	RLA1,	0x27, 2, 5, ZeroPage,	Cpu::rla,
-	RLA2,	0x37, 2, 6, ZeroPageX,	Cpu::rla,
-	RLA3,	0x2F, 3, 6, Absolute,	Cpu::rla,
-	RLA4,	0x3F, 3, 7, AbsoluteX,	Cpu::rla,
-	RLA5,	0x3B, 3, 7, AbsoluteY,	Cpu::rla,
-	RLA6,	0x23, 2, 8, IndirectX,	Cpu::rla,
-	RLA7,	0x33, 2, 8, IndirectY,	Cpu::rla,
-
-	RRA1,	0x67, 2, 5, ZeroPage,	Cpu::rra,
-	RRA2,	0x77, 2, 6, ZeroPageX,	Cpu::rra,
-	RRA3,	0x6F, 3, 6, Absolute,	Cpu::rra,
-	RRA4,	0x7F, 3, 7, AbsoluteX,	Cpu::rra,
-	RRA5,	0x7B, 3, 7, AbsoluteY,	Cpu::rra,
-	RRA6,	0x63, 2, 8, IndirectX,	Cpu::rra,
-	RRA7,	0x73, 2, 8, IndirectY,	Cpu::rra,
-
-	SLO1,	0x07, 2, 5, ZeroPage,	Cpu::slo,
-	SLO2,	0x17, 2, 6, ZeroPageX,	Cpu::slo,
-	SLO3,	0x0F, 3, 6, Absolute,	Cpu::slo,
-	SLO4,	0x1F, 3, 7, AbsoluteX,	Cpu::slo,
-	SLO5,	0x1B, 3, 7, AbsoluteY,	Cpu::slo,
-	SLO6,	0x03, 2, 8, IndirectX,	Cpu::slo,
-	SLO7,	0x13, 2, 8, IndirectY,	Cpu::slo,
-
-	SRE1,	0x47, 2, 5, ZeroPage,	Cpu::sre,
-	SRE2,	0x57, 2, 6, ZeroPageX,	Cpu::sre,
-	SRE3,	0x4F, 3, 6, Absolute,	Cpu::sre,
-	SRE4,	0x5F, 3, 7, AbsoluteX,	Cpu::sre,
-	SRE5,	0x5B, 3, 7, AbsoluteY,	Cpu::sre,
-	SRE6,	0x43, 2, 8, IndirectX,	Cpu::sre,
-	SRE7,	0x53, 2, 8, IndirectY,	Cpu::sre
+pub static OPCODES: [OpCodeDef; 256] = decode_table(DEFINITIONS);
+
+pub fn get_instruction_def(code: u8) -> &'static OpCodeDef {
+	&OPCODES[code as usize]
+}
+
+map!(DEFINITIONS;
+	BRK,	0x00, "BRK", 1, 7, Implied,	Jump,	true,	Cpu::brk,
+	TAX,	0xAA, "TAX", 1, 2, Implied,	Internal,	true,	Cpu::tax,
+	TAY,	0xA8, "TAY", 1, 2, Implied,	Internal,	true,	Cpu::tay,
+	INX,	0xE8, "INX", 1, 2, Implied,	Internal,	true,	Cpu::inx,
+	INY,	0xC8, "INY", 1, 2, Implied,	Internal,	true,	Cpu::iny,
+	DEX,	0xCA, "DEX", 1, 2, Implied,	Internal,	true,	Cpu::dex,
+	DEY,	0x88, "DEY", 1, 2, Implied,	Internal,	true,	Cpu::dey,
+	TXA,	0x8A, "TXA", 1, 2, Implied,	Internal,	true,	Cpu::txa,
+	TYA,	0x98, "TYA", 1, 2, Implied,	Internal,	true,	Cpu::tya,
+	BCC,	0x90, "BCC", 2, 2, Relative,	Branch,	true,	Cpu::bcc,
+	BCS,	0xB0, "BCS", 2, 2, Relative,	Branch,	true,	Cpu::bcs,
+	BEQ,	0xF0, "BEQ", 2, 2, Relative,	Branch,	true,	Cpu::beq,
+	BMI,	0x30, "BMI", 2, 2, Relative,	Branch,	true,	Cpu::bmi,
+	BNE,	0xD0, "BNE", 2, 2, Relative,	Branch,	true,	Cpu::bne,
+	BPL,	0x10, "BPL", 2, 2, Relative,	Branch,	true,	Cpu::bpl,
+	BVC,	0x50, "BVC", 2, 2, Relative,	Branch,	true,	Cpu::bvc,
+	BVS,	0x70, "BVS", 2, 2, Relative,	Branch,	true,	Cpu::bvs,
+	CLC,	0x18, "CLC", 1, 2, Implied,	Internal,	true,	Cpu::clc,
+	CLD,	0xD8, "CLD", 1, 2, Implied,	Internal,	true,	Cpu::cld,
+	CLI,	0x58, "CLI", 1, 2, Implied,	Internal,	true,	Cpu::cli,
+	CLV,	0xB8, "CLV", 1, 2, Implied,	Internal,	true,	Cpu::clv,
+	RTS,	0x60, "RTS", 1, 6, Implied,	Jump,	true,	Cpu::rts,
+	SEC,	0x38, "SEC", 1, 2, Implied,	Internal,	true,	Cpu::sec,
+	SED,	0xF8, "SED", 1, 2, Implied,	Internal,	true,	Cpu::sed,
+	SEI,	0x78, "SEI", 1, 2, Implied,	Internal,	true,	Cpu::sei,
+	TSX,	0xBA, "TSX", 1, 2, Implied,	Internal,	true,	Cpu::tsx,
+	TXS,	0x9A, "TXS", 1, 2, Implied,	Internal,	true,	Cpu::txs,
+	JSR,	0x20, "JSR", 3, 6, Absolute,	Jump,	true,	Cpu::jsr,
+	PHA,	0x48, "PHA", 1, 3, Implied,	Stack,	true,	Cpu::pha,
+	PLA,	0x68, "PLA", 1, 4, Implied,	Stack,	true,	Cpu::pla,
+	PLP,	0x28, "PLP", 1, 4, Implied,	Stack,	true,	Cpu::plp,
+	RTI,	0x40, "RTI", 1, 6, Implied,	Jump,	true,	Cpu::rti,
+	PHP,	0x08, "PHP", 1, 3, Implied,	Stack,	true,	Cpu::php,
+	AAR,	0x6B, "ARR", 2, 2, Immediate,	Read,	false,	Cpu::arr,
+	ASR,	0x4B, "ASR", 2, 2, Immediate,	Read,	false,	Cpu::asr,
+	ATX,	0xAB, "ATX", 2, 2, Immediate,	Read,	false,	Cpu::atx,
+	AXS,	0xCB, "AXS", 2, 2, Immediate,	Read,	false,	Cpu::axs,
+	LAR,	0xBB, "LAR", 3, 4, AbsoluteY,	Read,	false,	Cpu::lar,
+	SXA,	0x9E, "SXA", 3, 5, AbsoluteY,	Write,	false,	Cpu::sxa,
+	SYA,	0x9C, "SYA", 3, 5, AbsoluteX,	Write,	false,	Cpu::sya,
+	XAA,	0x8B, "XAA", 2, 2, Immediate,	Read,	false,	Cpu::xaa,
+	XAS,	0x9B, "XAS", 3, 5, AbsoluteY,	Write,	false,	Cpu::xas,
+	NOP,	0xEA, "NOP", 1, 2, Implied,	Internal,	true,	Cpu::nop,
+
+
+
+	LDA1,	0xA9, "LDA", 2, 2, Immediate,	Read,	true,	Cpu::lda,
+	LDA2,	0xA5, "LDA", 2, 3, ZeroPage,	Read,	true,	Cpu::lda,
+	LDA3,	0xB5, "LDA", 2, 4, ZeroPageX,	Read,	true,	Cpu::lda,
+	LDA4,	0xAD, "LDA", 3, 4, Absolute,	Read,	true,	Cpu::lda,
+	LDA5,	0xBD, "LDA", 3, 4, AbsoluteX,	Read,	true,	Cpu::lda,
+	LDA6,	0xB9, "LDA", 3, 4, AbsoluteY,	Read,	true,	Cpu::lda,
+	LDA7,	0xA1, "LDA", 2, 6, IndirectX,	Read,	true,	Cpu::lda,
+	LDA8,	0xB1, "LDA", 2, 5, IndirectY,	Read,	true,	Cpu::lda,
+
+	LDX1,	0xA2, "LDX", 2, 2, Immediate,	Read,	true,	Cpu::ldx,
+	LDX2,	0xA6, "LDX", 2, 3, ZeroPage,	Read,	true,	Cpu::ldx,
+	LDX3,	0xB6, "LDX", 2, 4, ZeroPageY,	Read,	true,	Cpu::ldx,
+	LDX4,	0xAE, "LDX", 3, 4, Absolute,	Read,	true,	Cpu::ldx,
+	LDX5,	0xBE, "LDX", 3, 4, AbsoluteY,	Read,	true,	Cpu::ldx,
+
+	LDY1,	0xA0, "LDY", 2, 2, Immediate,	Read,	true,	Cpu::ldy,
+	LDY2,	0xA4, "LDY", 2, 3, ZeroPage,	Read,	true,	Cpu::ldy,
+	LDY3,	0xB4, "LDY", 2, 4, ZeroPageX,	Read,	true,	Cpu::ldy,
+	LDY4,	0xAC, "LDY", 3, 4, Absolute,	Read,	true,	Cpu::ldy,
+	LDY5,	0xBC, "LDY", 3, 4, AbsoluteX,	Read,	true,	Cpu::ldy,
+
+	ASL1,	0x0A, "ASL", 1, 2, Accumulator,	Internal,	true,	Cpu::asl_a,
+	ASL2,	0x06, "ASL", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::asl_m,
+	ASL3,	0x16, "ASL", 2, 6, ZeroPageX,	ReadModifyWrite,	true,	Cpu::asl_m,
+	ASL4,	0x0E, "ASL", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::asl_m,
+	ASL5,	0x1E, "ASL", 3, 7, AbsoluteX,	ReadModifyWrite,	true,	Cpu::asl_m,
+
+	AND1,	0x29, "AND", 2, 2, Immediate,	Read,	true,	Cpu::and,
+	AND2,	0x25, "AND", 2, 3, ZeroPage,	Read,	true,	Cpu::and,
+	AND3,	0x35, "AND", 2, 4, ZeroPageX,	Read,	true,	Cpu::and,
+	AND4,	0x2D, "AND", 3, 4, Absolute,	Read,	true,	Cpu::and,
+	AND5,	0x3D, "AND", 3, 4, AbsoluteX, 	Read,	true,	Cpu::and,
+	AND6,	0x39, "AND", 3, 4, AbsoluteY, 	Read,	true,	Cpu::and,
+	AND7,	0x21, "AND", 2, 6, IndirectX, 	Read,	true,	Cpu::and,
+	AND8,	0x31, "AND", 2, 5, IndirectY, 	Read,	true,	Cpu::and,
+
+	EOR1,	0x49, "EOR", 2, 2, Immediate,	Read,	true,	Cpu::eor,
+	EOR2,	0x45, "EOR", 2, 3, ZeroPage,	Read,	true,	Cpu::eor,
+	EOR3,	0x55, "EOR", 2, 4, ZeroPageX,	Read,	true,	Cpu::eor,
+	EOR4,	0x4D, "EOR", 3, 4, Absolute,	Read,	true,	Cpu::eor,
+	EOR5,	0x5D, "EOR", 3, 4, AbsoluteX, 	Read,	true,	Cpu::eor,
+	EOR6,	0x59, "EOR", 3, 4, AbsoluteY, 	Read,	true,	Cpu::eor,
+	EOR7,	0x41, "EOR", 2, 6, IndirectX, 	Read,	true,	Cpu::eor,
+	EOR8,	0x51, "EOR", 2, 5, IndirectY, 	Read,	true,	Cpu::eor,
+
+	STA1,	0x85, "STA", 2, 3, ZeroPage,	Write,	true,	Cpu::sta,
+	STA2,	0x95, "STA", 2, 4, ZeroPageX,	Write,	true,	Cpu::sta,
+	STA3,	0x8D, "STA", 3, 4, Absolute,	Write,	true,	Cpu::sta,
+	STA4,	0x9D, "STA", 3, 5, AbsoluteX, 	Write,	true,	Cpu::sta,
+	STA5,	0x99, "STA", 3, 5, AbsoluteY, 	Write,	true,	Cpu::sta,
+	STA6,	0x81, "STA", 2, 6, IndirectX, 	Write,	true,	Cpu::sta,
+	STA7,	0x91, "STA", 2, 6, IndirectY, 	Write,	true,	Cpu::sta,
+
+	ADC1,	0x69, "ADC", 2, 2, Immediate,	Read,	true,	Cpu::adc,
+	ADC2,	0x65, "ADC", 2, 3, ZeroPage,	Read,	true,	Cpu::adc,
+	ADC3,	0x75, "ADC", 2, 4, ZeroPageX,	Read,	true,	Cpu::adc,
+	ADC4,	0x6D, "ADC", 3, 4, Absolute,	Read,	true,	Cpu::adc,
+	ADC5,	0x7D, "ADC", 3, 4, AbsoluteX, 	Read,	true,	Cpu::adc,
+	ADC6,	0x79, "ADC", 3, 4, AbsoluteY, 	Read,	true,	Cpu::adc,
+	ADC7,	0x61, "ADC", 2, 6, IndirectX, 	Read,	true,	Cpu::adc,
+	ADC8,	0x71, "ADC", 2, 5, IndirectY, 	Read,	true,	Cpu::adc,
+
+	SBC1,	0xE9, "SBC", 2, 2, Immediate,	Read,	true,	Cpu::sbc,
+	SBC2,	0xE5, "SBC", 2, 3, ZeroPage,	Read,	true,	Cpu::sbc,
+	SBC3,	0xF5, "SBC", 2, 4, ZeroPageX,	Read,	true,	Cpu::sbc,
+	SBC4,	0xED, "SBC", 3, 4, Absolute,	Read,	true,	Cpu::sbc,
+	SBC5,	0xFD, "SBC", 3, 4, AbsoluteX, 	Read,	true,	Cpu::sbc,
+	SBC6,	0xF9, "SBC", 3, 4, AbsoluteY, 	Read,	true,	Cpu::sbc,
+	SBC7,	0xE1, "SBC", 2, 6, IndirectX, 	Read,	true,	Cpu::sbc,
+	SBC8,	0xF1, "SBC", 2, 5, IndirectY, 	Read,	true,	Cpu::sbc,
+	SBC9,	0xEB, "SBC", 2, 2, Immediate,	Read,	false,	Cpu::sbc,
+
+	BIT1,	0x24, "BIT", 2, 3, ZeroPage, 	Read,	true,	Cpu::bit,
+	BIT2,	0x2C, "BIT", 3, 4, Absolute, 	Read,	true,	Cpu::bit,
+
+	CMP1,	0xC9, "CMP", 2, 2, Immediate,	Read,	true,	Cpu::cmp,
+	CMP2,	0xC5, "CMP", 2, 3, ZeroPage,	Read,	true,	Cpu::cmp,
+	CMP3,	0xD5, "CMP", 2, 4, ZeroPageX,	Read,	true,	Cpu::cmp,
+	CMP4,	0xCD, "CMP", 3, 4, Absolute,	Read,	true,	Cpu::cmp,
+	CMP5,	0xDD, "CMP", 3, 4, AbsoluteX,	Read,	true,	Cpu::cmp,
+	CMP6,	0xD9, "CMP", 3, 4, AbsoluteY,	Read,	true,	Cpu::cmp,
+	CMP7,	0xC1, "CMP", 2, 6, IndirectX,	Read,	true,	Cpu::cmp,
+	CMP8,	0xD1, "CMP", 2, 5, IndirectY,	Read,	true,	Cpu::cmp,
+
+	CPX1,	0xE0, "CPX", 2, 2, Immediate,	Read,	true,	Cpu::cpx,
+	CPX2,	0xE4, "CPX", 2, 3, ZeroPage,	Read,	true,	Cpu::cpx,
+	CPX3,	0xEC, "CPX", 3, 4, Absolute,	Read,	true,	Cpu::cpx,
+
+	CPY1,	0xC0, "CPY", 2, 2, Immediate,	Read,	true,	Cpu::cpy,
+	CPY2,	0xC4, "CPY", 2, 3, ZeroPage,	Read,	true,	Cpu::cpy,
+	CPY3,	0xCC, "CPY", 3, 4, Absolute,	Read,	true,	Cpu::cpy,
+
+	JMP1,	0x4C, "JMP", 3, 3, Absolute,	Jump,	true,	Cpu::jmp_absolute,
+	JMP2,	0x6C, "JMP", 3, 5, Indirect,	Jump,	true,	Cpu::jmp,
+
+	DEC1,	0xC6, "DEC", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::dec,
+	DEC2,	0xD6, "DEC", 2, 6, ZeroPageX,	ReadModifyWrite,	true,	Cpu::dec,
+	DEC3,	0xCE, "DEC", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::dec,
+	DEC4,	0xDE, "DEC", 3, 7, AbsoluteX,	ReadModifyWrite,	true,	Cpu::dec,
+
+	INC1,	0xE6, "INC", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::inc,
+	INC2,	0xF6, "INC", 2, 6, ZeroPageX,	ReadModifyWrite,	true,	Cpu::inc,
+	INC3,	0xEE, "INC", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::inc,
+	INC4,	0xFE, "INC", 3, 7, AbsoluteX,	ReadModifyWrite,	true,	Cpu::inc,
+
+	STX1,	0x86, "STX", 2, 3, ZeroPage,	Write,	true,	Cpu::stx,
+	STX2,	0x96, "STX", 2, 4, ZeroPageY,	Write,	true,	Cpu::stx,
+	STX3,	0x8E, "STX", 3, 4, Absolute,	Write,	true,	Cpu::stx,
+
+	STY1,	0x84, "STY", 2, 3, ZeroPage,	Write,	true,	Cpu::sty,
+	STY2,	0x94, "STY", 2, 4, ZeroPageX,	Write,	true,	Cpu::sty,
+	STY3,	0x8C, "STY", 3, 4, Absolute,	Write,	true,	Cpu::sty,
+
+	LSR_A,	0x4A, "LSR", 1, 2, Accumulator,	Internal,	true,	Cpu::lsr_a,
+	LSR_M1,	0x46, "LSR", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::lsr_m,
+	LSR_M2,	0x56, "LSR", 2, 6, ZeroPageX,	ReadModifyWrite,	true,	Cpu::lsr_m,
+	LSR_M3,	0x4E, "LSR", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::lsr_m,
+	LSR_M4,	0x5E, "LSR", 3, 7, AbsoluteX,	ReadModifyWrite,	true,	Cpu::lsr_m,
+
+	ORA1,	0x09, "ORA", 2, 2, Immediate,	Read,	true,	Cpu::ora,
+	ORA2,	0x05, "ORA", 2, 3, ZeroPage,	Read,	true,	Cpu::ora,
+	ORA3,	0x15, "ORA", 2, 4, ZeroPageX,	Read,	true,	Cpu::ora,
+	ORA4,	0x0D, "ORA", 3, 4, Absolute,	Read,	true,	Cpu::ora,
+	ORA5,	0x1D, "ORA", 3, 4, AbsoluteX,	Read,	true,	Cpu::ora,
+	ORA6,	0x19, "ORA", 3, 4, AbsoluteY,	Read,	true,	Cpu::ora,
+	ORA7,	0x01, "ORA", 2, 6, IndirectX,	Read,	true,	Cpu::ora,
+	ORA8,	0x11, "ORA", 2, 5, IndirectY,	Read,	true,	Cpu::ora,
+
+	ROL_A,	0x2A, "ROL", 1, 2, Accumulator,	Internal,	true,	Cpu::rol_a,
+	ROL_M1,	0x26, "ROL", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::rol_m,
+	ROL_M2,	0x36, "ROL", 2, 6, ZeroPageX,	ReadModifyWrite,	true,	Cpu::rol_m,
+	ROL_M3,	0x2E, "ROL", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::rol_m,
+	ROL_M4,	0x3E, "ROL", 3, 7, AbsoluteX,	ReadModifyWrite,	true,	Cpu::rol_m,
+
+	ROR_A,	0x6A, "ROR", 1, 2, Accumulator,	Internal,	true,	Cpu::ror_a,
+	ROR_M1,	0x66, "ROR", 2, 5, ZeroPage,	ReadModifyWrite,	true,	Cpu::ror_m,
+	ROR_M2,	0x76, "ROR", 2, 6, ZeroPageX,	ReadModifyWrite,	true,	Cpu::ror_m,
+	ROR_M3,	0x6E, "ROR", 3, 6, Absolute,	ReadModifyWrite,	true,	Cpu::ror_m,
+	ROR_M4,	0x7E, "ROR", 3, 7, AbsoluteX,	ReadModifyWrite,	true,	Cpu::ror_m,
+
+	AAC1,	0x0B, "AAC", 2, 2, Immediate, 	Read,	false,	Cpu::aac,
+	AAC2,	0x2B, "AAC", 2, 2, Immediate, 	Read,	false,	Cpu::aac,
+
+	AAX1,	0x87, "AAX", 2, 3, ZeroPage,	Write,	false,	Cpu::aax,
+	AAX2,	0x97, "AAX", 2, 4, ZeroPageY,	Write,	false,	Cpu::aax,
+	AAX3,	0x83, "AAX", 2, 6, IndirectX,	Write,	false,	Cpu::aax,
+	AAX4,	0x8F, "AAX", 3, 4, Absolute,	Write,	false,	Cpu::aax,
+
+	AXA1,	0x9F, "AXA", 3, 5, AbsoluteY,	Write,	false,	Cpu::axa_ab,
+	AXA2,	0x93, "AXA", 2, 6, IndirectY,	Write,	false,	Cpu::axa_in,
+
+	DCP1,	0xC7, "DCP", 2, 5, ZeroPage,	ReadModifyWrite,	false,	Cpu::dcp,
+	DCP2,	0xD7, "DCP", 2, 6, ZeroPageX,	ReadModifyWrite,	false,	Cpu::dcp,
+	DCP3,	0xCF, "DCP", 3, 6, Absolute,	ReadModifyWrite,	false,	Cpu::dcp,
+	DCP4,	0xDF, "DCP", 3, 7, AbsoluteX,	ReadModifyWrite,	false,	Cpu::dcp,
+	DCP5,	0xDB, "DCP", 3, 7, AbsoluteY,	ReadModifyWrite,	false,	Cpu::dcp,
+	DCP6,	0xC3, "DCP", 2, 8, IndirectX,	ReadModifyWrite,	false,	Cpu::dcp,
+	DCP7,	0xD3, "DCP", 2, 8, IndirectY,	ReadModifyWrite,	false,	Cpu::dcp,
+
+	ISC1,	0xE7, "ISC", 2, 5, ZeroPage,	ReadModifyWrite,	false,	Cpu::isc,
+	ISC2,	0xF7, "ISC", 2, 6, ZeroPageX,	ReadModifyWrite,	false,	Cpu::isc,
+	ISC3,	0xEF, "ISC", 3, 6, Absolute,	ReadModifyWrite,	false,	Cpu::isc,
+	ISC4,	0xFF, "ISC", 3, 7, AbsoluteX,	ReadModifyWrite,	false,	Cpu::isc,
+	ISC5,	0xFB, "ISC", 3, 7, AbsoluteY,	ReadModifyWrite,	false,	Cpu::isc,
+	ISC6,	0xE3, "ISC", 2, 8, IndirectX,	ReadModifyWrite,	false,	Cpu::isc,
+	ISC7,	0xF3, "ISC", 2, 8, IndirectY,	ReadModifyWrite,	false,	Cpu::isc,
+
+	LAX1,	0xA7, "LAX", 2, 3, ZeroPage,	Read,	false,	Cpu::lax,
+	LAX2,	0xB7, "LAX", 2, 4, ZeroPageY,	Read,	false,	Cpu::lax,
+	LAX3,	0xAF, "LAX", 3, 4, Absolute,	Read,	false,	Cpu::lax,
+	LAX4,	0xBF, "LAX", 3, 4, AbsoluteY,	Read,	false,	Cpu::lax,
+	LAX5,	0xA3, "LAX", 2, 6, IndirectX,	Read,	false,	Cpu::lax,
+	LAX6,	0xB3, "LAX", 2, 5, IndirectY,	Read,	false,	Cpu::lax,
+
+	RLA1,	0x27, "RLA", 2, 5, ZeroPage,	ReadModifyWrite,	false,	Cpu::rla,
+	RLA2,	0x37, "RLA", 2, 6, ZeroPageX,	ReadModifyWrite,	false,	Cpu::rla,
+	RLA3,	0x2F, "RLA", 3, 6, Absolute,	ReadModifyWrite,	false,	Cpu::rla,
+	RLA4,	0x3F, "RLA", 3, 7, AbsoluteX,	ReadModifyWrite,	false,	Cpu::rla,
+	RLA5,	0x3B, "RLA", 3, 7, AbsoluteY,	ReadModifyWrite,	false,	Cpu::rla,
+	RLA6,	0x23, "RLA", 2, 8, IndirectX,	ReadModifyWrite,	false,	Cpu::rla,
+	RLA7,	0x33, "RLA", 2, 8, IndirectY,	ReadModifyWrite,	false,	Cpu::rla,
+
+	RRA1,	0x67, "RRA", 2, 5, ZeroPage,	ReadModifyWrite,	false,	Cpu::rra,
+	RRA2,	0x77, "RRA", 2, 6, ZeroPageX,	ReadModifyWrite,	false,	Cpu::rra,
+	RRA3,	0x6F, "RRA", 3, 6, Absolute,	ReadModifyWrite,	false,	Cpu::rra,
+	RRA4,	0x7F, "RRA", 3, 7, AbsoluteX,	ReadModifyWrite,	false,	Cpu::rra,
+	RRA5,	0x7B, "RRA", 3, 7, AbsoluteY,	ReadModifyWrite,	false,	Cpu::rra,
+	RRA6,	0x63, "RRA", 2, 8, IndirectX,	ReadModifyWrite,	false,	Cpu::rra,
+	RRA7,	0x73, "RRA", 2, 8, IndirectY,	ReadModifyWrite,	false,	Cpu::rra,
+
+	SLO1,	0x07, "SLO", 2, 5, ZeroPage,	ReadModifyWrite,	false,	Cpu::slo,
+	SLO2,	0x17, "SLO", 2, 6, ZeroPageX,	ReadModifyWrite,	false,	Cpu::slo,
+	SLO3,	0x0F, "SLO", 3, 6, Absolute,	ReadModifyWrite,	false,	Cpu::slo,
+	SLO4,	0x1F, "SLO", 3, 7, AbsoluteX,	ReadModifyWrite,	false,	Cpu::slo,
+	SLO5,	0x1B, "SLO", 3, 7, AbsoluteY,	ReadModifyWrite,	false,	Cpu::slo,
+	SLO6,	0x03, "SLO", 2, 8, IndirectX,	ReadModifyWrite,	false,	Cpu::slo,
+	SLO7,	0x13, "SLO", 2, 8, IndirectY,	ReadModifyWrite,	false,	Cpu::slo,
+
+	SRE1,	0x47, "SRE", 2, 5, ZeroPage,	ReadModifyWrite,	false,	Cpu::sre,
+	SRE2,	0x57, "SRE", 2, 6, ZeroPageX,	ReadModifyWrite,	false,	Cpu::sre,
+	SRE3,	0x4F, "SRE", 3, 6, Absolute,	ReadModifyWrite,	false,	Cpu::sre,
+	SRE4,	0x5F, "SRE", 3, 7, AbsoluteX,	ReadModifyWrite,	false,	Cpu::sre,
+	SRE5,	0x5B, "SRE", 3, 7, AbsoluteY,	ReadModifyWrite,	false,	Cpu::sre,
+	SRE6,	0x43, "SRE", 2, 8, IndirectX,	ReadModifyWrite,	false,	Cpu::sre,
+	SRE7,	0x53, "SRE", 2, 8, IndirectY,	ReadModifyWrite,	false,	Cpu::sre;
+
+	NOP_IMPLIED,	[0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA], "NOP", 1, 2, Implied,	Internal,	false,	Cpu::nop,
+	DOP_IMMEDIATE,	[0x80, 0x82, 0x89, 0xC2, 0xE2], "NOP", 2, 2, Immediate,	Read,	false,	Cpu::nop,
+	DOP_ZERO_PAGE,	[0x04, 0x44, 0x64], "NOP", 2, 3, ZeroPage,	Read,	false,	Cpu::nop,
+	DOP_ZERO_PAGE_X,	[0x14, 0x34, 0x54, 0x74, 0xD4, 0xF4], "NOP", 2, 4, ZeroPageX,	Read,	false,	Cpu::nop,
+	TOP_ABSOLUTE,	[0x0C], "NOP", 3, 4, Absolute,	Read,	false,	Cpu::nop,
+	TOP_ABSOLUTE_X,	[0x1C, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC], "NOP", 3, 4, AbsoluteX,	Read,	false,	Cpu::nop,
+	KIL,	[0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2], "KIL", 1, 0, Implied,	Jam,	false,	Cpu::nop
 );
\ No newline at end of file
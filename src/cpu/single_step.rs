@@ -0,0 +1,271 @@
+// Runs test cases in the format of the SingleStepTests 65x02 suite: one JSON file
+// per opcode under <chip>/v1/, each case a starting state, the state after one
+// instruction and the bus cycles in between. The cases under tests/single_step
+// always run. Set SINGLE_STEP_TESTS to a checkout of the suite to run all of it.
+use std::path::Path;
+
+use super::*;
+use crate::bus::Device;
+
+// The suite's ANE and LXA cases assume this for the unstable constant.
+const MAGIC: u8 = 0xEE;
+
+// The suite gives the CPU 64KB of plain RAM.
+struct FlatRam(Vec<u8>);
+
+impl Device for FlatRam {
+	fn write(&mut self, addr: u16, data: u8) {
+		self.0[addr as usize] = data;
+	}
+
+	fn peek(&self, addr: u16) -> u8 {
+		self.0[addr as usize]
+	}
+
+	fn poke(&mut self, addr: u16, data: u8) {
+		self.0[addr as usize] = data;
+	}
+}
+
+// Just enough JSON for the test files.
+#[derive(Debug)]
+enum Json {
+	Number(i64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+	Other,
+}
+
+impl Json {
+	fn parse(text: &str) -> Json {
+		let mut parser = Parser { bytes: text.as_bytes(), pos: 0 };
+		let value = parser.value();
+		parser.skip_space();
+		assert_eq!(parser.pos, parser.bytes.len(), "trailing data after JSON");
+		value
+	}
+
+	fn get(&self, key: &str) -> &Json {
+		match self {
+			Json::Object(fields) => &fields.iter().find(|(name, _)| name == key).unwrap_or_else(|| panic!("no {}", key)).1,
+			_ => panic!("not an object looking for {}", key),
+		}
+	}
+
+	fn items(&self) -> &[Json] {
+		match self {
+			Json::Array(items) => items,
+			_ => panic!("not an array: {:?}", self),
+		}
+	}
+
+	fn number(&self) -> i64 {
+		match self {
+			Json::Number(value) => *value,
+			_ => panic!("not a number: {:?}", self),
+		}
+	}
+
+	fn text(&self) -> &str {
+		match self {
+			Json::String(text) => text,
+			_ => panic!("not a string: {:?}", self),
+		}
+	}
+}
+
+struct Parser<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+}
+
+impl Parser<'_> {
+	fn skip_space(&mut self) {
+		while self.bytes.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+			self.pos += 1;
+		}
+	}
+
+	fn expect(&mut self, byte: u8) {
+		self.skip_space();
+		assert_eq!(self.bytes.get(self.pos), Some(&byte), "expected {} at {}", byte as char, self.pos);
+		self.pos += 1;
+	}
+
+	// Whether the next byte closes a list, consuming it if so, and the comma if not.
+	fn end_of_list(&mut self, close: u8, first: bool) -> bool {
+		self.skip_space();
+		if self.bytes.get(self.pos) == Some(&close) {
+			self.pos += 1;
+			return true;
+		}
+		if !first {
+			self.expect(b',');
+		}
+		false
+	}
+
+	fn value(&mut self) -> Json {
+		self.skip_space();
+		match self.bytes.get(self.pos) {
+			Some(b'[') => {
+				self.pos += 1;
+				let mut items = Vec::new();
+				while !self.end_of_list(b']', items.is_empty()) {
+					items.push(self.value());
+				}
+				Json::Array(items)
+			}
+			Some(b'{') => {
+				self.pos += 1;
+				let mut fields = Vec::new();
+				while !self.end_of_list(b'}', fields.is_empty()) {
+					let Json::String(name) = self.value() else {
+						panic!("object key at {} is not a string", self.pos);
+					};
+					self.expect(b':');
+					fields.push((name, self.value()));
+				}
+				Json::Object(fields)
+			}
+			Some(b'"') => {
+				let start = self.pos + 1;
+				let len = self.bytes[start..].iter().position(|&byte| byte == b'"').expect("unterminated string");
+				self.pos = start + len + 1;
+				Json::String(String::from_utf8_lossy(&self.bytes[start..start + len]).into_owned())
+			}
+			Some(b'-' | b'0'..=b'9') => {
+				let start = self.pos;
+				self.pos += 1;
+				while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+					self.pos += 1;
+				}
+				let text = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap();
+				Json::Number(text.parse().unwrap())
+			}
+			Some(_) => {
+				while self.bytes.get(self.pos).is_some_and(u8::is_ascii_alphabetic) {
+					self.pos += 1;
+				}
+				Json::Other
+			}
+			None => panic!("unexpected end of JSON"),
+		}
+	}
+}
+
+fn registers(state: &Json) -> [i64; 6] {
+	["pc", "s", "a", "x", "y", "p"].map(|name| state.get(name).number())
+}
+
+fn ram(state: &Json) -> Vec<(u16, u8)> {
+	state.get("ram").items().iter().map(|cell| (cell.items()[0].number() as u16, cell.items()[1].number() as u8)).collect()
+}
+
+// Runs one case, returning what went wrong.
+fn run_case(variant: CpuVariant, case: &Json) -> Result<(), String> {
+	let initial = case.get("initial");
+	let mut cpu = Cpu::new();
+	cpu.variant = variant;
+	cpu.ane_magic = MAGIC;
+	cpu.lxa_magic = MAGIC;
+	cpu.bus.attach(0x0000..=0xFFFF, 0xFFFF, Box::new(FlatRam(vec![0; 0x10000])));
+	let [pc, s, a, x, y, p] = registers(initial);
+	cpu.program_counter = pc as u16;
+	cpu.stack_pointer = s as u8;
+	cpu.register_a = a as u8;
+	cpu.register_x = x as u8;
+	cpu.register_y = y as u8;
+	cpu.status = CpuStatus(p as u8);
+	for (address, value) in ram(initial) {
+		cpu.poke(address, value);
+	}
+	cpu.cycle_log = Some(Vec::new());
+	cpu.step().map_err(|err| err.to_string())?;
+
+	let expected: Vec<String> = case
+		.get("cycles")
+		.items()
+		.iter()
+		.map(|cycle| {
+			let cycle = cycle.items();
+			format!("{:04X} {:02X} {}", cycle[0].number(), cycle[1].number(), cycle[2].text())
+		})
+		.collect();
+	let cycles: Vec<String> = cpu
+		.cycle_log
+		.take()
+		.unwrap()
+		.iter()
+		.map(|cycle| {
+			let kind = match cycle.kind {
+				CycleKind::Read => "read",
+				CycleKind::Write => "write",
+			};
+			format!("{:04X} {:02X} {}", cycle.address, cycle.value, kind)
+		})
+		.collect();
+	if cycles != expected {
+		return Err(format!("cycles {:?}, expected {:?}", cycles, expected));
+	}
+
+	let last = case.get("final");
+	let found = [
+		cpu.program_counter as i64,
+		cpu.stack_pointer as i64,
+		cpu.register_a as i64,
+		cpu.register_x as i64,
+		cpu.register_y as i64,
+		cpu.status.0 as i64,
+	];
+	if found != registers(last) {
+		return Err(format!("pc s a x y p {:04X?}, expected {:04X?}", found, registers(last)));
+	}
+	for (address, value) in ram(last) {
+		if cpu.peek(address) != value {
+			return Err(format!("${:04X} is {:02X}, expected {:02X}", address, cpu.peek(address), value));
+		}
+	}
+	Ok(())
+}
+
+// Runs every opcode file found for the chips the suite has tests for, and returns
+// how many cases ran. Jams are skipped, a jammed CPU doesn't finish its instruction.
+fn run_suite(root: &Path) -> usize {
+	let mut count = 0;
+	let mut failures = Vec::new();
+	for (chip, variant) in [("nes6502", CpuVariant::Ricoh2A03), ("6502", CpuVariant::Nmos6502), ("wdc65c02", CpuVariant::Wdc65C02)] {
+		let Ok(entries) = std::fs::read_dir(root.join(chip).join("v1")) else {
+			continue;
+		};
+		let mut paths: Vec<_> = entries.map(|entry| entry.unwrap().path()).collect();
+		paths.sort();
+		for path in paths {
+			let Some(opcode) = path.file_stem().and_then(|stem| u8::from_str_radix(&stem.to_string_lossy(), 16).ok()) else {
+				continue;
+			};
+			if variant.opcodes()[opcode as usize].category == Category::Jam {
+				continue;
+			}
+			let cases = Json::parse(&std::fs::read_to_string(&path).unwrap());
+			for case in cases.items() {
+				count += 1;
+				if let Err(err) = run_case(variant, case) {
+					failures.push(format!("{} {}: {}", chip, case.get("name").text(), err));
+				}
+			}
+		}
+	}
+	assert!(failures.is_empty(), "{} of {} cases failed:\n{}", failures.len(), count, failures.join("\n"));
+	count
+}
+
+#[test]
+fn test_single_step() {
+	let count = run_suite(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/single_step"));
+	assert!(count > 0);
+	if let Some(root) = std::env::var_os("SINGLE_STEP_TESTS") {
+		run_suite(Path::new(&root));
+	}
+}
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::ops::Deref;
 use std::ops::DerefMut;
 
@@ -101,4 +102,19 @@ impl DerefMut for CpuStatus {
 	fn deref_mut(&mut self) -> &mut Self::Target {
 		&mut self.0
 	}
-}
\ No newline at end of file
+}
+
+// Shows the flags in NV-BDIZC order, upper case when set and lower case when clear.
+impl fmt::Display for CpuStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (shift, name) in (0..8).rev().zip("NV-BDIZC".chars()) {
+			let letter = match (name, self.get_flag(shift)) {
+				('-', _) => '-',
+				(_, true) => name,
+				(_, false) => name.to_ascii_lowercase(),
+			};
+			write!(f, "{}", letter)?;
+		}
+		Ok(())
+	}
+}
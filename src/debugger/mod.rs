@@ -0,0 +1,762 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use super::cpu::{CpuError, Cpu, CycleKind, Memory, JSR};
+use super::disasm::{decode, Instruction};
+use super::symbols::SymbolTable;
+
+// Which bus accesses a watchpoint stops on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+	Read,
+	Write,
+	ReadWrite,
+}
+
+impl Access {
+	fn matches(&self, kind: CycleKind) -> bool {
+		match self {
+			Access::Read => kind == CycleKind::Read,
+			Access::Write => kind == CycleKind::Write,
+			Access::ReadWrite => true,
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Register {
+	A,
+	X,
+	Y,
+	SP,
+	PC,
+	P,
+	Flag(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+}
+
+impl Comparison {
+	pub fn test<T: PartialOrd>(&self, left: T, right: T) -> bool {
+		match self {
+			Comparison::Equal => left == right,
+			Comparison::NotEqual => left != right,
+			Comparison::Less => left < right,
+			Comparison::LessEqual => left <= right,
+			Comparison::Greater => left > right,
+			Comparison::GreaterEqual => left >= right,
+		}
+	}
+}
+
+// A register compared with a constant, like "A == $10" or "Z != 0".
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Condition {
+	pub register: Register,
+	pub comparison: Comparison,
+	pub value: u16,
+}
+
+impl Condition {
+	pub fn holds(&self, cpu: &Cpu) -> bool {
+		let value = match self.register {
+			Register::A => cpu.register_a as u16,
+			Register::X => cpu.register_x as u16,
+			Register::Y => cpu.register_y as u16,
+			Register::SP => cpu.stack_pointer as u16,
+			Register::PC => cpu.program_counter,
+			Register::P => cpu.status.0 as u16,
+			Register::Flag(shift) => (cpu.status.0 >> shift & 1) as u16,
+		};
+		self.comparison.test(value, self.value)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Breakpoint {
+	// Stops before the instruction at the address runs, if the condition holds.
+	Address { address: u16, condition: Option<Condition> },
+	// Stops as soon as the condition starts to hold, wherever the program is. It
+	// doesn't stop again until the condition has been false in between.
+	Condition(Condition),
+	// Stops after an instruction that accessed the inclusive address range.
+	Watch { start: u16, end: u16, access: Access },
+}
+
+// Why execution gave control back to the user.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stop {
+	Stepped,
+	Breakpoint(usize),
+	// The access the watchpoint was set for, and the kind of cycle that tripped it.
+	Watchpoint { id: usize, address: u16, value: u8, access: Access, kind: CycleKind },
+	Brk,
+	Jammed,
+	Waiting,
+	Error(CpuError),
+}
+
+impl fmt::Display for Stop {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Stop::Stepped => Ok(()),
+			Stop::Breakpoint(id) => write!(f, "breakpoint {} hit", id),
+			Stop::Watchpoint { id, address, value, kind, .. } => {
+				let access = match kind {
+					CycleKind::Read => "read",
+					CycleKind::Write => "write",
+				};
+				write!(f, "watchpoint {} hit: {} ${:02X} at ${:04X}", id, access, value, address)
+			}
+			Stop::Brk => write!(f, "stopped at BRK"),
+			Stop::Jammed => write!(f, "CPU jammed"),
+			Stop::Waiting => write!(f, "CPU waiting for an interrupt"),
+			Stop::Error(err) => write!(f, "{}", err),
+		}
+	}
+}
+
+const HELP: &str = "\
+step [n]                 execute n instructions (s)
+next                     step over subroutine calls (n)
+continue                 run until something stops execution (c)
+break <addr> [if <cond>] stop before the instruction at addr (b)
+break if <cond>          stop as soon as the condition holds
+watch [r|w|rw] <addr>[-<end>]  stop on bus accesses to the range
+delete <id>              remove a breakpoint or watchpoint
+list                     show breakpoints and watchpoints (l)
+regs                     show the registers (r)
+mem <addr> [len]         dump memory (x)
+disasm [addr] [count]    disassemble, from PC by default (d)
+symbols <file>           load labels from a ca65 .dbg, FCEUX .nl or Mesen .mlb file
+reset                    reset the CPU
+quit                     leave the debugger (q)
+Conditions compare A, X, Y, SP, PC, P or a flag N V B D I Z C with == != < <= > >=.
+Addresses can be given as numbers or as labels from the symbol files.
+An empty line repeats the previous command.";
+
+pub struct Debugger {
+	pub cpu: Cpu,
+	pub symbols: SymbolTable,
+	breakpoints: Vec<(usize, Breakpoint)>,
+	// The condition breakpoints whose condition held after the last instruction.
+	held: Vec<usize>,
+	next_id: usize,
+	last_command: String,
+}
+
+impl Debugger {
+	pub fn new(cpu: Cpu) -> Self {
+		Debugger {
+			cpu,
+			symbols: SymbolTable::new(),
+			breakpoints: Vec::new(),
+			held: Vec::new(),
+			next_id: 1,
+			last_command: String::new(),
+		}
+	}
+
+	pub fn breakpoints(&self) -> &[(usize, Breakpoint)] {
+		&self.breakpoints
+	}
+
+	pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+		let id = self.next_id;
+		self.next_id += 1;
+		if let Breakpoint::Condition(condition) = breakpoint {
+			if condition.holds(&self.cpu) {
+				self.held.push(id);
+			}
+		}
+		self.breakpoints.push((id, breakpoint));
+		id
+	}
+
+	pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+		let len = self.breakpoints.len();
+		self.breakpoints.retain(|(other, _)| *other != id);
+		self.held.retain(|other| *other != id);
+		self.breakpoints.len() != len
+	}
+
+	// Runs one instruction, then reports the first breakpoint or watchpoint it tripped.
+	pub fn step(&mut self) -> Stop {
+		if self.cpu.waiting {
+			return Stop::Waiting;
+		}
+		let log = self.cpu.cycle_log.replace(Vec::new());
+		let result = self.cpu.step();
+		let cycles = std::mem::replace(&mut self.cpu.cycle_log, log).unwrap_or_default();
+		if let Err(err) = result {
+			return Stop::Error(err);
+		}
+
+		for (id, breakpoint) in self.breakpoints.iter() {
+			if let Breakpoint::Watch { start, end, access } = breakpoint {
+				let hit = cycles.iter().find(|cycle| {
+					(*start..=*end).contains(&cycle.address) && access.matches(cycle.kind)
+				});
+				if let Some(cycle) = hit {
+					return Stop::Watchpoint {
+						id: *id,
+						address: cycle.address,
+						value: cycle.value,
+						access: *access,
+						kind: cycle.kind,
+					};
+				}
+			}
+		}
+
+		if self.cpu.jammed {
+			return Stop::Jammed;
+		}
+
+		// Every condition is checked, even past the first hit, so that the edges of
+		// the others aren't missed.
+		let held = std::mem::take(&mut self.held);
+		let mut stop = None;
+		for (id, breakpoint) in self.breakpoints.iter() {
+			let hit = match breakpoint {
+				Breakpoint::Address { address, condition } => {
+					*address == self.cpu.program_counter && condition.is_none_or(|c| c.holds(&self.cpu))
+				}
+				Breakpoint::Condition(condition) => {
+					let holds = condition.holds(&self.cpu);
+					if holds {
+						self.held.push(*id);
+					}
+					holds && !held.contains(id)
+				}
+				Breakpoint::Watch { .. } => false,
+			};
+			if hit {
+				stop.get_or_insert(Stop::Breakpoint(*id));
+			}
+		}
+		if let Some(stop) = stop {
+			return stop;
+		}
+
+		if self.cpu.waiting {
+			return Stop::Waiting;
+		}
+		Stop::Stepped
+	}
+
+	// Runs until something stops execution. Like Cpu::run, a BRK is not executed.
+	pub fn resume(&mut self) -> Stop {
+		loop {
+			if let Some(stop) = self.run_for(usize::MAX) {
+				return stop;
+			}
+		}
+	}
+
+	// Like resume, but gives up after a number of instructions so that callers
+	// get a chance to look for an interrupt request.
+	pub fn run_for(&mut self, limit: usize) -> Option<Stop> {
+		for _ in 0..limit {
+			if self.cpu.peek(self.cpu.program_counter) == 0x00 {
+				return Some(Stop::Brk);
+			}
+			match self.step() {
+				Stop::Stepped => {}
+				stop => return Some(stop),
+			}
+		}
+		None
+	}
+
+	// Steps over JSR by running until the stack unwinds back to the return address.
+	pub fn step_over(&mut self) -> Stop {
+		let pc = self.cpu.program_counter;
+		if self.cpu.peek(pc) != JSR.code {
+			return self.step();
+		}
+		let stack = self.cpu.stack_pointer;
+		let target = pc.wrapping_add(JSR.len as u16);
+		loop {
+			match self.step() {
+				Stop::Stepped => {}
+				stop => return stop,
+			}
+			if self.cpu.program_counter == target && self.cpu.stack_pointer >= stack {
+				return Stop::Stepped;
+			}
+			if self.cpu.peek(self.cpu.program_counter) == 0x00 {
+				return Stop::Brk;
+			}
+		}
+	}
+
+	pub fn repl<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+		write!(output, "(nes) ")?;
+		output.flush()?;
+		for line in input.lines() {
+			if !self.execute(&line?, output)? {
+				return Ok(());
+			}
+			write!(output, "(nes) ")?;
+			output.flush()?;
+		}
+		writeln!(output)
+	}
+
+	// Runs one command line, returning false once the user asks to quit.
+	pub fn execute<W: Write>(&mut self, line: &str, output: &mut W) -> io::Result<bool> {
+		let line = match line.trim() {
+			"" => self.last_command.clone(),
+			line => line.to_string(),
+		};
+		self.last_command = line.clone();
+
+		let mut words = line.split_whitespace();
+		let command = match words.next() {
+			Some(command) => command.to_ascii_lowercase(),
+			None => return Ok(true),
+		};
+		let args: Vec<&str> = words.collect();
+
+		match command.as_str() {
+			"s" | "step" => {
+				let count = match args.first() {
+					Some(arg) => match parse_number(arg) {
+						Some(count) => count,
+						None => return self.error(output, "expected a step count"),
+					},
+					None => 1,
+				};
+				let mut stop = Stop::Stepped;
+				for _ in 0..count {
+					stop = self.step();
+					if stop != Stop::Stepped {
+						break;
+					}
+				}
+				self.report(stop, output)?;
+			}
+			"n" | "next" => {
+				let stop = self.step_over();
+				self.report(stop, output)?;
+			}
+			"c" | "continue" => {
+				let stop = self.resume();
+				self.report(stop, output)?;
+			}
+			"b" | "break" => {
+				let breakpoint = match args.as_slice() {
+					["if", condition @ ..] => parse_condition(&condition.join(" ")).map(Breakpoint::Condition),
+					[address] => self.parse_address(address).map(|address| Breakpoint::Address { address, condition: None }),
+					[address, "if", condition @ ..] => self.parse_address(address).zip(parse_condition(&condition.join(" ")))
+						.map(|(address, condition)| Breakpoint::Address { address, condition: Some(condition) }),
+					_ => None,
+				};
+				match breakpoint {
+					Some(breakpoint) => {
+						let id = self.add_breakpoint(breakpoint);
+						writeln!(output, "breakpoint {}: {}", id, describe(&breakpoint))?;
+					}
+					None => return self.error(output, "usage: break <addr> [if <cond>] | break if <cond>"),
+				}
+			}
+			"w" | "watch" => {
+				let (access, range) = match args.as_slice() {
+					[range] => (Some(Access::ReadWrite), range),
+					[access, range] => (parse_access(access), range),
+					_ => (None, &""),
+				};
+				match access.zip(self.parse_range(range)) {
+					Some((access, (start, end))) => {
+						let breakpoint = Breakpoint::Watch { start, end, access };
+						let id = self.add_breakpoint(breakpoint);
+						writeln!(output, "watchpoint {}: {}", id, describe(&breakpoint))?;
+					}
+					None => return self.error(output, "usage: watch [r|w|rw] <addr>[-<end>]"),
+				}
+			}
+			"delete" | "del" => {
+				match args.first().and_then(|id| id.parse().ok()) {
+					Some(id) if self.remove_breakpoint(id) => writeln!(output, "deleted {}", id)?,
+					_ => return self.error(output, "no such breakpoint"),
+				}
+			}
+			"l" | "list" => {
+				if self.breakpoints.is_empty() {
+					writeln!(output, "no breakpoints")?;
+				}
+				for (id, breakpoint) in self.breakpoints.iter() {
+					writeln!(output, "{:>3}  {}", id, describe(breakpoint))?;
+				}
+			}
+			"r" | "regs" => self.print_registers(output)?,
+			"x" | "mem" => {
+				let address = args.first().and_then(|arg| self.parse_address(arg));
+				let len = args.get(1).map_or(Some(64), |arg| parse_number(arg));
+				match address.zip(len) {
+					Some((address, len)) => self.hexdump(address, len, output)?,
+					None => return self.error(output, "usage: mem <addr> [len]"),
+				}
+			}
+			"d" | "disasm" => {
+				let address = args.first().map_or(Some(self.cpu.program_counter), |arg| self.parse_address(arg));
+				let count = args.get(1).map_or(Some(10), |arg| parse_number(arg));
+				match address.zip(count) {
+					Some((address, count)) => self.disassemble(address, count, output)?,
+					None => return self.error(output, "usage: disasm [addr] [count]"),
+				}
+			}
+			"sym" | "symbols" => {
+				let path = match args.first() {
+					Some(path) => std::path::Path::new(path),
+					None => return self.error(output, "usage: symbols <file>"),
+				};
+				match self.symbols.load(path) {
+					Ok(count) => writeln!(output, "loaded {} symbols from {}", count, path.display())?,
+					Err(err) => return self.error(output, &format!("cannot load {}: {}", path.display(), err)),
+				}
+			}
+			"reset" => {
+				self.cpu.reset_from_vector();
+				self.print_registers(output)?;
+			}
+			"h" | "help" => writeln!(output, "{}", HELP)?,
+			"q" | "quit" | "exit" => return Ok(false),
+			_ => return self.error(output, &format!("unknown command {}, try help", command)),
+		}
+		Ok(true)
+	}
+
+	fn error<W: Write>(&self, output: &mut W, message: &str) -> io::Result<bool> {
+		writeln!(output, "{}", message)?;
+		Ok(true)
+	}
+
+	fn report<W: Write>(&self, stop: Stop, output: &mut W) -> io::Result<()> {
+		if stop != Stop::Stepped {
+			writeln!(output, "{}", stop)?;
+		}
+		let instruction = decode(&self.cpu, self.cpu.program_counter, self.cpu.variant);
+		writeln!(output, "{}", self.listing(&instruction, true))?;
+		self.print_registers(output)
+	}
+
+	pub fn print_registers<W: Write>(&self, output: &mut W) -> io::Result<()> {
+		let cpu = &self.cpu;
+		writeln!(
+			output,
+			"A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} PC:{:04X} P:{:02X} {} CYC:{}",
+			cpu.register_a, cpu.register_x, cpu.register_y, cpu.stack_pointer,
+			cpu.program_counter, cpu.status.0, cpu.status, cpu.cycles
+		)
+	}
+
+	pub fn hexdump<W: Write>(&self, address: u16, len: u16, output: &mut W) -> io::Result<()> {
+		let end = address as u32 + len as u32;
+		let mut row = address as u32 & !0xF;
+		while row < end {
+			let mut hex = String::new();
+			let mut text = String::new();
+			for addr in row..row + 16 {
+				if addr < address as u32 || addr >= end || addr > 0xFFFF {
+					hex.push_str("   ");
+					text.push(' ');
+					continue;
+				}
+				let byte = self.cpu.peek(addr as u16);
+				hex.push_str(&format!(" {:02X}", byte));
+				text.push(if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' });
+			}
+			writeln!(output, "{:04X}:{}  |{}|", row, hex, text)?;
+			row += 16;
+		}
+		Ok(())
+	}
+
+	pub fn disassemble<W: Write>(&self, address: u16, count: u16, output: &mut W) -> io::Result<()> {
+		let mut address = address;
+		for _ in 0..count {
+			let instruction = decode(&self.cpu, address, self.cpu.variant);
+			if let Some(symbol) = self.symbols.get(&self.cpu.bus, address) {
+				writeln!(output, "{}:", symbol.name)?;
+			}
+			writeln!(output, "{}", self.listing(&instruction, address == self.cpu.program_counter))?;
+			address = instruction.next_address();
+		}
+		Ok(())
+	}
+
+	fn listing(&self, instruction: &Instruction, current: bool) -> String {
+		let bytes: Vec<String> = instruction.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+		let marker = if current { '>' } else { ' ' };
+		let text = instruction.with_symbols(&self.symbols, &self.cpu.bus);
+		let line = format!("{} {:04X}  {:<9} {}", marker, instruction.address, bytes.join(" "), text);
+		match self.symbols.get(&self.cpu.bus, instruction.address) {
+			Some(symbol) if !symbol.comment.is_empty() => format!("{:<32} ; {}", line, symbol.comment),
+			_ => line,
+		}
+	}
+
+	// A number or a label.
+	fn parse_address(&self, text: &str) -> Option<u16> {
+		parse_number(text).or_else(|| self.symbols.resolve(&self.cpu.bus, text.trim()))
+	}
+
+	fn parse_range(&self, text: &str) -> Option<(u16, u16)> {
+		match text.split_once('-') {
+			Some((start, end)) => {
+				let (start, end) = (self.parse_address(start)?, self.parse_address(end)?);
+				(start <= end).then_some((start, end))
+			}
+			None => self.parse_address(text).map(|address| (address, address)),
+		}
+	}
+}
+
+fn describe(breakpoint: &Breakpoint) -> String {
+	match breakpoint {
+		Breakpoint::Address { address, condition: None } => format!("at ${:04X}", address),
+		Breakpoint::Address { address, condition: Some(condition) } => {
+			format!("at ${:04X} if {}", address, describe_condition(condition))
+		}
+		Breakpoint::Condition(condition) => format!("if {}", describe_condition(condition)),
+		Breakpoint::Watch { start, end, access } => {
+			let access = match access {
+				Access::Read => "read",
+				Access::Write => "write",
+				Access::ReadWrite => "read/write",
+			};
+			match start == end {
+				true => format!("{} ${:04X}", access, start),
+				false => format!("{} ${:04X}-${:04X}", access, start, end),
+			}
+		}
+	}
+}
+
+fn describe_condition(condition: &Condition) -> String {
+	let register = match condition.register {
+		Register::A => "A".to_string(),
+		Register::X => "X".to_string(),
+		Register::Y => "Y".to_string(),
+		Register::SP => "SP".to_string(),
+		Register::PC => "PC".to_string(),
+		Register::P => "P".to_string(),
+		Register::Flag(shift) => "CZIDB-VN".chars().nth(shift as usize).unwrap().to_string(),
+	};
+	let comparison = match condition.comparison {
+		Comparison::Equal => "==",
+		Comparison::NotEqual => "!=",
+		Comparison::Less => "<",
+		Comparison::LessEqual => "<=",
+		Comparison::Greater => ">",
+		Comparison::GreaterEqual => ">=",
+	};
+	format!("{} {} ${:02X}", register, comparison, condition.value)
+}
+
+// Accepts $hex, 0xhex, %binary and decimal numbers.
+pub fn parse_number(text: &str) -> Option<u16> {
+	let text = text.trim();
+	if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+		u16::from_str_radix(hex, 16).ok()
+	} else if let Some(bin) = text.strip_prefix('%') {
+		u16::from_str_radix(bin, 2).ok()
+	} else {
+		text.parse().ok()
+	}
+}
+
+fn parse_access(text: &str) -> Option<Access> {
+	match text.to_ascii_lowercase().as_str() {
+		"r" | "read" => Some(Access::Read),
+		"w" | "write" => Some(Access::Write),
+		"rw" | "wr" | "access" => Some(Access::ReadWrite),
+		_ => None,
+	}
+}
+
+pub fn parse_condition(text: &str) -> Option<Condition> {
+	let operators = [
+		("==", Comparison::Equal),
+		("!=", Comparison::NotEqual),
+		("<=", Comparison::LessEqual),
+		(">=", Comparison::GreaterEqual),
+		("<", Comparison::Less),
+		(">", Comparison::Greater),
+		("=", Comparison::Equal),
+	];
+	let (index, symbol, comparison) = operators
+		.iter()
+		.filter_map(|(symbol, comparison)| text.find(symbol).map(|index| (index, *symbol, *comparison)))
+		.min_by_key(|(index, symbol, _)| (*index, usize::MAX - symbol.len()))?;
+
+	let register = match text[..index].trim().to_ascii_uppercase().as_str() {
+		"A" => Register::A,
+		"X" => Register::X,
+		"Y" => Register::Y,
+		"SP" | "S" => Register::SP,
+		"PC" => Register::PC,
+		"P" => Register::P,
+		"N" => Register::Flag(7),
+		"V" => Register::Flag(6),
+		"B" => Register::Flag(4),
+		"D" => Register::Flag(3),
+		"I" => Register::Flag(2),
+		"Z" => Register::Flag(1),
+		"C" => Register::Flag(0),
+		_ => return None,
+	};
+	let value = parse_number(&text[index + symbol.len()..])?;
+	Some(Condition { register, comparison, value })
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cartridge::{Cartridge, Mirroring};
+
+	fn debugger(source: &str) -> Debugger {
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assembler::assemble(source).unwrap().bytes);
+		cpu.reset();
+		Debugger::new(cpu)
+	}
+
+	fn session(debugger: &mut Debugger, script: &str) -> String {
+		let mut output = Vec::new();
+		debugger.repl(script.as_bytes(), &mut output).unwrap();
+		String::from_utf8(output).unwrap()
+	}
+
+	const COUNTER: &str = "
+		      ldx #$00
+		loop: inx
+		      stx $10
+		      cpx #$05
+		      bne loop
+		      jsr sub
+		      lda $10
+		      brk
+		sub:  ldy #$07
+		      rts
+	";
+
+	#[test]
+	fn test_step_and_registers() {
+		let mut debugger = debugger(COUNTER);
+		let output = session(&mut debugger, "step\n\nregs\n");
+		assert_eq!(debugger.cpu.register_x, 1);
+		assert!(output.contains("> 0603  86 10     STX $10"));
+		assert!(output.contains("A:00 X:01 Y:00 SP:FD PC:0603 P:24 nv-bdIzc CYC:4"));
+	}
+
+	#[test]
+	fn test_breakpoints() {
+		let mut debugger = debugger(COUNTER);
+		let output = session(&mut debugger, "break $0602 if X == 3\ncontinue\n");
+		assert!(output.contains("breakpoint 1: at $0602 if X == $03"));
+		assert!(output.contains("breakpoint 1 hit"));
+		assert_eq!(debugger.cpu.register_x, 3);
+
+		session(&mut debugger, "delete 1\nb if Z == 1\nc\n");
+		assert_eq!(debugger.cpu.register_x, 5);
+		assert_eq!(debugger.cpu.program_counter, 0x0607);
+
+		// Z is still set, but the condition has to go false and back before it stops again.
+		let output = session(&mut debugger, "c\n");
+		assert!(output.contains("stopped at BRK"));
+		assert_eq!(debugger.cpu.register_y, 7);
+	}
+
+	#[test]
+	fn test_watchpoints() {
+		let mut debugger = debugger(COUNTER);
+		let output = session(&mut debugger, "watch w $10\nc\nc\n");
+		assert!(output.contains("watchpoint 1: write $0010"));
+		assert!(output.contains("watchpoint 1 hit: write $02 at $0010"));
+
+		let output = session(&mut debugger, "delete 1\nwatch r $0000-$00FF\nc\n");
+		assert!(output.contains("watchpoint 2 hit: read $05 at $0010"));
+		assert_eq!(debugger.cpu.register_a, 5);
+	}
+
+	#[test]
+	fn test_symbols() {
+		use crate::symbols::Location;
+		let mut debugger = debugger(COUNTER);
+		debugger.symbols.insert(Location::Cpu(0x0602), "loop", "count up to five", 1);
+		debugger.symbols.insert(Location::Cpu(0x0010), "counter", "", 1);
+		let output = session(&mut debugger, "b loop if X == 2
+c
+disasm loop 2
+");
+		assert!(output.contains("breakpoint 1: at $0602 if X == $02"));
+		assert_eq!(debugger.cpu.register_x, 2);
+		assert!(output.contains("loop:\n> 0602  E8        INX            ; count up to five\n  0603  86 10     STX counter\n"));
+
+		let output = session(&mut debugger, "symbols missing.txt
+b nowhere
+");
+		assert!(output.contains("cannot load missing.txt"));
+		assert!(output.contains("usage: break"));
+	}
+
+	#[test]
+	fn test_next_steps_over_subroutines() {
+		let mut debugger = debugger(COUNTER);
+		session(&mut debugger, "b $0609\nc\ndelete 1\nnext\n");
+		assert_eq!(debugger.cpu.program_counter, 0x060C);
+		assert_eq!(debugger.cpu.register_y, 7);
+	}
+
+	#[test]
+	fn test_reset_uses_vector() {
+		let mut prg_rom = vec![0xEA; 0x4000];
+		prg_rom[0x3FFC..].copy_from_slice(&[0x34, 0xC2, 0x00, 0xC0]);
+		let mut cpu = Cpu::new();
+		cpu.bus.insert(Cartridge {
+			prg_rom,
+			chr_rom: Vec::new(),
+			prg_ram: Vec::new(),
+			mapper: 0,
+			mirroring: Mirroring::Horizontal,
+			battery: false,
+			region: None,
+		});
+		let mut debugger = Debugger::new(cpu);
+		let output = session(&mut debugger, "step\nreset\n");
+		assert!(output.contains("PC:C234"));
+		assert_eq!(debugger.cpu.program_counter, 0xC234);
+	}
+
+	#[test]
+	fn test_memory_and_disassembly() {
+		let mut debugger = debugger(COUNTER);
+		let output = session(&mut debugger, "x $0600 8\nd $0600 2\n");
+		assert!(output.contains("0600: A2 00 E8 86 10 E0 05 D0"));
+		assert!(output.contains("|...."));
+		assert!(output.contains("> 0600  A2 00     LDX #$00"));
+		assert!(output.contains("  0602  E8        INX"));
+	}
+
+	#[test]
+	fn test_parse_condition() {
+		assert_eq!(parse_condition("A>=$80"), Some(Condition {
+			register: Register::A,
+			comparison: Comparison::GreaterEqual,
+			value: 0x80,
+		}));
+		assert_eq!(parse_condition("c != 0").unwrap().register, Register::Flag(0));
+		assert_eq!(parse_condition("Q == 1"), None);
+	}
+}
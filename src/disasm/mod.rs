@@ -0,0 +1,253 @@
+use std::fmt;
+use std::ops::Range;
+
+use super::cpu::{AddressingMode, CpuVariant, OpCodeDef};
+use super::memory::Memory;
+use super::bus::Bus;
+use super::symbols::SymbolTable;
+
+// A single decoded instruction. Everything a tracer or debugger needs is kept
+// in structured form, the text rendering is only done on demand.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Instruction {
+	pub address: u16,
+	pub opcode: u8,
+	pub mnemonic: &'static str,
+	pub mode: AddressingMode,
+	// Raw operand bytes, little endian. BBR/BBS keep the zero page address
+	// in the low byte and the branch offset in the high byte.
+	pub operand: u16,
+	// The address the operand names, with branch offsets already resolved.
+	// Indexed and indirect modes give their base address.
+	pub target: Option<u16>,
+	pub len: u8,
+	pub official: bool,
+}
+
+impl Instruction {
+	pub fn next_address(&self) -> u16 {
+		self.address.wrapping_add(self.len as u16)
+	}
+
+	pub fn bytes(&self) -> Vec<u8> {
+		let mut bytes = vec![self.opcode];
+		if self.len > 1 {
+			bytes.push(self.operand as u8);
+		}
+		if self.len > 2 {
+			bytes.push((self.operand >> 8) as u8);
+		}
+		bytes
+	}
+
+	// The operand in ca65 syntax. Absolute operands that fit in the zero page get
+	// the a: prefix so that reassembling them keeps the same opcode.
+	pub fn operand_text(&self) -> String {
+		self.format_operand(|address, digits| format!("${:0digits$X}", address, digits = digits))
+	}
+
+	// Same as Display, but with addresses named after the symbols that cover them.
+	pub fn with_symbols(&self, symbols: &SymbolTable, bus: &Bus) -> String {
+		let operand = self.format_operand(|address, digits| symbols.name(bus, address, digits));
+		let marker = if self.official { "" } else { "*" };
+		match operand.is_empty() {
+			true => format!("{}{}", marker, self.mnemonic),
+			false => format!("{}{} {}", marker, self.mnemonic, operand),
+		}
+	}
+
+	// Renders the operand with the given function naming addresses, which gets the
+	// number of hex digits a plain address would have.
+	fn format_operand<F: Fn(u16, usize) -> String>(&self, name: F) -> String {
+		let byte = self.operand as u8;
+		let zp = name(byte as u16, 2);
+		let word = self.operand;
+		let abs = if word < 0x100 { "a:" } else { "" };
+		let target = || name(self.target.unwrap_or_default(), 4);
+		match self.mode {
+			AddressingMode::Implied => String::new(),
+			AddressingMode::Accumulator => "A".to_string(),
+			AddressingMode::Immediate => format!("#${:02X}", byte),
+			AddressingMode::ZeroPage => zp,
+			AddressingMode::ZeroPageX => format!("{},X", zp),
+			AddressingMode::ZeroPageY => format!("{},Y", zp),
+			AddressingMode::Absolute => format!("{}{}", abs, name(word, 4)),
+			AddressingMode::AbsoluteX => format!("{}{},X", abs, name(word, 4)),
+			AddressingMode::AbsoluteY => format!("{}{},Y", abs, name(word, 4)),
+			AddressingMode::Indirect => format!("({})", name(word, 4)),
+			AddressingMode::IndirectX => format!("({},X)", zp),
+			AddressingMode::IndirectY => format!("({}),Y", zp),
+			AddressingMode::ZeroPageIndirect => format!("({})", zp),
+			AddressingMode::AbsoluteIndirectX => format!("({},X)", name(word, 4)),
+			AddressingMode::Relative => target(),
+			AddressingMode::ZeroPageRelative => format!("{},{}", zp, target()),
+		}
+	}
+}
+
+// Renders the instruction the way ca65 accepts it. Unofficial opcodes are marked
+// with a leading * like nestest does, which has to be stripped before assembling.
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if !self.official {
+			write!(f, "*")?;
+		}
+		write!(f, "{}", self.mnemonic)?;
+		let operand = self.operand_text();
+		if !operand.is_empty() {
+			write!(f, " {}", operand)?;
+		}
+		Ok(())
+	}
+}
+
+// The decode tables use the names from the 6502 undocumented opcode document,
+// ca65 knows most unofficial instructions under different ones.
+pub fn ca65_mnemonic(name: &'static str) -> &'static str {
+	match name {
+		"AAC" => "ANC",
+		"AAX" => "SAX",
+		"ASR" => "ALR",
+		"ATX" => "LAX",
+		"AXA" => "SHA",
+		"KIL" => "JAM",
+		"LAR" => "LAS",
+		"SXA" => "SHX",
+		"SYA" => "SHY",
+		"XAA" => "ANE",
+		"XAS" => "TAS",
+		_ => name,
+	}
+}
+
+pub fn decode<M: Memory + ?Sized>(memory: &M, address: u16, variant: CpuVariant) -> Instruction {
+	let def: &OpCodeDef = &variant.opcodes()[memory.peek(address) as usize];
+	let mut operand = 0;
+	for i in 1..def.len as u16 {
+		operand |= (memory.peek(address.wrapping_add(i)) as u16) << (8 * (i - 1));
+	}
+
+	let next = address.wrapping_add(def.len as u16);
+	let relative = |offset: u8| next.wrapping_add(offset as i8 as u16);
+	let target = match def.mode {
+		AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Immediate => None,
+		AddressingMode::ZeroPage
+		| AddressingMode::ZeroPageX
+		| AddressingMode::ZeroPageY
+		| AddressingMode::IndirectX
+		| AddressingMode::IndirectY
+		| AddressingMode::ZeroPageIndirect => Some(operand & 0xFF),
+		AddressingMode::Absolute
+		| AddressingMode::AbsoluteX
+		| AddressingMode::AbsoluteY
+		| AddressingMode::Indirect
+		| AddressingMode::AbsoluteIndirectX => Some(operand),
+		AddressingMode::Relative => Some(relative(operand as u8)),
+		AddressingMode::ZeroPageRelative => Some(relative((operand >> 8) as u8)),
+	};
+
+	Instruction {
+		address,
+		opcode: def.code,
+		mnemonic: ca65_mnemonic(def.name),
+		mode: def.mode,
+		operand,
+		target,
+		len: def.len,
+		official: def.official,
+	}
+}
+
+// Decodes every instruction starting inside the range. The last one may run past its end.
+pub fn decode_range<M: Memory + ?Sized>(memory: &M, range: Range<u16>, variant: CpuVariant) -> Vec<Instruction> {
+	let mut instructions = Vec::new();
+	let mut address = range.start as u32;
+	while address < range.end as u32 {
+		let instruction = decode(memory, address as u16, variant);
+		address += instruction.len as u32;
+		instructions.push(instruction);
+	}
+	instructions
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cpu::Cpu;
+
+	fn load(program: &[u8]) -> Cpu {
+		let mut cpu = Cpu::new();
+		for (i, byte) in program.iter().enumerate() {
+			cpu.write(0x0600 + i as u16, *byte);
+		}
+		cpu
+	}
+
+	fn text(program: &[u8], variant: CpuVariant) -> String {
+		decode(&load(program), 0x0600, variant).to_string()
+	}
+
+	#[test]
+	fn test_addressing_modes() {
+		let nes = CpuVariant::Ricoh2A03;
+		assert_eq!(text(&[0xEA], nes), "NOP");
+		assert_eq!(text(&[0x0A], nes), "ASL A");
+		assert_eq!(text(&[0xA9, 0x42], nes), "LDA #$42");
+		assert_eq!(text(&[0xA5, 0x10], nes), "LDA $10");
+		assert_eq!(text(&[0xB5, 0x10], nes), "LDA $10,X");
+		assert_eq!(text(&[0xB6, 0x10], nes), "LDX $10,Y");
+		assert_eq!(text(&[0xAD, 0x00, 0x20], nes), "LDA $2000");
+		assert_eq!(text(&[0xAD, 0x10, 0x00], nes), "LDA a:$0010");
+		assert_eq!(text(&[0xBD, 0x00, 0x20], nes), "LDA $2000,X");
+		assert_eq!(text(&[0xB9, 0x00, 0x20], nes), "LDA $2000,Y");
+		assert_eq!(text(&[0x6C, 0xFC, 0xFF], nes), "JMP ($FFFC)");
+		assert_eq!(text(&[0xA1, 0x10], nes), "LDA ($10,X)");
+		assert_eq!(text(&[0xB1, 0x10], nes), "LDA ($10),Y");
+		assert_eq!(text(&[0xD0, 0xFE], nes), "BNE $0600");
+	}
+
+	#[test]
+	fn test_unofficial_opcodes() {
+		let nes = CpuVariant::Ricoh2A03;
+		assert_eq!(text(&[0x04, 0x10], nes), "*NOP $10");
+		assert_eq!(text(&[0xA7, 0x10], nes), "*LAX $10");
+		assert_eq!(text(&[0x87, 0x10], nes), "*SAX $10");
+		assert_eq!(text(&[0xEB, 0x01], nes), "*SBC #$01");
+		assert_eq!(text(&[0x02], nes), "*JAM");
+	}
+
+	#[test]
+	fn test_cmos_opcodes() {
+		let cmos = CpuVariant::Wdc65C02;
+		assert_eq!(text(&[0x80, 0x02], cmos), "BRA $0604");
+		assert_eq!(text(&[0xB2, 0x10], cmos), "LDA ($10)");
+		assert_eq!(text(&[0x7C, 0x00, 0x20], cmos), "JMP ($2000,X)");
+		assert_eq!(text(&[0x1A], cmos), "INC A");
+		assert_eq!(text(&[0x87, 0x10], cmos), "SMB0 $10");
+		assert_eq!(text(&[0x0F, 0x10, 0xFD], cmos), "BBR0 $10,$0600");
+	}
+
+	#[test]
+	fn test_structured_output() {
+		let cpu = load(&[0x20, 0x34, 0x12, 0xF0, 0x80, 0x0F, 0x10, 0x05]);
+		let instructions = decode_range(&cpu, 0x0600..0x0608, CpuVariant::Ricoh2A03);
+		assert_eq!(instructions.len(), 3);
+
+		let jsr = instructions[0];
+		assert_eq!(jsr.mnemonic, "JSR");
+		assert_eq!(jsr.operand, 0x1234);
+		assert_eq!(jsr.target, Some(0x1234));
+		assert_eq!(jsr.len, 3);
+		assert_eq!(jsr.bytes(), vec![0x20, 0x34, 0x12]);
+
+		let beq = instructions[1];
+		assert_eq!(beq.address, 0x0603);
+		assert_eq!(beq.target, Some(0x0585));
+		assert_eq!(beq.next_address(), 0x0605);
+
+		let slo = instructions[2];
+		assert_eq!(slo.mnemonic, "SLO");
+		assert!(!slo.official);
+		assert_eq!(slo.target, Some(0x0510));
+	}
+}
@@ -0,0 +1,517 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::cpu::{Cpu, CycleKind, Memory};
+use super::debugger::{Access, Breakpoint, Debugger, Stop};
+
+// GDB has no 6502 support of its own, so the register layout is described to it
+// through target.xml. The order here is the order of the g and G packets.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.nes-rs.6502">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+const REGISTERS: usize = 6;
+
+// The largest packet the stub takes or sends, as advertised in qSupported. Memory
+// goes over the wire as two hex digits a byte, so m and M move half that at most.
+const PACKET_SIZE: usize = 0x4000;
+
+// How many instructions run between checks for an interrupt from the client.
+const POLL_INTERVAL: usize = 4096;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// The transport a session talks over. Besides plain reads and writes the stub needs
+// to notice the ^C GDB sends while the target runs, without blocking on it.
+pub trait Connection: Read + Write {
+	fn poll_interrupt(&mut self) -> io::Result<bool> {
+		Ok(false)
+	}
+}
+
+impl Connection for TcpStream {
+	fn poll_interrupt(&mut self) -> io::Result<bool> {
+		self.set_nonblocking(true)?;
+		let mut byte = [0];
+		let result = self.peek(&mut byte);
+		self.set_nonblocking(false)?;
+		match result {
+			Ok(1) if byte[0] == 0x03 => {
+				self.read_exact(&mut byte)?;
+				Ok(true)
+			}
+			Ok(_) => Ok(false),
+			Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+			Err(err) => Err(err),
+		}
+	}
+}
+
+pub struct GdbStub<C: Connection> {
+	connection: C,
+	pub debugger: Debugger,
+	buffer: Vec<u8>,
+	position: usize,
+	no_ack: bool,
+}
+
+// Waits for a single GDB client on the address and serves it until it detaches or kills the target.
+pub fn serve<A: ToSocketAddrs>(address: A, cpu: Cpu) -> io::Result<Cpu> {
+	let listener = TcpListener::bind(address)?;
+	let (stream, _) = listener.accept()?;
+	stream.set_nodelay(true)?;
+	let mut stub = GdbStub::new(stream, cpu);
+	stub.run()?;
+	Ok(stub.debugger.cpu)
+}
+
+impl<C: Connection> GdbStub<C> {
+	pub fn new(connection: C, cpu: Cpu) -> Self {
+		GdbStub {
+			connection,
+			debugger: Debugger::new(cpu),
+			buffer: Vec::new(),
+			position: 0,
+			no_ack: false,
+		}
+	}
+
+	pub fn run(&mut self) -> io::Result<()> {
+		while let Some(packet) = self.receive()? {
+			match self.handle(&packet)? {
+				Some(reply) => self.send(&reply)?,
+				None => return Ok(()),
+			}
+		}
+		Ok(())
+	}
+
+	fn next_byte(&mut self) -> io::Result<Option<u8>> {
+		if self.position == self.buffer.len() {
+			self.buffer.resize(1024, 0);
+			let len = self.connection.read(&mut self.buffer)?;
+			self.buffer.truncate(len);
+			self.position = 0;
+			if len == 0 {
+				return Ok(None);
+			}
+		}
+		self.position += 1;
+		Ok(Some(self.buffer[self.position - 1]))
+	}
+
+	// Reads the next $data#checksum packet, acknowledging it unless no-ack mode is on.
+	// Stray acks and interrupts outside of a packet are skipped.
+	fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+		loop {
+			match self.next_byte()? {
+				None => return Ok(None),
+				Some(b'$') => {}
+				Some(_) => continue,
+			}
+
+			let mut data = Vec::new();
+			loop {
+				match self.next_byte()? {
+					None => return Ok(None),
+					Some(b'#') => break,
+					Some(byte) => data.push(byte),
+				}
+			}
+			let mut checksum = [0; 2];
+			for digit in checksum.iter_mut() {
+				match self.next_byte()? {
+					Some(byte) => *digit = byte,
+					None => return Ok(None),
+				}
+			}
+
+			if self.no_ack {
+				return Ok(Some(data));
+			}
+			let expected = std::str::from_utf8(&checksum).ok().and_then(|text| u8::from_str_radix(text, 16).ok());
+			if expected == Some(checksum_of(&data)) {
+				self.connection.write_all(b"+")?;
+				return Ok(Some(data));
+			}
+			self.connection.write_all(b"-")?;
+		}
+	}
+
+	fn send(&mut self, reply: &str) -> io::Result<()> {
+		let data = escape(reply.as_bytes());
+		let mut packet = Vec::with_capacity(data.len() + 4);
+		packet.push(b'$');
+		packet.extend_from_slice(&data);
+		packet.extend_from_slice(format!("#{:02x}", checksum_of(&data)).as_bytes());
+		self.connection.write_all(&packet)?;
+		self.connection.flush()
+	}
+
+	// Answers one packet. None ends the session. Unsupported packets get the
+	// empty reply, which tells GDB to fall back to something else.
+	fn handle(&mut self, packet: &[u8]) -> io::Result<Option<String>> {
+		let Some((&command, args)) = packet.split_first() else {
+			return Ok(Some(String::new()));
+		};
+		// Only the arguments are text, so a stray byte can't land inside a character.
+		let args = String::from_utf8_lossy(args).into_owned();
+		let args = args.as_str();
+		let reply = match command {
+			b'?' => format!("S{:02x}", SIGTRAP),
+			b'g' => (0..REGISTERS).map(|n| self.read_register(n)).collect(),
+			b'G' => {
+				let bytes = decode_hex(args);
+				let mut offset = 0;
+				for n in 0..REGISTERS {
+					let width = register_width(n);
+					match bytes.get(offset..offset + width) {
+						Some(value) => self.write_register(n, value),
+						None => return Ok(Some("E01".to_string())),
+					}
+					offset += width;
+				}
+				"OK".to_string()
+			}
+			b'p' => match usize::from_str_radix(args, 16) {
+				Ok(n) if n < REGISTERS => self.read_register(n),
+				_ => "E01".to_string(),
+			},
+			b'P' => {
+				let parsed = args.split_once('=').and_then(|(n, value)| usize::from_str_radix(n, 16).ok().zip(Some(decode_hex(value))));
+				match parsed {
+					Some((n, value)) if n < REGISTERS && value.len() == register_width(n) => {
+						self.write_register(n, &value);
+						"OK".to_string()
+					}
+					_ => "E01".to_string(),
+				}
+			}
+			b'm' => match parse_address_length(args) {
+				Some((address, len)) => (0..len)
+					.map(|i| format!("{:02x}", self.debugger.cpu.peek(address.wrapping_add(i as u16))))
+					.collect(),
+				None => "E01".to_string(),
+			},
+			b'M' => {
+				let parsed = args.split_once(':').and_then(|(range, data)| parse_address_length(range).zip(Some(decode_hex(data))));
+				match parsed {
+					Some(((address, len), data)) if data.len() == len => {
+						for (i, byte) in data.into_iter().enumerate() {
+							self.debugger.cpu.poke(address.wrapping_add(i as u16), byte);
+						}
+						"OK".to_string()
+					}
+					_ => "E01".to_string(),
+				}
+			}
+			b'c' | b's' => {
+				if let Ok(address) = u16::from_str_radix(args, 16) {
+					self.debugger.cpu.program_counter = address;
+				}
+				let stop = match command {
+					b's' => Some(self.debugger.step()),
+					_ => self.resume()?,
+				};
+				stop_reply(stop)
+			}
+			b'Z' | b'z' => self.breakpoint(command == b'Z', args),
+			b'k' => return Ok(None),
+			b'D' => {
+				self.send("OK")?;
+				return Ok(None);
+			}
+			b'H' => "OK".to_string(),
+			b'q' | b'Q' => self.query(&String::from_utf8_lossy(packet)),
+			_ => String::new(),
+		};
+		Ok(Some(reply))
+	}
+
+	// Runs until the debugger stops, or None once the client interrupts.
+	fn resume(&mut self) -> io::Result<Option<Stop>> {
+		loop {
+			if let Some(stop) = self.debugger.run_for(POLL_INTERVAL) {
+				return Ok(Some(stop));
+			}
+			if self.connection.poll_interrupt()? {
+				return Ok(None);
+			}
+		}
+	}
+
+	fn query(&mut self, packet: &str) -> String {
+		if packet.starts_with("qSupported") {
+			return format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+");
+		}
+		if packet == "QStartNoAckMode" {
+			self.no_ack = true;
+			return "OK".to_string();
+		}
+		if packet == "qAttached" {
+			return "1".to_string();
+		}
+		if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+			return match parse_address_length(range) {
+				Some((offset, len)) => {
+					let offset = (offset as usize).min(TARGET_XML.len());
+					let end = (offset + len).min(TARGET_XML.len());
+					let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+					format!("{}{}", marker, &TARGET_XML[offset..end])
+				}
+				None => "E01".to_string(),
+			};
+		}
+		String::new()
+	}
+
+	// Z0 and Z1 are both plain breakpoints here, Z2 to Z4 watch writes, reads and both.
+	fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+		let mut fields = args.split(',');
+		let kind = fields.next();
+		let address = fields.next().and_then(|address| u16::from_str_radix(address, 16).ok());
+		let len = fields.next().and_then(|len| u16::from_str_radix(len, 16).ok()).unwrap_or(1).max(1);
+		let address = match address {
+			Some(address) => address,
+			None => return "E01".to_string(),
+		};
+		let end = address.saturating_add(len - 1);
+		let breakpoint = match kind {
+			Some("0") | Some("1") => Breakpoint::Address { address, condition: None },
+			Some("2") => Breakpoint::Watch { start: address, end, access: Access::Write },
+			Some("3") => Breakpoint::Watch { start: address, end, access: Access::Read },
+			Some("4") => Breakpoint::Watch { start: address, end, access: Access::ReadWrite },
+			_ => return String::new(),
+		};
+
+		if insert {
+			self.debugger.add_breakpoint(breakpoint);
+			return "OK".to_string();
+		}
+		let id = self.debugger.breakpoints().iter().find(|(_, other)| *other == breakpoint).map(|(id, _)| *id);
+		match id {
+			Some(id) => {
+				self.debugger.remove_breakpoint(id);
+				"OK".to_string()
+			}
+			None => "E01".to_string(),
+		}
+	}
+
+	fn read_register(&self, n: usize) -> String {
+		let cpu = &self.debugger.cpu;
+		match n {
+			0 => format!("{:02x}", cpu.register_a),
+			1 => format!("{:02x}", cpu.register_x),
+			2 => format!("{:02x}", cpu.register_y),
+			3 => format!("{:02x}", cpu.status.0),
+			4 => format!("{:02x}", cpu.stack_pointer),
+			_ => format!("{:02x}{:02x}", cpu.program_counter as u8, cpu.program_counter >> 8),
+		}
+	}
+
+	fn write_register(&mut self, n: usize, value: &[u8]) {
+		let cpu = &mut self.debugger.cpu;
+		match n {
+			0 => cpu.register_a = value[0],
+			1 => cpu.register_x = value[0],
+			2 => cpu.register_y = value[0],
+			3 => cpu.status.0 = value[0],
+			4 => cpu.stack_pointer = value[0],
+			_ => cpu.program_counter = u16::from_le_bytes([value[0], value[1]]),
+		}
+	}
+}
+
+fn register_width(n: usize) -> usize {
+	if n == REGISTERS - 1 { 2 } else { 1 }
+}
+
+// Watchpoint hits name the kind and address so GDB can report which one fired. The
+// kind has to match the Z packet that set it, awatch for Z4.
+fn stop_reply(stop: Option<Stop>) -> String {
+	match stop {
+		None => format!("S{:02x}", SIGINT),
+		Some(Stop::Watchpoint { address, access, kind, .. }) => {
+			let kind = match (access, kind) {
+				(Access::ReadWrite, _) => "awatch",
+				(_, CycleKind::Read) => "rwatch",
+				(_, CycleKind::Write) => "watch",
+			};
+			format!("T{:02x}{}:{:04x};", SIGTRAP, kind, address)
+		}
+		Some(Stop::Jammed) | Some(Stop::Error(_)) => format!("S{:02x}", SIGILL),
+		Some(_) => format!("S{:02x}", SIGTRAP),
+	}
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+	data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+// $, #, } and * can't appear raw in a packet body.
+fn escape(data: &[u8]) -> Vec<u8> {
+	let mut escaped = Vec::with_capacity(data.len());
+	for &byte in data {
+		if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+			escaped.push(b'}');
+			escaped.push(byte ^ 0x20);
+		} else {
+			escaped.push(byte);
+		}
+	}
+	escaped
+}
+
+fn decode_hex(text: &str) -> Vec<u8> {
+	(0..text.len() / 2)
+		.filter_map(|i| u8::from_str_radix(text.get(2 * i..2 * i + 2)?, 16).ok())
+		.collect()
+}
+
+fn parse_address_length(text: &str) -> Option<(u16, usize)> {
+	let (address, len) = text.split_once(',')?;
+	let len = usize::from_str_radix(len, 16).ok().filter(|&len| len <= PACKET_SIZE / 2)?;
+	Some((u16::from_str_radix(address, 16).ok()?, len))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::io::Cursor;
+
+	struct Script {
+		input: Cursor<Vec<u8>>,
+		output: Vec<u8>,
+	}
+
+	impl Read for Script {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			self.input.read(buf)
+		}
+	}
+
+	impl Write for Script {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.output.write(buf)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	impl Connection for Script {}
+
+	fn packet(data: &str) -> String {
+		format!("${}#{:02x}", data, checksum_of(data.as_bytes()))
+	}
+
+	// Feeds the packets to a stub and returns every reply, acks stripped.
+	fn session(source: &str, packets: &[&str]) -> (Vec<String>, Cpu) {
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assembler::assemble(source).unwrap().bytes);
+		cpu.reset();
+		let input: String = packets.iter().map(|data| packet(data)).collect();
+		let script = Script { input: Cursor::new(input.into_bytes()), output: Vec::new() };
+		let mut stub = GdbStub::new(script, cpu);
+		stub.run().unwrap();
+
+		let output = String::from_utf8(stub.connection.output).unwrap();
+		let replies = output
+			.split('$')
+			.skip(1)
+			.map(|reply| reply.split('#').next().unwrap().to_string())
+			.collect();
+		(replies, stub.debugger.cpu)
+	}
+
+	const PROGRAM: &str = "
+		      ldx #$00
+		loop: inx
+		      stx $10
+		      cpx #$03
+		      bne loop
+		      lda $10
+		      brk
+	";
+
+	#[test]
+	fn test_registers() {
+		let (replies, cpu) = session(PROGRAM, &["?", "g", "s", "p5", "P0=42", "G01020324fd0006", "p2"]);
+		assert_eq!(replies, vec!["S05", "00000024fd0006", "S05", "0206", "OK", "OK", "03"]);
+		assert_eq!(cpu.register_a, 0x01);
+		assert_eq!(cpu.status.0, 0x24);
+		assert_eq!(cpu.program_counter, 0x0600);
+	}
+
+	#[test]
+	fn test_non_ascii_packet() {
+		let mut input = b"$\xff#ff".to_vec();
+		input.extend_from_slice(packet("?").as_bytes());
+		let script = Script { input: Cursor::new(input), output: Vec::new() };
+		let mut stub = GdbStub::new(script, Cpu::new());
+		stub.run().unwrap();
+		assert_eq!(stub.connection.output, b"+$#00+$S05#b8");
+	}
+
+	#[test]
+	fn test_memory() {
+		let (replies, cpu) = session(PROGRAM, &["m600,4", "M10,2:abcd", "m10,2", "mzz", "m0,2001", "mffff,ffffffffffffffff", "M10,2001:00"]);
+		assert_eq!(replies, vec!["a200e886", "OK", "abcd", "E01", "E01", "E01", "E01"]);
+		assert_eq!(cpu.peek(0x11), 0xCD);
+	}
+
+	#[test]
+	fn test_breakpoints_and_watchpoints() {
+		let (replies, cpu) = session(PROGRAM, &[
+			"Z0,609,1", "Z2,10,1", "c", "g", "z2,10,1", "c", "z0,609,1", "Z3,10,1", "c", "z3,10,1", "c",
+		]);
+		assert_eq!(replies, vec![
+			"OK", "OK", "T05watch:0010;", "00010024fd0506", "OK", "S05", "OK", "OK", "T05rwatch:0010;", "OK", "S05",
+		]);
+		assert_eq!(cpu.register_a, 3);
+		assert_eq!(cpu.program_counter, 0x060B);
+
+		// Access watchpoints report as awatch whichever way they were tripped.
+		let (replies, _) = session(PROGRAM, &["Z4,10,1", "c", "c"]);
+		assert_eq!(replies, vec!["OK", "T05awatch:0010;", "T05awatch:0010;"]);
+	}
+
+	#[test]
+	fn test_queries() {
+		let (replies, _) = session(PROGRAM, &["qSupported:xmlRegisters=i386", "qXfer:features:read:target.xml:0,10", "qAttached", "vMustReplyEmpty"]);
+		assert!(replies[0].contains("qXfer:features:read+"));
+		assert_eq!(replies[1], "m<?xml version=\"1");
+		assert_eq!(replies[2], "1");
+		assert_eq!(replies[3], "");
+
+		let (replies, _) = session(PROGRAM, &["qXfer:features:read:target.xml:0,1000"]);
+		assert!(replies[0].starts_with('l'));
+		assert!(replies[0].ends_with("</target>\n"));
+	}
+
+	#[test]
+	fn test_acks_and_detach() {
+		let script = Script {
+			input: Cursor::new(format!("+$g#00{}{}", packet("D"), packet("g")).into_bytes()),
+			output: Vec::new(),
+		};
+		let mut stub = GdbStub::new(script, Cpu::new());
+		stub.run().unwrap();
+		let output = String::from_utf8(stub.connection.output).unwrap();
+		assert_eq!(output, "-+$OK#9a");
+	}
+}
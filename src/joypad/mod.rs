@@ -0,0 +1,74 @@
+use super::state::{Snapshot, StateError, StateReader, StateWriter};
+
+// Button bits in the order the controller shifts them out.
+pub const BUTTON_A: u8 = 0b0000_0001;
+pub const BUTTON_B: u8 = 0b0000_0010;
+pub const BUTTON_SELECT: u8 = 0b0000_0100;
+pub const BUTTON_START: u8 = 0b0000_1000;
+pub const BUTTON_UP: u8 = 0b0001_0000;
+pub const BUTTON_DOWN: u8 = 0b0010_0000;
+pub const BUTTON_LEFT: u8 = 0b0100_0000;
+pub const BUTTON_RIGHT: u8 = 0b1000_0000;
+
+// A standard controller. While the strobe is high the shift register keeps reloading
+// from the buttons, once it drops every read shifts out the next button. After all
+// eight an official controller keeps returning 1.
+pub struct Joypad {
+	pub buttons: u8,
+	strobe: bool,
+	shift: u8,
+}
+
+impl Joypad {
+	pub fn new() -> Self {
+		Joypad {
+			buttons: 0,
+			strobe: false,
+			shift: 0,
+		}
+	}
+
+	pub fn write(&mut self, data: u8) {
+		self.strobe = data & 1 != 0;
+		if self.strobe {
+			self.shift = self.buttons;
+		}
+	}
+
+	pub fn read(&mut self) -> u8 {
+		let data = self.peek();
+		if !self.strobe {
+			self.shift = self.shift >> 1 | 0x80;
+		}
+		data
+	}
+
+	// The bit the next read returns, without shifting.
+	pub fn peek(&self) -> u8 {
+		match self.strobe {
+			true => self.buttons & BUTTON_A,
+			false => self.shift & 1,
+		}
+	}
+}
+
+impl Default for Joypad {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Snapshot for Joypad {
+	fn save(&self, writer: &mut StateWriter) {
+		writer.write_u8(self.buttons);
+		writer.write_bool(self.strobe);
+		writer.write_u8(self.shift);
+	}
+
+	fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+		self.buttons = reader.read_u8()?;
+		self.strobe = reader.read_bool("joypad strobe")?;
+		self.shift = reader.read_u8()?;
+		Ok(())
+	}
+}
@@ -0,0 +1,18 @@
+pub mod cpu;
+pub mod bus;
+pub mod memory;
+pub mod joypad;
+pub mod region;
+pub mod cartridge;
+pub mod cheats;
+pub mod symbols;
+pub mod disasm;
+pub mod assembler;
+pub mod debugger;
+pub mod search;
+pub mod cdl;
+pub mod profiler;
+pub mod gdb;
+pub mod state;
+pub mod rewind;
+pub mod movie;
@@ -1,11 +1,19 @@
-use cpu::Cpu;
-use cpu::*;
+use std::io;
+use std::path::Path;
 
-mod cpu;
-mod bus;
-mod memory;
+use nes_rs::cartridge::{Battery, Cartridge, FileStorage};
+use nes_rs::cpu::*;
+use nes_rs::debugger::Debugger;
+use nes_rs::gdb;
 
 fn main() {
+	let args: Vec<String> = std::env::args().collect();
+	match args.get(1).map(String::as_str) {
+		Some("debug") => return debug(args.get(2), args.get(3..).unwrap_or_default()),
+		Some("gdb") => return gdb(args.get(2), args.get(3)),
+		_ => {}
+	}
+
 	let mut cpu = Cpu::new();
 	cpu.interpret(vec![
 		LDA1.code,
@@ -15,4 +23,102 @@ fn main() {
 		0x00,			// Break
 	]);
 	println!("{}", cpu.register_x); // Print the value stored in register X (2)
-}
\ No newline at end of file
+}
+
+// Where Cpu::load puts a raw program, and how much of it fits before the end of RAM.
+const PROGRAM_START: u16 = 0x0600;
+const PROGRAM_SPACE: usize = 0x0800 - PROGRAM_START as usize;
+
+// The debugger and gdb don't run whole frames, so the save is only written on exit.
+const AUTOSAVE_FRAMES: u32 = 60;
+
+// Loads an iNES file as a cartridge and starts it at its reset vector, or anything
+// else as a raw program at $0600, the way Cpu::load does. A cartridge with a battery
+// gets its RAM from the .sav file next to it.
+fn load(path: Option<&String>, usage: &str) -> (Cpu, Option<Battery<FileStorage>>) {
+	let program = match path.map(std::fs::read) {
+		Some(Ok(program)) => program,
+		Some(Err(err)) => {
+			eprintln!("cannot read {}: {}", path.unwrap(), err);
+			std::process::exit(1);
+		}
+		None => {
+			eprintln!("usage: {}", usage);
+			std::process::exit(1);
+		}
+	};
+
+	let mut cpu = Cpu::new();
+	if program.starts_with(b"NES\x1A") {
+		let mut cartridge = match Cartridge::from_ines(&program) {
+			Ok(cartridge) => cartridge,
+			Err(err) => {
+				eprintln!("cannot load {}: {}", path.unwrap(), err);
+				std::process::exit(1);
+			}
+		};
+		let mut battery = None;
+		if cartridge.battery {
+			let storage = FileStorage::beside(Path::new(path.unwrap()));
+			match Battery::attach(&mut cartridge, storage, AUTOSAVE_FRAMES) {
+				Ok(attached) => battery = Some(attached),
+				Err(err) => {
+					eprintln!("cannot load the save for {}: {}", path.unwrap(), err);
+					std::process::exit(1);
+				}
+			}
+		}
+		cpu.bus.insert(cartridge);
+		cpu.reset_from_vector();
+		return (cpu, battery);
+	}
+
+	if program.len() > PROGRAM_SPACE {
+		eprintln!("{} is {} bytes, a raw program can be at most {} bytes", path.unwrap(), program.len(), PROGRAM_SPACE);
+		std::process::exit(1);
+	}
+	cpu.load(program);
+	cpu.reset();
+	(cpu, None)
+}
+
+// Writes the cartridge's battery-backed RAM out when the session is over.
+fn save(battery: Option<Battery<FileStorage>>, cpu: &Cpu) {
+	let (Some(mut battery), Some(cartridge)) = (battery, &cpu.bus.cartridge) else {
+		return;
+	};
+	if let Err(err) = battery.flush(cartridge) {
+		eprintln!("cannot write {}: {}", battery.storage.path.display(), err);
+		std::process::exit(1);
+	}
+}
+
+// Hands the program to the interactive debugger, with labels from any symbol
+// files given after it.
+fn debug(path: Option<&String>, symbol_files: &[String]) {
+	let (cpu, battery) = load(path, "nes-rs debug <program> [symbol files...]");
+	let mut debugger = Debugger::new(cpu);
+	for file in symbol_files {
+		if let Err(err) = debugger.symbols.load(Path::new(file)) {
+			eprintln!("cannot load {}: {}", file, err);
+			std::process::exit(1);
+		}
+	}
+	debugger.print_registers(&mut io::stdout()).unwrap();
+	debugger.repl(io::stdin().lock(), &mut io::stdout()).unwrap();
+	save(battery, &debugger.cpu);
+}
+
+// Waits for a GDB client on localhost, port 6502 unless told otherwise.
+fn gdb(path: Option<&String>, port: Option<&String>) {
+	let (cpu, battery) = load(path, "nes-rs gdb <program> [port]");
+	let port = port.map_or("6502", String::as_str);
+	println!("waiting for gdb on 127.0.0.1:{}", port);
+	match gdb::serve(format!("127.0.0.1:{}", port), cpu) {
+		Ok(cpu) => save(battery, &cpu),
+		Err(err) => {
+			eprintln!("gdb session failed: {}", err);
+			std::process::exit(1);
+		}
+	}
+}
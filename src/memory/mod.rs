@@ -1,11 +1,17 @@
+// Reads and writes as the CPU makes them, with whatever side effects they have on
+// the hardware, like a controller shifting out the next button. Debuggers and
+// other tools that only look use peek and poke instead, which leave everything
+// but the memory itself alone.
 pub trait Memory {
-	fn read(&self, addr: u16) -> u8;
+	fn read(&mut self, addr: u16) -> u8;
 	fn write(&mut self, addr: u16, data: u8);
-	
-	fn read_u16(&self, pos: u16) -> u16 {
+	fn peek(&self, addr: u16) -> u8;
+	fn poke(&mut self, addr: u16, data: u8);
+
+	fn read_u16(&mut self, pos: u16) -> u16 {
 		let lo = self.read(pos) as u16;
 		let hi = self.read(pos + 1) as u16;
-		(hi << 8) | (lo as u16)
+		(hi << 8) | lo
 	}
 
 	fn write_u16(&mut self, pos: u16, data: u16) {
@@ -14,4 +20,85 @@ pub trait Memory {
 		self.write(pos, lo);
 		self.write(pos + 1, hi);
 	}
-}
\ No newline at end of file
+
+	fn peek_u16(&self, pos: u16) -> u16 {
+		let lo = self.peek(pos) as u16;
+		let hi = self.peek(pos.wrapping_add(1)) as u16;
+		(hi << 8) | lo
+	}
+
+	fn poke_u16(&mut self, pos: u16, data: u16) {
+		self.poke(pos, data as u8);
+		self.poke(pos.wrapping_add(1), (data >> 8) as u8);
+	}
+}
+
+// The streams RamInit::fill is called with for each RAM in the console.
+pub const STREAM_RAM: u64 = 0;
+pub const STREAM_PRG_RAM: u64 = 1;
+pub const STREAM_OAM: u64 = 2;
+pub const STREAM_PALETTE: u64 = 3;
+
+// What RAM holds when the console is switched on. Real RAM comes up with whatever
+// its cells settle to, which differs between consoles, and some games end up
+// depending on it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RamInit {
+	#[default]
+	Zeros,
+	Ones,
+	// Four bytes of $00 then four of $FF, what FCEUX uses.
+	Pattern,
+	// The same seed gives the same contents every time.
+	Random(u64),
+}
+
+impl RamInit {
+	// Every RAM filled at power on passes its own stream, so seeded random contents
+	// differ between them.
+	pub fn fill(&self, ram: &mut [u8], stream: u64) {
+		match *self {
+			RamInit::Zeros => ram.fill(0x00),
+			RamInit::Ones => ram.fill(0xFF),
+			RamInit::Pattern => {
+				for (i, byte) in ram.iter_mut().enumerate() {
+					*byte = if i & 4 != 0 { 0xFF } else { 0x00 };
+				}
+			}
+			RamInit::Random(seed) => {
+				// SplitMix64, stepped once per eight bytes.
+				let mut state = seed ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03);
+				for chunk in ram.chunks_mut(8) {
+					state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+					let mut z = state;
+					z = (z ^ z >> 30).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+					z = (z ^ z >> 27).wrapping_mul(0x94D0_49BB_1331_11EB);
+					z ^= z >> 31;
+					chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_ram_init() {
+		let mut ram = [0x55; 12];
+		RamInit::Pattern.fill(&mut ram, 0);
+		assert_eq!(ram, [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+		RamInit::Ones.fill(&mut ram, 0);
+		assert_eq!(ram, [0xFF; 12]);
+
+		let (mut a, mut b, mut c) = ([0; 64], [0; 64], [0; 64]);
+		RamInit::Random(1).fill(&mut a, 0);
+		RamInit::Random(1).fill(&mut b, 0);
+		RamInit::Random(1).fill(&mut c, 1);
+		assert_eq!(a, b);
+		assert_ne!(a, c);
+		assert_ne!(a, [0; 64]);
+	}
+}
@@ -0,0 +1,361 @@
+use std::fmt;
+
+use super::cpu::{Cpu, Memory};
+use super::region::Region;
+
+// Frame commands, as the bit field in the first column of an FM2 input line.
+pub const COMMAND_RESET: u8 = 0b0000_0001;
+pub const COMMAND_POWER: u8 = 0b0000_0010;
+
+// FM2 spells the gamepad buttons out from the most significant bit down.
+const PAD_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MovieError {
+	Syntax { line: usize, text: String },
+	Unsupported { line: usize, feature: String },
+}
+
+impl fmt::Display for MovieError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MovieError::Syntax { line, text } => write!(f, "line {}: cannot parse \"{}\"", line, text),
+			MovieError::Unsupported { line, feature } => write!(f, "line {}: {} is not supported", line, feature),
+		}
+	}
+}
+
+impl std::error::Error for MovieError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FrameInput {
+	pub commands: u8,
+	// Button bits for the two controller ports, see the joypad module.
+	pub pads: [u8; 2],
+}
+
+// An input movie in FCEUX's FM2 text format. Header fields this emulator doesn't
+// use are kept as they were so a parsed movie writes back out unchanged.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Movie {
+	pub header: Vec<(String, String)>,
+	pub comments: Vec<String>,
+	pub frames: Vec<FrameInput>,
+}
+
+impl Movie {
+	pub fn new() -> Self {
+		Movie {
+			header: vec![
+				("version".to_string(), "3".to_string()),
+				("emuVersion".to_string(), "22020".to_string()),
+				("rerecordCount".to_string(), "0".to_string()),
+				("palFlag".to_string(), "0".to_string()),
+				("port0".to_string(), "1".to_string()),
+				("port1".to_string(), "1".to_string()),
+				("port2".to_string(), "0".to_string()),
+				("FDS".to_string(), "0".to_string()),
+				("NewPPU".to_string(), "0".to_string()),
+			],
+			comments: Vec::new(),
+			frames: Vec::new(),
+		}
+	}
+
+	pub fn field(&self, key: &str) -> Option<&str> {
+		self.header.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+	}
+
+	pub fn set_field(&mut self, key: &str, value: &str) {
+		match self.header.iter_mut().find(|(name, _)| name == key) {
+			Some(field) => field.1 = value.to_string(),
+			None => self.header.push((key.to_string(), value.to_string())),
+		}
+	}
+
+	// FM2 only tells NTSC and PAL apart, FCEUX records Dendy movies as NTSC.
+	pub fn region(&self) -> Region {
+		match self.field("palFlag") {
+			Some("1") => Region::Pal,
+			_ => Region::Ntsc,
+		}
+	}
+
+	pub fn set_region(&mut self, region: Region) {
+		self.set_field("palFlag", if region == Region::Pal { "1" } else { "0" });
+	}
+
+	pub fn parse(text: &str) -> Result<Movie, MovieError> {
+		let mut movie = Movie { header: Vec::new(), comments: Vec::new(), frames: Vec::new() };
+		for (number, line) in text.lines().enumerate() {
+			let number = number + 1;
+			let line = line.trim_end_matches('\r');
+			if line.is_empty() {
+				continue;
+			}
+			if line.starts_with('|') {
+				movie.frames.push(parse_frame(line, number)?);
+				continue;
+			}
+
+			let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+			let unsupported = |feature: &str| Err(MovieError::Unsupported { line: number, feature: feature.to_string() });
+			match (key, value) {
+				("comment", _) => {
+					movie.comments.push(value.to_string());
+					continue;
+				}
+				("binary", "1") => return unsupported("binary input"),
+				("fourscore", "1") => return unsupported("the Four Score"),
+				("port0" | "port1", "2") => return unsupported("the Zapper"),
+				("port2", value) if value != "0" => return unsupported("expansion port devices"),
+				_ => {}
+			}
+			movie.header.push((key.to_string(), value.to_string()));
+		}
+		Ok(movie)
+	}
+
+	pub fn to_fm2(&self) -> String {
+		let mut text = String::new();
+		for (key, value) in self.header.iter() {
+			text.push_str(&format!("{} {}\n", key, value));
+		}
+		for comment in self.comments.iter() {
+			text.push_str(&format!("comment {}\n", comment));
+		}
+		for frame in self.frames.iter() {
+			text.push_str(&format!("|{}|{}|{}||\n", frame.commands, pad_string(frame.pads[0]), pad_string(frame.pads[1])));
+		}
+		text
+	}
+
+	// Appends the buttons currently held on both controllers as the next frame.
+	pub fn record(&mut self, cpu: &Cpu, commands: u8) {
+		self.frames.push(FrameInput {
+			commands,
+			pads: [cpu.bus.joypad1.buttons, cpu.bus.joypad2.buttons],
+		});
+	}
+
+	// Carries out the commands of a frame and sets the controllers for it. Returns
+	// false past the end of the movie.
+	pub fn apply(&self, frame: usize, cpu: &mut Cpu) -> bool {
+		let input = match self.frames.get(frame) {
+			Some(input) => input,
+			None => return false,
+		};
+		if input.commands & COMMAND_POWER != 0 {
+			cpu.bus.power_on();
+			cpu.reset_from_vector();
+		} else if input.commands & COMMAND_RESET != 0 {
+			cpu.reset_from_vector();
+		}
+		cpu.bus.joypad1.buttons = input.pads[0];
+		cpu.bus.joypad2.buttons = input.pads[1];
+		true
+	}
+
+	// Plays the whole movie back, with the host running one frame per input line.
+	pub fn play<F: FnMut(&mut Cpu)>(&self, cpu: &mut Cpu, mut run_frame: F) {
+		for frame in 0..self.frames.len() {
+			self.apply(frame, cpu);
+			run_frame(cpu);
+		}
+	}
+}
+
+impl Default for Movie {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// FNV-1a over the 2KB of internal RAM. Stable across platforms and runs, so it can
+// be checked into a test as the expected end state of a movie.
+pub fn ram_hash(cpu: &Cpu) -> u64 {
+	(0..0x0800u16).fold(0xcbf2_9ce4_8422_2325, |hash, addr| {
+		(hash ^ cpu.peek(addr) as u64).wrapping_mul(0x0000_0100_0000_01b3)
+	})
+}
+
+fn pad_string(buttons: u8) -> String {
+	PAD_BUTTONS
+		.iter()
+		.enumerate()
+		.map(|(i, &name)| if buttons & 0x80 >> i != 0 { name as char } else { '.' })
+		.collect()
+}
+
+// Any character other than a space or a dot counts as a pressed button.
+fn parse_pad(text: &str) -> Option<u8> {
+	match text.len() {
+		0 => Some(0),
+		8 => Some(text.bytes().enumerate().fold(0, |buttons, (i, c)| match c {
+			b'.' | b' ' => buttons,
+			_ => buttons | 0x80 >> i,
+		})),
+		_ => None,
+	}
+}
+
+fn parse_frame(line: &str, number: usize) -> Result<FrameInput, MovieError> {
+	let syntax = || MovieError::Syntax { line: number, text: line.to_string() };
+	let fields: Vec<&str> = line.split('|').collect();
+	if fields.len() < 4 {
+		return Err(syntax());
+	}
+	let commands = fields[1].trim().parse().map_err(|_| syntax())?;
+	let pad = |i: usize| fields.get(i).and_then(|field| parse_pad(field)).ok_or_else(syntax);
+	Ok(FrameInput { commands, pads: [pad(2)?, pad(3)?] })
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::assembler::Assembler;
+	use crate::cartridge::{Cartridge, Mirroring};
+	use crate::cpu::CpuVariant;
+	use crate::joypad::*;
+
+	const FM2: &str = "version 3
+emuVersion 22020
+rerecordCount 7
+palFlag 0
+romFilename test
+romChecksum base64:AAAAAAAAAAAAAAAAAAAAAA==
+guid 00000000-0000-0000-0000-000000000000
+fourscore 0
+microphone 0
+port0 1
+port1 1
+port2 0
+FDS 0
+NewPPU 0
+comment author nobody
+|2|........|........||
+|0|.......A|........||
+|0|R..U..B.|..D.....||
+|1|........|.L......||
+";
+
+	#[test]
+	fn test_parse_and_write() {
+		let movie = Movie::parse(FM2).unwrap();
+		assert_eq!(movie.field("rerecordCount"), Some("7"));
+		assert_eq!(movie.comments, vec!["author nobody"]);
+		assert_eq!(movie.frames, vec![
+			FrameInput { commands: COMMAND_POWER, pads: [0, 0] },
+			FrameInput { commands: 0, pads: [BUTTON_A, 0] },
+			FrameInput { commands: 0, pads: [BUTTON_RIGHT | BUTTON_UP | BUTTON_B, BUTTON_DOWN] },
+			FrameInput { commands: COMMAND_RESET, pads: [0, BUTTON_LEFT] },
+		]);
+		assert_eq!(movie.to_fm2(), FM2);
+
+		let mut movie = movie;
+		assert_eq!(movie.region(), Region::Ntsc);
+		movie.set_region(Region::Pal);
+		assert_eq!(movie.field("palFlag"), Some("1"));
+		assert_eq!(movie.region(), Region::Pal);
+	}
+
+	#[test]
+	fn test_errors() {
+		assert_eq!(Movie::parse("binary 1"), Err(MovieError::Unsupported { line: 1, feature: "binary input".to_string() }));
+		assert_eq!(Movie::parse("port0 2").unwrap_err().to_string(), "line 1: the Zapper is not supported");
+		assert_eq!(Movie::parse("version 3\n|x|........|........||"), Err(MovieError::Syntax {
+			line: 2,
+			text: "|x|........|........||".to_string(),
+		}));
+		assert!(Movie::parse("|0|...|........||").is_err());
+	}
+
+	// Reads the first controller each frame and sums up the buttons pressed.
+	const PROGRAM: &[&str] = &[
+		"frame:  lda #$01",
+		"        sta $4016",
+		"        lda #$00",
+		"        sta $4016",
+		"        ldx #$08",
+		"read:   lda $4016",
+		"        lsr a",
+		"        rol $10",
+		"        dex",
+		"        bne read",
+		"        lda $10",
+		"        clc",
+		"        adc $11",
+		"        sta $11",
+		"        inc $12",
+		"        jmp frame",
+	];
+
+	fn machine() -> Cpu {
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assembler::assemble(&PROGRAM.join("\n")).unwrap().bytes);
+		cpu.reset();
+		cpu
+	}
+
+	fn run_frame(cpu: &mut Cpu) {
+		for _ in 0..56 {
+			cpu.step().unwrap();
+		}
+	}
+
+	#[test]
+	fn test_joypad_reads() {
+		let mut cpu = machine();
+		cpu.bus.joypad1.buttons = BUTTON_A | BUTTON_START | BUTTON_RIGHT;
+		run_frame(&mut cpu);
+		// The buttons come out A first, so rotating them in reverses the bit order.
+		assert_eq!(cpu.read(0x10), 0b1001_0001);
+		assert_eq!(cpu.read(0x12), 1);
+	}
+
+	#[test]
+	fn test_record_and_replay() {
+		let inputs = [0, BUTTON_A, BUTTON_B | BUTTON_UP, BUTTON_A, 0, BUTTON_LEFT, BUTTON_SELECT];
+		let mut cpu = machine();
+		let mut movie = Movie::new();
+		for &buttons in inputs.iter().cycle().take(100) {
+			cpu.bus.joypad1.buttons = buttons;
+			movie.record(&cpu, 0);
+			run_frame(&mut cpu);
+		}
+		let expected = ram_hash(&cpu);
+		assert_ne!(expected, ram_hash(&machine()));
+
+		let movie = Movie::parse(&movie.to_fm2()).unwrap();
+		let mut replay = machine();
+		movie.play(&mut replay, run_frame);
+		assert_eq!(ram_hash(&replay), expected);
+		assert_eq!(replay.save_state(), cpu.save_state());
+	}
+
+	#[test]
+	fn test_reset_uses_vector() {
+		let assembler = Assembler { origin: 0xC000, ..Assembler::new(CpuVariant::Ricoh2A03) };
+		let mut prg_rom = assembler.assemble(&PROGRAM.join("\n")).unwrap().bytes;
+		prg_rom.resize(0x4000, 0);
+		prg_rom[0x3FFC..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0]);
+		let mut cpu = Cpu::new();
+		cpu.bus.insert(Cartridge {
+			prg_rom,
+			chr_rom: Vec::new(),
+			prg_ram: Vec::new(),
+			mapper: 0,
+			mirroring: Mirroring::Horizontal,
+			battery: false,
+			region: None,
+		});
+		cpu.reset_from_vector();
+
+		// A frame runs one pass of the loop and five instructions into the next, so
+		// the reset on the last frame leaves PC five instructions in again.
+		let movie = Movie::parse("|0|........|........||\n|0|.......A|........||\n|1|........|........||\n").unwrap();
+		movie.play(&mut cpu, run_frame);
+		assert_eq!(cpu.program_counter, 0xC00C);
+		assert_eq!(cpu.peek(0x12), 3);
+	}
+}
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use super::cpu::{Cpu, CpuError, Memory, BRK, JSR, RTI, RTS};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameKind {
+	Subroutine,
+	Interrupt,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct StackFrame {
+	entry: u16,
+	kind: FrameKind,
+	// The stack pointer before the call pushed anything. Once it's back at or above
+	// this the call has returned.
+	caller_stack: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct AddressStats {
+	pub cycles: u64,
+	pub count: u64,
+}
+
+// Self cycles are spent in the subroutine itself, total cycles include everything
+// it calls, interrupts that happen to arrive meanwhile included.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FunctionStats {
+	pub calls: u64,
+	pub self_cycles: u64,
+	pub total_cycles: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameStats {
+	pub cycles: u64,
+	// Cycles outside the idle loop, which is what has to fit in the frame.
+	pub busy: u64,
+}
+
+// Counts where the cycles go while the host runs the CPU through it. Calls are
+// followed by watching for JSR, RTS, RTI and interrupt entry, so the call stack
+// is known at every instruction.
+pub struct Profiler {
+	pub budget: u64,
+	idle: HashSet<u16>,
+	stack: Vec<StackFrame>,
+	addresses: BTreeMap<u16, AddressStats>,
+	functions: BTreeMap<u16, FunctionStats>,
+	// Cycles per call stack, from the outermost entry point in.
+	stacks: HashMap<Vec<u16>, u64>,
+	frames: Vec<FrameStats>,
+	current: FrameStats,
+}
+
+impl Profiler {
+	// Whatever is running when profiling starts becomes the root of the call graph.
+	// The budget is a frame's worth of cycles in the bus's region, rounded up.
+	pub fn new(cpu: &Cpu) -> Self {
+		let mut profiler = Profiler {
+			budget: cpu.bus.region.timing().frame_cycles().ceil() as u64,
+			idle: HashSet::new(),
+			stack: Vec::new(),
+			addresses: BTreeMap::new(),
+			functions: BTreeMap::new(),
+			stacks: HashMap::new(),
+			frames: Vec::new(),
+			current: FrameStats { cycles: 0, busy: 0 },
+		};
+		profiler.enter(cpu.program_counter, FrameKind::Subroutine, cpu.stack_pointer);
+		profiler
+	}
+
+	// Marks an instruction of the loop the game spins in while waiting for the next
+	// NMI. Cycles spent there don't count against the frame budget.
+	pub fn add_idle(&mut self, address: u16) {
+		self.idle.insert(address);
+	}
+
+	pub fn step(&mut self, cpu: &mut Cpu) -> Result<(), CpuError> {
+		let address = cpu.program_counter;
+		let opcode = cpu.peek(address);
+		let stack = cpu.stack_pointer;
+		let cycles = cpu.cycles;
+		let result = cpu.step();
+		self.account(address, cpu.cycles - cycles, cpu.waiting);
+
+		if result.is_ok() && !cpu.jammed {
+			match opcode {
+				op if op == JSR.code => self.enter(cpu.program_counter, FrameKind::Subroutine, stack),
+				op if op == BRK.code => self.enter(cpu.program_counter, FrameKind::Interrupt, stack),
+				op if op == RTS.code || op == RTI.code => self.unwind(cpu.stack_pointer),
+				_ => {}
+			}
+		}
+		result
+	}
+
+	pub fn nmi(&mut self, cpu: &mut Cpu) {
+		self.interrupt(cpu, Cpu::nmi);
+	}
+
+	pub fn irq(&mut self, cpu: &mut Cpu) {
+		self.interrupt(cpu, Cpu::irq);
+	}
+
+	fn interrupt(&mut self, cpu: &mut Cpu, raise: fn(&mut Cpu)) {
+		let (address, stack, cycles) = (cpu.program_counter, cpu.stack_pointer, cpu.cycles);
+		raise(cpu);
+		if cpu.stack_pointer != stack {
+			// The entry sequence counts towards the handler.
+			self.enter(cpu.program_counter, FrameKind::Interrupt, stack);
+			self.account(cpu.program_counter, cpu.cycles - cycles, false);
+		} else {
+			self.account(address, cpu.cycles - cycles, false);
+		}
+	}
+
+	// Call at the end of every frame, e.g. right before raising the NMI.
+	pub fn end_frame(&mut self) {
+		self.frames.push(self.current);
+		self.current = FrameStats { cycles: 0, busy: 0 };
+	}
+
+	fn enter(&mut self, entry: u16, kind: FrameKind, caller_stack: u8) {
+		self.stack.push(StackFrame { entry, kind, caller_stack });
+		self.functions.entry(entry).or_default().calls += 1;
+	}
+
+	// Returns pop every frame the stack pointer has moved back past. Pushing an
+	// address and returning to it, the RTS trick, doesn't get that far and counts as
+	// a jump within the subroutine. The root frame never goes away.
+	fn unwind(&mut self, stack_pointer: u8) {
+		while self.stack.len() > 1 && self.stack.last().is_some_and(|frame| frame.caller_stack <= stack_pointer) {
+			self.stack.pop();
+		}
+	}
+
+	fn account(&mut self, address: u16, cycles: u64, waiting: bool) {
+		if cycles == 0 {
+			return;
+		}
+		let stats = self.addresses.entry(address).or_default();
+		stats.cycles += cycles;
+		stats.count += 1;
+
+		let path: Vec<u16> = self.stack.iter().map(|frame| frame.entry).collect();
+		if let Some(&top) = path.last() {
+			self.functions.entry(top).or_default().self_cycles += cycles;
+		}
+		// Recursion would count the same cycles twice otherwise.
+		let mut seen = HashSet::new();
+		for &entry in path.iter().filter(|&&entry| seen.insert(entry)) {
+			self.functions.entry(entry).or_default().total_cycles += cycles;
+		}
+		*self.stacks.entry(path).or_default() += cycles;
+
+		self.current.cycles += cycles;
+		if !waiting && !self.idle.contains(&address) {
+			self.current.busy += cycles;
+		}
+	}
+
+	// Entry points of the subroutines and interrupt handlers currently running, from
+	// the outermost in.
+	pub fn call_stack(&self) -> Vec<(u16, FrameKind)> {
+		self.stack.iter().map(|frame| (frame.entry, frame.kind)).collect()
+	}
+
+	pub fn addresses(&self) -> &BTreeMap<u16, AddressStats> {
+		&self.addresses
+	}
+
+	pub fn functions(&self) -> &BTreeMap<u16, FunctionStats> {
+		&self.functions
+	}
+
+	pub fn frames(&self) -> &[FrameStats] {
+		&self.frames
+	}
+
+	// The addresses with the most cycles, busiest first.
+	pub fn hot_spots(&self, count: usize) -> Vec<(u16, AddressStats)> {
+		let mut spots: Vec<(u16, AddressStats)> = self.addresses.iter().map(|(&address, &stats)| (address, stats)).collect();
+		spots.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(&b.0)));
+		spots.truncate(count);
+		spots
+	}
+
+	// The folded stack format flamegraph.pl and inferno read, one line per call stack
+	// with the frames separated by semicolons and the cycles spent in it. The name
+	// function turns entry points into names, e.g. with SymbolTable::name.
+	pub fn folded_stacks<F: Fn(u16) -> String>(&self, name: F) -> String {
+		let mut lines: Vec<String> = self
+			.stacks
+			.iter()
+			.map(|(path, cycles)| {
+				let names: Vec<String> = path.iter().map(|&entry| name(entry)).collect();
+				format!("{} {}", names.join(";"), cycles)
+			})
+			.collect();
+		lines.sort();
+		lines.iter().map(|line| format!("{}\n", line)).collect()
+	}
+
+	// One line per finished frame with how much of the budget it used.
+	pub fn frame_report(&self) -> String {
+		let mut report = String::from("frame    busy  budget    used\n");
+		for (i, frame) in self.frames.iter().enumerate() {
+			let used = frame.busy as f64 * 100.0 / self.budget as f64;
+			let overrun = if frame.busy > self.budget { "  overrun" } else { "" };
+			writeln!(report, "{:>5} {:>7} {:>7} {:>6.1}%{}", i, frame.busy, self.budget, used, overrun).unwrap();
+		}
+		report
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cartridge::{Cartridge, Mirroring};
+
+	// The main loop spins at "idle" until the NMI handler bumps the frame counter.
+	const GAME: &[&str] = &[
+		"main:   jsr update",
+		"idle:   lda $10",
+		"        beq idle",
+		"        lda #$00",
+		"        sta $10",
+		"        jmp main",
+		"update: ldx #$20",
+		"loop:   jsr work",
+		"        dex",
+		"        bne loop",
+		"        rts",
+		"work:   nop",
+		"        rts",
+		"nmi:    inc $10",
+		"        rti",
+	];
+
+	// Runs the game from a 16KB NROM cartridge with the vectors pointing into it.
+	fn machine() -> (Cpu, crate::assembler::Program) {
+		let mut assembler = crate::assembler::Assembler::new(crate::cpu::CpuVariant::Ricoh2A03);
+		assembler.origin = 0xC000;
+		let source = [GAME, &[".org $FFFA", ".word nmi, main, main"]].concat();
+		let program = assembler.assemble(&source.join("\n")).unwrap();
+		let mut prg_rom = vec![0; 0x4000];
+		prg_rom[..program.bytes.len()].copy_from_slice(&program.bytes);
+		let mut cpu = Cpu::new();
+		cpu.bus.insert(Cartridge {
+			prg_rom,
+			chr_rom: Vec::new(),
+			prg_ram: Vec::new(),
+			mapper: 0,
+			mirroring: Mirroring::Horizontal,
+			battery: false,
+			region: None,
+		});
+		cpu.reset();
+		cpu.program_counter = program.labels["main"];
+		(cpu, program)
+	}
+
+	#[test]
+	fn test_call_graph() {
+		let (mut cpu, program) = machine();
+		let mut profiler = Profiler::new(&cpu);
+		let label = |name: &str| program.labels[name];
+
+		// Stop in the middle of "work", then unwind through both returns.
+		while cpu.program_counter != label("work") {
+			profiler.step(&mut cpu).unwrap();
+		}
+		assert_eq!(profiler.call_stack(), vec![
+			(label("main"), FrameKind::Subroutine),
+			(label("update"), FrameKind::Subroutine),
+			(label("work"), FrameKind::Subroutine),
+		]);
+		while cpu.program_counter != label("idle") {
+			profiler.step(&mut cpu).unwrap();
+		}
+		assert_eq!(profiler.call_stack(), vec![(label("main"), FrameKind::Subroutine)]);
+
+		// NOP and RTS, the JSR counts towards the caller.
+		let work = profiler.functions()[&label("work")];
+		assert_eq!(work, FunctionStats { calls: 32, self_cycles: 32 * 8, total_cycles: 32 * 8 });
+		let update = profiler.functions()[&label("update")];
+		assert_eq!(update.calls, 1);
+		assert_eq!(update.total_cycles, update.self_cycles + work.total_cycles);
+		assert_eq!(profiler.hot_spots(1)[0], (label("loop"), AddressStats { cycles: 32 * 6, count: 32 }));
+
+		let name = |entry: u16| program.labels.iter().find(|(_, &address)| address == entry).unwrap().0.clone();
+		let folded = profiler.folded_stacks(name);
+		assert_eq!(folded.lines().count(), 3);
+		assert!(folded.starts_with("main 6\n"));
+		assert!(folded.ends_with(&format!("main;update;work {}\n", 32 * 8)));
+	}
+
+	#[test]
+	fn test_interrupts_and_frames() {
+		let (mut cpu, program) = machine();
+		let mut profiler = Profiler::new(&cpu);
+		assert_eq!(profiler.budget, 29781);
+		profiler.budget = 500;
+		profiler.add_idle(program.labels["idle"]);
+		profiler.add_idle(program.labels["idle"] + 2);
+
+		for _ in 0..2 {
+			let start = cpu.cycles;
+			while cpu.cycles - start < 1000 {
+				profiler.step(&mut cpu).unwrap();
+			}
+			profiler.end_frame();
+			profiler.nmi(&mut cpu);
+			assert_eq!(profiler.call_stack()[1], (program.labels["nmi"], FrameKind::Interrupt));
+		}
+		// Entry, INC and RTI take 18 cycles, then it is back to the idle loop.
+		profiler.step(&mut cpu).unwrap();
+		profiler.step(&mut cpu).unwrap();
+		assert_eq!(profiler.call_stack().len(), 1);
+		let nmi = profiler.functions()[&program.labels["nmi"]];
+		assert_eq!(nmi, FunctionStats { calls: 2, self_cycles: 2 * 18, total_cycles: 2 * 18 });
+
+		let frames = profiler.frames();
+		assert_eq!(frames.len(), 2);
+		assert!(frames.iter().all(|frame| frame.cycles >= 1000 && frame.busy < frame.cycles));
+		let report = profiler.frame_report();
+		assert!(report.starts_with("frame    busy  budget    used\n    0 "));
+		assert_eq!(report.matches("overrun").count(), 2);
+	}
+}
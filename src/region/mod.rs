@@ -0,0 +1,162 @@
+// The console models, which run from different master clocks and draw different
+// numbers of scanlines. Dendy is the Russian famiclone, which pairs a PAL frame
+// with NTSC-like CPU timing so NTSC games run at close to the right speed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Region {
+	#[default]
+	Ntsc,
+	Pal,
+	Dendy,
+}
+
+// Everything that depends on the region. Clock dividers are in master clock
+// cycles, the APU tables are in CPU cycles.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Timing {
+	pub master_clock: f64,
+	pub cpu_divider: u32,
+	pub ppu_divider: u32,
+	pub scanlines: u16,
+	// Idle lines after the picture, before vblank starts.
+	pub post_render_scanlines: u16,
+	pub vblank_scanlines: u16,
+	// NTSC skips a dot on the pre-render line of every other frame while rendering.
+	pub odd_frame_skip: bool,
+	// When the frame counter clocks the quarter frames of the four step sequence,
+	// and where the fifth step of the five step sequence falls.
+	pub frame_counter: [u32; 5],
+	pub noise_periods: [u16; 16],
+	pub dmc_periods: [u16; 16],
+}
+
+pub const DOTS_PER_SCANLINE: u16 = 341;
+
+const NTSC_NOISE: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+const PAL_NOISE: [u16; 16] = [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778];
+const NTSC_DMC: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+const PAL_DMC: [u16; 16] = [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50];
+
+const NTSC: Timing = Timing {
+	master_clock: 21_477_272.7,
+	cpu_divider: 12,
+	ppu_divider: 4,
+	scanlines: 262,
+	post_render_scanlines: 1,
+	vblank_scanlines: 20,
+	odd_frame_skip: true,
+	frame_counter: [7457, 14913, 22371, 29829, 37281],
+	noise_periods: NTSC_NOISE,
+	dmc_periods: NTSC_DMC,
+};
+
+const PAL: Timing = Timing {
+	master_clock: 26_601_712.5,
+	cpu_divider: 16,
+	ppu_divider: 5,
+	scanlines: 312,
+	post_render_scanlines: 1,
+	vblank_scanlines: 70,
+	odd_frame_skip: false,
+	frame_counter: [8313, 16627, 24939, 33253, 41565],
+	noise_periods: PAL_NOISE,
+	dmc_periods: PAL_DMC,
+};
+
+// The PAL clock and frame, but vblank as long as NTSC's and an NTSC APU.
+const DENDY: Timing = Timing {
+	master_clock: 26_601_712.5,
+	cpu_divider: 15,
+	ppu_divider: 5,
+	scanlines: 312,
+	post_render_scanlines: 51,
+	vblank_scanlines: 20,
+	odd_frame_skip: false,
+	frame_counter: NTSC.frame_counter,
+	noise_periods: NTSC_NOISE,
+	dmc_periods: NTSC_DMC,
+};
+
+impl Region {
+	pub fn timing(&self) -> &'static Timing {
+		match self {
+			Region::Ntsc => &NTSC,
+			Region::Pal => &PAL,
+			Region::Dendy => &DENDY,
+		}
+	}
+
+	// The CPU/PPU timing field of an NES 2.0 header. None for games that run on
+	// every region.
+	pub fn from_nes2(timing: u8) -> Option<Region> {
+		match timing & 0b11 {
+			0 => Some(Region::Ntsc),
+			1 => Some(Region::Pal),
+			3 => Some(Region::Dendy),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Region::Ntsc => "NTSC",
+			Region::Pal => "PAL",
+			Region::Dendy => "Dendy",
+		}
+	}
+}
+
+impl Timing {
+	pub fn cpu_clock(&self) -> f64 {
+		self.master_clock / self.cpu_divider as f64
+	}
+
+	// 3 on NTSC and Dendy, 3.2 on PAL.
+	pub fn dots_per_cpu_cycle(&self) -> f64 {
+		self.cpu_divider as f64 / self.ppu_divider as f64
+	}
+
+	// The first scanline of vblank, where the NMI fires, counting the pre-render line
+	// as the last one.
+	pub fn vblank_scanline(&self) -> u16 {
+		240 + self.post_render_scanlines
+	}
+
+	// The average over an odd and an even frame, with rendering on.
+	pub fn frame_cycles(&self) -> f64 {
+		let skipped = if self.odd_frame_skip { 0.5 } else { 0.0 };
+		(self.scanlines as f64 * DOTS_PER_SCANLINE as f64 - skipped) / self.dots_per_cpu_cycle()
+	}
+
+	pub fn frame_rate(&self) -> f64 {
+		self.cpu_clock() / self.frame_cycles()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_timing() {
+		let ntsc = Region::Ntsc.timing();
+		assert_eq!(ntsc.dots_per_cpu_cycle(), 3.0);
+		assert_eq!(ntsc.frame_cycles(), 29780.5);
+		assert_eq!(ntsc.vblank_scanline(), 241);
+		assert_eq!((ntsc.cpu_clock() / 1000.0).round(), 1790.0);
+		assert_eq!((ntsc.frame_rate() * 100.0).round(), 6010.0);
+
+		let pal = Region::Pal.timing();
+		assert_eq!(pal.dots_per_cpu_cycle(), 3.2);
+		assert_eq!(pal.frame_cycles(), 33247.5);
+		assert_eq!((pal.frame_rate() * 100.0).round(), 5001.0);
+
+		// Dendy fits the same frame into more CPU cycles.
+		let dendy = Region::Dendy.timing();
+		assert_eq!(dendy.frame_cycles(), 35464.0);
+		assert_eq!(dendy.vblank_scanline(), 291);
+		assert_eq!(dendy.scanlines, dendy.vblank_scanline() + dendy.vblank_scanlines + 1);
+		assert_eq!(ntsc.scanlines, ntsc.vblank_scanline() + ntsc.vblank_scanlines + 1);
+		assert_eq!(pal.scanlines, pal.vblank_scanline() + pal.vblank_scanlines + 1);
+		assert_eq!(dendy.noise_periods, ntsc.noise_periods);
+	}
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+
+use super::cpu::{Cpu, StateError};
+
+// Keeps a save state every few frames so the host can step backwards in time.
+//
+// Only the newest snapshot is kept whole. Every older one is stored as the XOR of
+// itself with the next newer snapshot, run-length encoded. Consecutive states differ
+// in a handful of bytes, so those deltas are mostly zero runs and compress to almost
+// nothing. Walking back from the newest snapshot undoes one delta at a time, and the
+// oldest snapshots can be dropped whenever the memory budget runs out.
+pub struct Rewind {
+	interval: u64,
+	budget: usize,
+	frame: u64,
+	entries: VecDeque<Entry>,
+	newest: Vec<u8>,
+}
+
+struct Entry {
+	frame: u64,
+	// Empty for the newest entry, whose state is kept in Rewind::newest.
+	delta: Vec<u8>,
+}
+
+impl Rewind {
+	// Starts recording at frame zero with the current state of the machine.
+	pub fn new(cpu: &Cpu, interval: u32, budget: usize) -> Self {
+		let mut rewind = Rewind {
+			interval: interval.max(1) as u64,
+			budget,
+			frame: 0,
+			entries: VecDeque::new(),
+			newest: Vec::new(),
+		};
+		rewind.snapshot(cpu);
+		rewind
+	}
+
+	pub fn frame(&self) -> u64 {
+		self.frame
+	}
+
+	// The oldest frame that can still be restored.
+	pub fn oldest_frame(&self) -> u64 {
+		self.entries.front().map_or(self.frame, |entry| entry.frame)
+	}
+
+	pub fn snapshots(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn memory_used(&self) -> usize {
+		self.newest.len() + self.entries.iter().map(|entry| entry.delta.len()).sum::<usize>()
+	}
+
+	// Call once after every emulated frame.
+	pub fn record(&mut self, cpu: &Cpu) {
+		self.frame += 1;
+		if self.frame.is_multiple_of(self.interval) {
+			self.snapshot(cpu);
+		}
+	}
+
+	fn snapshot(&mut self, cpu: &Cpu) {
+		let state = cpu.save_state();
+		if let Some(previous) = self.entries.back_mut() {
+			previous.delta = rle_encode(&xor_delta(&self.newest, &state));
+		}
+		self.entries.push_back(Entry { frame: self.frame, delta: Vec::new() });
+		self.newest = state;
+
+		while self.memory_used() > self.budget && self.entries.len() > 1 {
+			self.entries.pop_front();
+		}
+	}
+
+	// Goes back a single frame. See rewind_to.
+	pub fn step_back<F: FnMut(&mut Cpu)>(&mut self, cpu: &mut Cpu, run_frame: F) -> Result<bool, StateError> {
+		match self.frame {
+			0 => Ok(false),
+			frame => self.rewind_to(cpu, frame - 1, run_frame),
+		}
+	}
+
+	// Restores the closest snapshot at or before the frame, then has the host run the
+	// frames in between. Snapshots after the frame are discarded, since the machine is
+	// about to take a new path from there. Returns false when the frame is no longer
+	// in the buffer, leaving the machine untouched.
+	pub fn rewind_to<F: FnMut(&mut Cpu)>(&mut self, cpu: &mut Cpu, frame: u64, mut run_frame: F) -> Result<bool, StateError> {
+		if frame > self.frame || frame < self.oldest_frame() {
+			return Ok(false);
+		}
+
+		while self.entries.back().is_some_and(|entry| entry.frame > frame) {
+			self.entries.pop_back();
+			let previous = self.entries.back_mut().unwrap();
+			self.newest = apply_delta(&self.newest, &rle_decode(&previous.delta));
+			previous.delta = Vec::new();
+		}
+
+		cpu.load_state(&self.newest)?;
+		let start = self.entries.back().unwrap().frame;
+		for _ in start..frame {
+			run_frame(cpu);
+		}
+		self.frame = frame;
+		Ok(true)
+	}
+}
+
+// XORs two states, keeping the length of the second. Applying the result to either
+// state gives back the other one, as long as its length is stored alongside.
+fn xor_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+	let mut delta = Vec::with_capacity(to.len() + 4);
+	delta.extend_from_slice(&(from.len() as u32).to_le_bytes());
+	let len = from.len().max(to.len());
+	for i in 0..len {
+		delta.push(from.get(i).copied().unwrap_or(0) ^ to.get(i).copied().unwrap_or(0));
+	}
+	delta
+}
+
+// Undoes xor_delta given the state it was taken against.
+fn apply_delta(state: &[u8], delta: &[u8]) -> Vec<u8> {
+	let len = u32::from_le_bytes(delta[..4].try_into().unwrap()) as usize;
+	(0..len).map(|i| state.get(i).copied().unwrap_or(0) ^ delta.get(i + 4).copied().unwrap_or(0)).collect()
+}
+
+// Runs of zeroes and literal bytes alternate, each prefixed with its length as a varint.
+fn rle_encode(data: &[u8]) -> Vec<u8> {
+	let mut encoded = Vec::new();
+	let mut i = 0;
+	while i < data.len() {
+		let zeroes = data[i..].iter().take_while(|&&byte| byte == 0).count();
+		i += zeroes;
+		let literals = data[i..].windows(2).position(|pair| pair == [0, 0]).unwrap_or(data.len() - i);
+		write_varint(&mut encoded, zeroes);
+		write_varint(&mut encoded, literals);
+		encoded.extend_from_slice(&data[i..i + literals]);
+		i += literals;
+	}
+	encoded
+}
+
+fn rle_decode(encoded: &[u8]) -> Vec<u8> {
+	let mut data = Vec::new();
+	let mut i = 0;
+	while i < encoded.len() {
+		let zeroes = read_varint(encoded, &mut i);
+		data.resize(data.len() + zeroes, 0);
+		let literals = read_varint(encoded, &mut i);
+		data.extend_from_slice(&encoded[i..i + literals]);
+		i += literals;
+	}
+	data
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+	while value >= 0x80 {
+		out.push(value as u8 | 0x80);
+		value >>= 7;
+	}
+	out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+	let mut value = 0;
+	let mut shift = 0;
+	loop {
+		let byte = data[*i];
+		*i += 1;
+		value |= ((byte & 0x7F) as usize) << shift;
+		if byte & 0x80 == 0 {
+			return value;
+		}
+		shift += 7;
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn machine() -> Cpu {
+		let mut cpu = Cpu::new();
+		cpu.load(crate::assemble!(
+			"loop:   inx",
+			"        txa",
+			"        sta $0200,x",
+			"        adc $10",
+			"        sta $10",
+			"        jmp loop",
+		));
+		cpu.reset();
+		cpu
+	}
+
+	fn run_frame(cpu: &mut Cpu) {
+		for _ in 0..37 {
+			cpu.step().unwrap();
+		}
+	}
+
+	#[test]
+	fn test_rle() {
+		let data = vec![0, 0, 0, 5, 0, 6, 0, 0, 0, 0, 7, 8];
+		let encoded = rle_encode(&data);
+		assert_eq!(encoded, vec![3, 3, 5, 0, 6, 4, 2, 7, 8]);
+		assert_eq!(rle_decode(&encoded), data);
+		assert_eq!(rle_decode(&rle_encode(&vec![0; 1000])), vec![0; 1000]);
+
+		let delta = xor_delta(&[1, 2, 3], &[1, 2, 3, 4]);
+		assert_eq!(apply_delta(&[1, 2, 3, 4], &delta), vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn test_step_back_frame_by_frame() {
+		let mut cpu = machine();
+		let mut rewind = Rewind::new(&cpu, 4, usize::MAX);
+		let mut states = vec![cpu.save_state()];
+		for _ in 0..30 {
+			run_frame(&mut cpu);
+			rewind.record(&cpu);
+			states.push(cpu.save_state());
+		}
+		assert_eq!(rewind.frame(), 30);
+		assert_eq!(rewind.snapshots(), 8);
+		assert!(rewind.memory_used() < 2 * states[0].len());
+
+		for frame in (0..30).rev() {
+			assert!(rewind.step_back(&mut cpu, run_frame).unwrap());
+			assert_eq!(rewind.frame(), frame);
+			assert_eq!(cpu.save_state(), states[frame as usize], "frame {}", frame);
+		}
+		assert!(!rewind.step_back(&mut cpu, run_frame).unwrap());
+	}
+
+	#[test]
+	fn test_record_after_rewind() {
+		let mut cpu = machine();
+		let mut rewind = Rewind::new(&cpu, 2, usize::MAX);
+		let mut states = vec![cpu.save_state()];
+		for _ in 0..10 {
+			run_frame(&mut cpu);
+			rewind.record(&cpu);
+			states.push(cpu.save_state());
+		}
+
+		assert!(rewind.rewind_to(&mut cpu, 5, run_frame).unwrap());
+		assert_eq!(rewind.snapshots(), 3);
+		for _ in 5..10 {
+			run_frame(&mut cpu);
+			rewind.record(&cpu);
+		}
+		assert_eq!(rewind.snapshots(), 6);
+		assert!(rewind.rewind_to(&mut cpu, 7, run_frame).unwrap());
+		assert_eq!(cpu.save_state(), states[7]);
+	}
+
+	#[test]
+	fn test_memory_budget() {
+		let mut cpu = machine();
+		let state_size = cpu.save_state().len();
+		let mut rewind = Rewind::new(&cpu, 1, state_size + 200);
+		for _ in 0..100 {
+			run_frame(&mut cpu);
+			rewind.record(&cpu);
+		}
+		assert!(rewind.memory_used() <= state_size + 200);
+		assert!(rewind.oldest_frame() > 0);
+		assert!(rewind.snapshots() > 1);
+
+		let oldest = rewind.oldest_frame();
+		let current = cpu.save_state();
+		assert!(!rewind.rewind_to(&mut cpu, oldest - 1, run_frame).unwrap());
+		assert_eq!(cpu.save_state(), current);
+		assert!(rewind.rewind_to(&mut cpu, oldest, run_frame).unwrap());
+	}
+}
@@ -0,0 +1,259 @@
+mod watch;
+
+use super::cpu::Cpu;
+use super::debugger::Comparison;
+
+pub use watch::*;
+
+const RAM_SIZE: usize = 0x0800;
+const PRG_RAM_START: u16 = 0x6000;
+const PRG_RAM_WINDOW: usize = 0x2000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataType {
+	U8,
+	I8,
+	// Words are little endian, like everything else on the 6502.
+	U16,
+	I16,
+}
+
+impl DataType {
+	pub fn size(&self) -> usize {
+		match self {
+			DataType::U8 | DataType::I8 => 1,
+			DataType::U16 | DataType::I16 => 2,
+		}
+	}
+
+	fn decode(&self, bytes: &[u8]) -> i32 {
+		match self {
+			DataType::U8 => bytes[0] as i32,
+			DataType::I8 => bytes[0] as i8 as i32,
+			DataType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+			DataType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+		}
+	}
+}
+
+// What a search compares the current value of each candidate with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operand {
+	// The value at the previous search, so Equal finds unchanged values, NotEqual
+	// changed ones and Greater and Less the ones that went up or down.
+	Previous,
+	Value(i32),
+	// The change since the previous search, to find a counter that went up by one.
+	Difference(i32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+	pub address: u16,
+	pub value: i32,
+	pub previous: i32,
+}
+
+// Narrows down where a game keeps something by repeatedly comparing memory with
+// an earlier snapshot of it. Covers the internal RAM and the cartridge's PRG-RAM.
+pub struct RamSearch {
+	pub data_type: DataType,
+	// Offsets into the snapshot, see address_of.
+	candidates: Vec<usize>,
+	previous: Vec<u8>,
+}
+
+impl RamSearch {
+	pub fn new(cpu: &Cpu, data_type: DataType) -> Self {
+		let mut search = RamSearch { data_type, candidates: Vec::new(), previous: Vec::new() };
+		search.reset(cpu);
+		search
+	}
+
+	// Starts over with every address as a candidate.
+	pub fn reset(&mut self, cpu: &Cpu) {
+		self.previous = snapshot(cpu);
+		let size = self.data_type.size();
+		// Words can't straddle the end of the internal RAM or the PRG-RAM.
+		self.candidates = (0..self.previous.len())
+			.filter(|&offset| {
+				let end = offset + size - 1;
+				end < self.previous.len() && (offset < RAM_SIZE) == (end < RAM_SIZE)
+			})
+			.collect();
+	}
+
+	pub fn len(&self) -> usize {
+		self.candidates.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.candidates.is_empty()
+	}
+
+	// Keeps the candidates for which "current <comparison> operand" holds and takes
+	// a new snapshot for the next search. Returns how many are left.
+	pub fn filter(&mut self, cpu: &Cpu, comparison: Comparison, operand: Operand) -> usize {
+		let current = snapshot(cpu);
+		if current.len() != self.previous.len() {
+			// The cartridge was swapped, none of the old offsets mean anything now.
+			self.reset(cpu);
+			return self.len();
+		}
+
+		let data_type = self.data_type;
+		let previous = &self.previous;
+		self.candidates.retain(|&offset| {
+			let value = data_type.decode(&current[offset..]);
+			let old = data_type.decode(&previous[offset..]);
+			match operand {
+				Operand::Previous => comparison.test(value, old),
+				Operand::Value(expected) => comparison.test(value, expected),
+				Operand::Difference(change) => comparison.test(value - old, change),
+			}
+		});
+		self.previous = current;
+		self.len()
+	}
+
+	// The candidates left, with their values now and at the last search.
+	pub fn results(&self, cpu: &Cpu) -> Vec<SearchResult> {
+		let current = snapshot(cpu);
+		self.candidates
+			.iter()
+			.filter(|&&offset| offset + self.data_type.size() <= current.len())
+			.map(|&offset| SearchResult {
+				address: address_of(offset),
+				value: self.data_type.decode(&current[offset..]),
+				previous: self.data_type.decode(&self.previous[offset..]),
+			})
+			.collect()
+	}
+}
+
+// Reads a byte of RAM or PRG-RAM without going through the bus, so neither the
+// cheats nor any side effects of reading get in the way. PRG-RAM smaller than the
+// window is mirrored across it, as the CPU sees it.
+pub fn peek(cpu: &Cpu, address: u16) -> Option<u8> {
+	match address {
+		0x0000..=0x1FFF => Some(cpu.bus.ram()[address as usize % RAM_SIZE]),
+		0x6000..=0x7FFF => {
+			let prg_ram = &cpu.bus.cartridge.as_ref()?.prg_ram;
+			if prg_ram.is_empty() {
+				return None;
+			}
+			Some(prg_ram[(address - PRG_RAM_START) as usize % prg_ram.len()])
+		}
+		_ => None,
+	}
+}
+
+// Internal RAM followed by as much PRG-RAM as shows up at $6000-$7FFF. Mirrors of
+// a smaller PRG-RAM are left out, results name the first copy.
+fn snapshot(cpu: &Cpu) -> Vec<u8> {
+	let mut data = cpu.bus.ram().to_vec();
+	if let Some(cart) = &cpu.bus.cartridge {
+		data.extend_from_slice(&cart.prg_ram[..cart.prg_ram.len().min(PRG_RAM_WINDOW)]);
+	}
+	data
+}
+
+fn address_of(offset: usize) -> u16 {
+	match offset {
+		0..RAM_SIZE => offset as u16,
+		_ => PRG_RAM_START + (offset - RAM_SIZE) as u16,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cartridge::{Cartridge, Mirroring};
+	use crate::cpu::Memory;
+
+	fn machine() -> Cpu {
+		let mut cpu = Cpu::new();
+		cpu.bus.insert(Cartridge {
+			prg_rom: vec![0; 0x8000],
+			chr_rom: Vec::new(),
+			prg_ram: vec![0; 0x2000],
+			mapper: 0,
+			mirroring: Mirroring::Horizontal,
+			battery: false,
+			region: None,
+		});
+		cpu
+	}
+
+	#[test]
+	fn test_find_counter() {
+		let mut cpu = machine();
+		let mut search = RamSearch::new(&cpu, DataType::U8);
+		assert_eq!(search.len(), 0x0800 + 0x2000);
+
+		// Lives at $0075 and a timer in PRG-RAM at $6100, with some noise elsewhere.
+		cpu.write(0x0075, 3);
+		cpu.write(0x6100, 100);
+		search.filter(&cpu, Comparison::NotEqual, Operand::Previous);
+		assert_eq!(search.len(), 2);
+
+		cpu.write(0x0075, 2);
+		cpu.write(0x6100, 99);
+		cpu.write(0x0300, 1);
+		search.filter(&cpu, Comparison::Less, Operand::Previous);
+		cpu.write(0x6100, 98);
+		assert_eq!(search.results(&cpu), vec![
+			SearchResult { address: 0x0075, value: 2, previous: 2 },
+			SearchResult { address: 0x6100, value: 98, previous: 99 },
+		]);
+
+		search.filter(&cpu, Comparison::Equal, Operand::Value(2));
+		assert_eq!(search.results(&cpu)[0].address, 0x0075);
+		assert_eq!(search.len(), 1);
+	}
+
+	#[test]
+	fn test_signed_words() {
+		let mut cpu = machine();
+		let mut search = RamSearch::new(&cpu, DataType::I16);
+		// Words can't start on the last byte of either area.
+		assert_eq!(search.len(), 0x07FF + 0x1FFF);
+
+		cpu.write_u16(0x0040, 0xFFFE);
+		search.filter(&cpu, Comparison::Less, Operand::Value(0));
+		assert_eq!(search.results(&cpu), vec![
+			SearchResult { address: 0x003F, value: -512, previous: -512 },
+			SearchResult { address: 0x0040, value: -2, previous: -2 },
+		]);
+
+		cpu.write_u16(0x0040, 0x0001);
+		search.filter(&cpu, Comparison::Equal, Operand::Difference(3));
+		assert_eq!(search.results(&cpu)[0].address, 0x0040);
+		assert_eq!(search.len(), 1);
+	}
+
+	#[test]
+	fn test_peek() {
+		let mut cpu = machine();
+		cpu.write(0x0123, 0x45);
+		cpu.write(0x7FFF, 0x67);
+		cpu.bus.cheats.add("0123:00", "").unwrap();
+		assert_eq!(peek(&cpu, 0x0923), Some(0x45));
+		assert_eq!(peek(&cpu, 0x7FFF), Some(0x67));
+		assert_eq!(peek(&cpu, 0x8000), None);
+		assert_eq!(peek(&Cpu::new(), 0x6000), None);
+
+		// 2KB of PRG-RAM shows up four times, like it does for the CPU.
+		cpu.bus.cartridge.as_mut().unwrap().prg_ram = vec![0; 0x0800];
+		cpu.write(0x7812, 0x89);
+		assert_eq!(peek(&cpu, 0x6012), Some(0x89));
+		assert_eq!(peek(&cpu, 0x7812), Some(cpu.peek(0x7812)));
+		let mut search = RamSearch::new(&cpu, DataType::U8);
+		assert_eq!(search.len(), 0x0800 + 0x0800);
+		search.filter(&cpu, Comparison::Equal, Operand::Value(0x89));
+		assert_eq!(search.results(&cpu)[0].address, 0x6012);
+
+		cpu.bus.cartridge.as_mut().unwrap().prg_ram.clear();
+		assert_eq!(peek(&cpu, 0x6000), None);
+	}
+}
@@ -0,0 +1,103 @@
+use std::io::{self, Write};
+
+use super::{peek, DataType};
+use super::super::cpu::Cpu;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Watch {
+	pub address: u16,
+	pub data_type: DataType,
+	pub name: String,
+}
+
+impl Watch {
+	pub fn new(address: u16, data_type: DataType, name: &str) -> Self {
+		Watch { address, data_type, name: name.to_string() }
+	}
+
+	// None when the address isn't in RAM or PRG-RAM.
+	pub fn value(&self, cpu: &Cpu) -> Option<i32> {
+		let bytes = (0..self.data_type.size() as u16)
+			.map(|i| peek(cpu, self.address.wrapping_add(i)))
+			.collect::<Option<Vec<u8>>>()?;
+		Some(self.data_type.decode(&bytes))
+	}
+}
+
+// Writes the watched values out as CSV, one row per frame. The header row names
+// the columns after the watches, or their addresses for unnamed ones.
+pub struct WatchList<W: Write> {
+	pub watches: Vec<Watch>,
+	out: W,
+	frame: u64,
+}
+
+impl<W: Write> WatchList<W> {
+	pub fn new(out: W, watches: Vec<Watch>) -> io::Result<Self> {
+		let mut list = WatchList { watches, out, frame: 0 };
+		let mut header = String::from("frame");
+		for watch in list.watches.iter() {
+			header.push(',');
+			match watch.name.is_empty() {
+				true => header.push_str(&format!("${:04X}", watch.address)),
+				false => header.push_str(&csv_field(&watch.name)),
+			}
+		}
+		writeln!(list.out, "{}", header)?;
+		Ok(list)
+	}
+
+	// Call once after every emulated frame. Addresses outside RAM and PRG-RAM are
+	// left empty.
+	pub fn log_frame(&mut self, cpu: &Cpu) -> io::Result<()> {
+		let mut row = self.frame.to_string();
+		for watch in self.watches.iter() {
+			row.push(',');
+			if let Some(value) = watch.value(cpu) {
+				row.push_str(&value.to_string());
+			}
+		}
+		self.frame += 1;
+		writeln!(self.out, "{}", row)
+	}
+
+	pub fn into_inner(self) -> W {
+		self.out
+	}
+}
+
+fn csv_field(text: &str) -> String {
+	match text.contains([',', '"', '\n']) {
+		true => format!("\"{}\"", text.replace('"', "\"\"")),
+		false => text.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cpu::Memory;
+
+	#[test]
+	fn test_csv_log() {
+		let mut cpu = Cpu::new();
+		let watches = vec![
+			Watch::new(0x0075, DataType::U8, "lives"),
+			Watch::new(0x0086, DataType::I16, "x, \"fine\""),
+			Watch::new(0x0200, DataType::U8, ""),
+			Watch::new(0x6000, DataType::U8, "wram"),
+		];
+		let mut log = WatchList::new(Vec::new(), watches).unwrap();
+		for frame in 0..3 {
+			cpu.write(0x0075, 3 - frame);
+			cpu.write_u16(0x0086, (frame as u16).wrapping_sub(1));
+			log.log_frame(&cpu).unwrap();
+		}
+		assert_eq!(String::from_utf8(log.into_inner()).unwrap(), "\
+frame,lives,\"x, \"\"fine\"\"\",$0200,wram
+0,3,-1,0,
+1,2,0,0,
+2,1,1,0,
+");
+	}
+}
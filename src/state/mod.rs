@@ -0,0 +1,181 @@
+use std::fmt;
+
+// Every save state starts with the magic and a format version. The rest is a list
+// of chunks, one per component, each a four byte tag followed by a little endian
+// u32 length and the body. All multi-byte values are little endian regardless of host.
+pub const MAGIC: &[u8; 8] = b"NES-RS\x1A\x00";
+pub const VERSION: u16 = 6;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StateError {
+	BadMagic,
+	UnsupportedVersion { found: u16, supported: u16 },
+	Truncated,
+	MissingChunk { expected: [u8; 4], found: [u8; 4] },
+	Invalid { field: &'static str, value: u64 },
+	TrailingData { chunk: [u8; 4] },
+}
+
+impl fmt::Display for StateError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StateError::BadMagic => write!(f, "not a save state"),
+			StateError::UnsupportedVersion { found, supported } => {
+				write!(f, "save state version {} is not supported, expected version {}", found, supported)
+			}
+			StateError::Truncated => write!(f, "save state is truncated"),
+			StateError::MissingChunk { expected, found } => {
+				write!(f, "expected chunk {} but found {}", tag_name(expected), tag_name(found))
+			}
+			StateError::Invalid { field, value } => {
+				write!(f, "invalid value {} for {}", value, field)
+			}
+			StateError::TrailingData { chunk } => {
+				write!(f, "unexpected data at the end of chunk {}", tag_name(chunk))
+			}
+		}
+	}
+}
+
+impl std::error::Error for StateError {}
+
+fn tag_name(tag: &[u8; 4]) -> String {
+	String::from_utf8_lossy(tag).trim_end().to_string()
+}
+
+// Implemented by every component that holds machine state.
+pub trait Snapshot {
+	fn save(&self, writer: &mut StateWriter);
+	fn load(&mut self, reader: &mut StateReader) -> Result<(), StateError>;
+}
+
+pub struct StateWriter {
+	data: Vec<u8>,
+}
+
+impl StateWriter {
+	pub fn new() -> Self {
+		let mut writer = StateWriter { data: Vec::new() };
+		writer.data.extend_from_slice(MAGIC);
+		writer.write_u16(VERSION);
+		writer
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		self.data
+	}
+
+	// Writes a component as its own chunk, patching in the length once it is known.
+	pub fn chunk(&mut self, tag: &[u8; 4], component: &dyn Snapshot) {
+		self.data.extend_from_slice(tag);
+		let start = self.data.len();
+		self.write_u32(0);
+		component.save(self);
+		let len = (self.data.len() - start - 4) as u32;
+		self.data[start..start + 4].copy_from_slice(&len.to_le_bytes());
+	}
+
+	pub fn write_u8(&mut self, value: u8) {
+		self.data.push(value);
+	}
+
+	pub fn write_bool(&mut self, value: bool) {
+		self.write_u8(value as u8);
+	}
+
+	pub fn write_u16(&mut self, value: u16) {
+		self.data.extend_from_slice(&value.to_le_bytes());
+	}
+
+	pub fn write_u32(&mut self, value: u32) {
+		self.data.extend_from_slice(&value.to_le_bytes());
+	}
+
+	pub fn write_u64(&mut self, value: u64) {
+		self.data.extend_from_slice(&value.to_le_bytes());
+	}
+
+	pub fn write_bytes(&mut self, bytes: &[u8]) {
+		self.data.extend_from_slice(bytes);
+	}
+}
+
+impl Default for StateWriter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+pub struct StateReader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+	// Checks the magic and version, leaving the reader at the first chunk.
+	pub fn new(data: &'a [u8]) -> Result<Self, StateError> {
+		let mut reader = StateReader { data };
+		if reader.read_bytes(MAGIC.len()).map_err(|_| StateError::BadMagic)? != MAGIC {
+			return Err(StateError::BadMagic);
+		}
+		let version = reader.read_u16()?;
+		if version != VERSION {
+			return Err(StateError::UnsupportedVersion { found: version, supported: VERSION });
+		}
+		Ok(reader)
+	}
+
+	// Loads the next chunk into a component, which has to consume all of it.
+	pub fn chunk(&mut self, tag: &[u8; 4], component: &mut dyn Snapshot) -> Result<(), StateError> {
+		let found: [u8; 4] = self.read_bytes(4)?.try_into().unwrap();
+		if &found != tag {
+			return Err(StateError::MissingChunk { expected: *tag, found });
+		}
+		let len = self.read_u32()? as usize;
+		let mut body = StateReader { data: self.read_bytes(len)? };
+		component.load(&mut body)?;
+		if !body.data.is_empty() {
+			return Err(StateError::TrailingData { chunk: *tag });
+		}
+		Ok(())
+	}
+
+	pub fn finish(self) -> Result<(), StateError> {
+		match self.data.len() {
+			0 => Ok(()),
+			_ => Err(StateError::Invalid { field: "state length", value: self.data.len() as u64 }),
+		}
+	}
+
+	pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+		if self.data.len() < len {
+			return Err(StateError::Truncated);
+		}
+		let (bytes, rest) = self.data.split_at(len);
+		self.data = rest;
+		Ok(bytes)
+	}
+
+	pub fn read_u8(&mut self) -> Result<u8, StateError> {
+		Ok(self.read_bytes(1)?[0])
+	}
+
+	pub fn read_bool(&mut self, field: &'static str) -> Result<bool, StateError> {
+		match self.read_u8()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			value => Err(StateError::Invalid { field, value: value as u64 }),
+		}
+	}
+
+	pub fn read_u16(&mut self) -> Result<u16, StateError> {
+		Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+	}
+
+	pub fn read_u32(&mut self) -> Result<u32, StateError> {
+		Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+	}
+
+	pub fn read_u64(&mut self) -> Result<u64, StateError> {
+		Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+	}
+}
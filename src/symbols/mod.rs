@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::bus::Bus;
+
+const INES_HEADER_LEN: usize = 16;
+// FCEUX numbers the .nl files of PRG banks in 16KB units.
+const NL_BANK_SIZE: usize = 0x4000;
+const PRG_RAM_START: u16 = 0x6000;
+const PRG_RAM_SIZE: usize = 0x2000;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SymbolError {
+	Syntax { line: usize, text: String },
+}
+
+impl fmt::Display for SymbolError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SymbolError::Syntax { line, text } => write!(f, "line {}: cannot parse \"{}\"", line, text),
+		}
+	}
+}
+
+impl std::error::Error for SymbolError {}
+
+// Where a label points. PRG-ROM labels are kept by their offset into the ROM, so a
+// label in a switchable bank only shows up while that bank is mapped in.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Location {
+	Cpu(u16),
+	Prg(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Symbol {
+	pub name: String,
+	pub comment: String,
+	// Bytes covered, so addresses inside a table show up as table+n.
+	pub size: u16,
+}
+
+#[derive(Clone, Default)]
+pub struct SymbolTable {
+	symbols: BTreeMap<Location, Symbol>,
+}
+
+impl SymbolTable {
+	pub fn new() -> Self {
+		SymbolTable { symbols: BTreeMap::new() }
+	}
+
+	pub fn len(&self) -> usize {
+		self.symbols.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.symbols.is_empty()
+	}
+
+	// Later definitions replace earlier ones at the same location.
+	pub fn insert(&mut self, location: Location, name: &str, comment: &str, size: u16) {
+		self.symbols.insert(location, Symbol {
+			name: name.to_string(),
+			comment: comment.to_string(),
+			size: size.max(1),
+		});
+	}
+
+	// The label at exactly this address, as currently mapped.
+	pub fn get(&self, bus: &Bus, address: u16) -> Option<&Symbol> {
+		prg_location(bus, address)
+			.and_then(|location| self.symbols.get(&location))
+			.or_else(|| self.symbols.get(&Location::Cpu(address)))
+	}
+
+	// The label covering the address, with the offset into it.
+	pub fn find(&self, bus: &Bus, address: u16) -> Option<(&Symbol, u16)> {
+		let containing = |location: Location| {
+			let (start, symbol) = self.symbols.range(..=location).next_back()?;
+			let offset = match (start, location) {
+				(Location::Cpu(start), Location::Cpu(address)) => address as usize - *start as usize,
+				(Location::Prg(start), Location::Prg(offset)) => offset - start,
+				_ => return None,
+			};
+			(offset < symbol.size as usize).then_some((symbol, offset as u16))
+		};
+		prg_location(bus, address).and_then(containing).or_else(|| containing(Location::Cpu(address)))
+	}
+
+	// Renders an address as label, label+n or a plain hex number.
+	pub fn name(&self, bus: &Bus, address: u16, digits: usize) -> String {
+		match self.find(bus, address) {
+			Some((symbol, 0)) => symbol.name.clone(),
+			Some((symbol, offset)) => format!("{}+{}", symbol.name, offset),
+			None => format!("${:0digits$X}", address, digits = digits),
+		}
+	}
+
+	// The CPU address a label stands for. PRG-ROM labels resolve through the
+	// cartridge as currently mapped, to the highest mirror if there are several.
+	pub fn resolve(&self, bus: &Bus, name: &str) -> Option<u16> {
+		let (location, _) = self.symbols.iter().find(|(_, symbol)| symbol.name == name)?;
+		match *location {
+			Location::Cpu(address) => Some(address),
+			Location::Prg(offset) => (0x8000..=0xFFFFu16).rev().find(|&address| prg_location(bus, address) == Some(Location::Prg(offset))),
+		}
+	}
+
+	// Picks the format from the file name: .dbg, .nl or .mlb.
+	pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+		let text = fs::read_to_string(path)?;
+		let name = path.file_name().map(|name| name.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+		let result = match path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase()).as_deref() {
+			Some("dbg") => self.load_ca65(&text),
+			Some("nl") => self.load_nl(&text, nl_bank(&name)),
+			Some("mlb") => self.load_mlb(&text),
+			_ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown symbol file {}", name))),
+		};
+		result.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+	}
+
+	// ld65 debug files, from --dbgfile. Segments written to the ROM place their
+	// labels in PRG-ROM, everything else is RAM or I/O. Returns the number of labels.
+	pub fn load_ca65(&mut self, text: &str) -> Result<usize, SymbolError> {
+		// Segment id to its start address and PRG-ROM offset.
+		let mut segments: HashMap<String, (usize, Option<usize>)> = HashMap::new();
+		let mut symbols = Vec::new();
+		for (number, line) in text.lines().enumerate() {
+			let syntax = || SymbolError::Syntax { line: number + 1, text: line.to_string() };
+			let (kind, fields) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+			let fields = dbg_fields(fields);
+			let number = |key: &str| fields.get(key).and_then(|value| parse_dbg_number(value));
+			match kind {
+				"seg" => {
+					let id = fields.get("id").ok_or_else(syntax)?;
+					let start = number("start").ok_or_else(syntax)?;
+					let offset = number("ooffs").and_then(|ooffs| ooffs.checked_sub(INES_HEADER_LEN));
+					segments.insert(id.clone(), (start, offset));
+				}
+				// Equates and imports aren't addresses, only labels are.
+				"sym" if fields.get("type").map(String::as_str) == Some("lab") => {
+					let name = fields.get("name").ok_or_else(syntax)?;
+					let value = number("val").ok_or_else(syntax)?;
+					let size = number("size").unwrap_or(1);
+					symbols.push((name.clone(), value, size, fields.get("seg").cloned()));
+				}
+				_ => {}
+			}
+		}
+
+		let count = symbols.len();
+		for (name, value, size, segment) in symbols {
+			let location = match segment.and_then(|id| segments.get(&id)) {
+				Some(&(start, Some(offset))) if value >= start => Location::Prg(offset + value - start),
+				_ => Location::Cpu(value as u16),
+			};
+			self.insert(location, &name, "", size as u16);
+		}
+		Ok(count)
+	}
+
+	// FCEUX name lists, lines like "$C000#Reset#comment" or "$0200/20#buffer#".
+	// game.nes.ram.nl covers everything outside PRG-ROM, game.nes.0.nl and on the
+	// 16KB PRG banks. Bank is None for the RAM file.
+	pub fn load_nl(&mut self, text: &str, bank: Option<usize>) -> Result<usize, SymbolError> {
+		let mut count = 0;
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim_end();
+			if line.is_empty() {
+				continue;
+			}
+			let syntax = || SymbolError::Syntax { line: number + 1, text: line.to_string() };
+			let mut fields = line.splitn(3, '#');
+			let address = fields.next().and_then(|field| field.strip_prefix('$')).ok_or_else(syntax)?;
+			let name = fields.next().ok_or_else(syntax)?;
+			let comment = fields.next().unwrap_or("").trim_end_matches('#');
+			let (address, size) = match address.split_once('/') {
+				Some((address, size)) => (address, u16::from_str_radix(size, 16).map_err(|_| syntax())?),
+				None => (address, 1),
+			};
+			let address = u16::from_str_radix(address, 16).map_err(|_| syntax())?;
+			let location = match bank {
+				Some(bank) => Location::Prg(bank * NL_BANK_SIZE + (address as usize % NL_BANK_SIZE)),
+				None => Location::Cpu(address),
+			};
+			self.insert(location, name, comment, size);
+			count += 1;
+		}
+		Ok(count)
+	}
+
+	// Mesen label files, lines like "P:0010:Reset:comment" with an optional end
+	// address as in "R:0200-02FF:buffer". Both the one letter memory types of Mesen
+	// and the longer names of Mesen 2 are understood.
+	pub fn load_mlb(&mut self, text: &str) -> Result<usize, SymbolError> {
+		let mut count = 0;
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim_end();
+			if line.is_empty() {
+				continue;
+			}
+			let syntax = || SymbolError::Syntax { line: number + 1, text: line.to_string() };
+			let fields: Vec<&str> = line.splitn(4, ':').collect();
+			if fields.len() < 3 {
+				return Err(syntax());
+			}
+			let (start, end) = fields[1].split_once('-').unwrap_or((fields[1], fields[1]));
+			let start = usize::from_str_radix(start, 16).map_err(|_| syntax())?;
+			let end = usize::from_str_radix(end, 16).map_err(|_| syntax())?;
+			let size = end.checked_sub(start).ok_or_else(syntax)? + 1;
+			let size = u16::try_from(size).map_err(|_| syntax())?;
+			let location = match fields[0] {
+				"P" | "NesPrgRom" => Location::Prg(start),
+				"R" | "NesInternalRam" | "G" | "NesMemory" | "Register" if end <= 0xFFFF => {
+					Location::Cpu(start as u16)
+				}
+				"R" | "NesInternalRam" | "G" | "NesMemory" | "Register" => return Err(syntax()),
+				// Only the first 8KB of save and work RAM shows up at $6000-$7FFF, labels
+				// in banks past that, as with MMC5, have no fixed CPU address.
+				"S" | "W" | "NesSaveRam" | "NesWorkRam" if end < PRG_RAM_SIZE => {
+					Location::Cpu(PRG_RAM_START + start as u16)
+				}
+				"S" | "W" | "NesSaveRam" | "NesWorkRam" => continue,
+				// CHR and other PPU side labels have no CPU address.
+				_ => continue,
+			};
+			// Mesen writes line breaks in comments as \n.
+			let comment = fields.get(3).map_or(String::new(), |comment| comment.replace("\\n", " "));
+			if !fields[2].is_empty() {
+				self.insert(location, fields[2], &comment, size);
+				count += 1;
+			}
+		}
+		Ok(count)
+	}
+}
+
+fn prg_location(bus: &Bus, address: u16) -> Option<Location> {
+	bus.cartridge.as_ref()?.prg_offset(address).map(Location::Prg)
+}
+
+// The bank of a .nl file, from names like game.nes.1.nl. The number is in hex.
+fn nl_bank(name: &str) -> Option<usize> {
+	let stem = name.strip_suffix(".nl")?;
+	let (_, bank) = stem.rsplit_once('.')?;
+	usize::from_str_radix(bank, 16).ok()
+}
+
+// Splits key=value,key="value" lists, keeping commas inside quotes.
+fn dbg_fields(text: &str) -> HashMap<String, String> {
+	let mut fields = HashMap::new();
+	let mut rest = text.trim();
+	while !rest.is_empty() {
+		let (key, value) = rest.split_once('=').unwrap_or((rest, ""));
+		let (value, next) = match value.strip_prefix('"') {
+			Some(quoted) => {
+				let end = quoted.find('"').unwrap_or(quoted.len());
+				(&quoted[..end], quoted[end..].trim_start_matches('"'))
+			}
+			None => value.split_at(value.find(',').unwrap_or(value.len())),
+		};
+		fields.insert(key.trim().to_string(), value.to_string());
+		rest = next.trim_start_matches(',');
+	}
+	fields
+}
+
+fn parse_dbg_number(text: &str) -> Option<usize> {
+	match text.strip_prefix("0x") {
+		Some(hex) => usize::from_str_radix(hex, 16).ok(),
+		None => text.parse().ok(),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::cartridge::{Cartridge, Mirroring};
+
+	fn bus() -> Bus {
+		let mut bus = Bus::new();
+		bus.insert(Cartridge {
+			prg_rom: vec![0; 0x4000],
+			chr_rom: Vec::new(),
+			prg_ram: vec![0; 0x2000],
+			mapper: 0,
+			mirroring: Mirroring::Vertical,
+			battery: true,
+			region: None,
+		});
+		bus
+	}
+
+	#[test]
+	fn test_ca65() {
+		let text = r#"version	major=2,minor=0
+seg	id=0,name="CODE",start=0x00C000,size=0x0100,addrsize=absolute,type=ro,oname="game,1.nes",ooffs=16
+seg	id=1,name="ZEROPAGE",start=0x000000,size=0x0004,addrsize=zeropage,type=rw
+sym	id=0,name="reset",addrsize=absolute,scope=0,def=1,ref=3,val=0xC000,seg=0,type=lab
+sym	id=1,name="table",addrsize=absolute,size=8,scope=0,def=2,val=0xC010,seg=0,type=lab
+sym	id=2,name="player_x",addrsize=zeropage,size=1,scope=0,def=5,val=0x2,seg=1,type=lab
+sym	id=3,name="PPUCTRL",addrsize=absolute,scope=0,def=6,val=0x2000,type=equ
+"#;
+		let mut symbols = SymbolTable::new();
+		assert_eq!(symbols.load_ca65(text), Ok(3));
+		let bus = bus();
+		assert_eq!(symbols.get(&bus, 0xC000).unwrap().name, "reset");
+		// The 16KB ROM is mirrored, so the label shows up at both addresses.
+		assert_eq!(symbols.get(&bus, 0x8000).unwrap().name, "reset");
+		assert_eq!(symbols.name(&bus, 0xC013, 4), "table+3");
+		assert_eq!(symbols.name(&bus, 0xC018, 4), "$C018");
+		assert_eq!(symbols.name(&bus, 0x0002, 2), "player_x");
+		assert_eq!(symbols.name(&bus, 0x2000, 4), "$2000");
+		assert_eq!(symbols.resolve(&bus, "reset"), Some(0xC000));
+		assert_eq!(symbols.resolve(&bus, "player_x"), Some(0x0002));
+		assert_eq!(symbols.resolve(&bus, "missing"), None);
+
+		// Without the cartridge there is nothing for PRG-ROM labels to point at.
+		assert_eq!(symbols.name(&Bus::new(), 0xC000, 4), "$C000");
+	}
+
+	#[test]
+	fn test_fceux_nl() {
+		let mut symbols = SymbolTable::new();
+		assert_eq!(symbols.load_nl("$0010#lives#How many are left\n$0200/10#oam#\n", None), Ok(2));
+		assert_eq!(symbols.load_nl("$C123#nmi#\n", Some(0)), Ok(1));
+		let bus = bus();
+		assert_eq!(symbols.get(&bus, 0x0010).unwrap().comment, "How many are left");
+		assert_eq!(symbols.name(&bus, 0x020F, 4), "oam+15");
+		assert_eq!(symbols.name(&bus, 0xC123, 4), "nmi");
+		assert_eq!(symbols.load_nl("C000#oops#", Some(0)), Err(SymbolError::Syntax { line: 1, text: "C000#oops#".to_string() }));
+
+		assert_eq!(nl_bank("game.nes.ram.nl"), None);
+		assert_eq!(nl_bank("game.nes.1f.nl"), Some(0x1F));
+	}
+
+	#[test]
+	fn test_mesen_mlb() {
+		let text = "P:0000:reset:Entry point\nR:0010:lives\nS:0100-01FF:save_slot\nNesPrgRom:0123:nmi\nC:0000:tiles\n";
+		let mut symbols = SymbolTable::new();
+		assert_eq!(symbols.load_mlb(text), Ok(4));
+		let bus = bus();
+		assert_eq!(symbols.get(&bus, 0xC000).unwrap().comment, "Entry point");
+		assert_eq!(symbols.name(&bus, 0x0010, 2), "lives");
+		assert_eq!(symbols.name(&bus, 0x6180, 4), "save_slot+128");
+		assert_eq!(symbols.resolve(&bus, "nmi"), Some(0xC123));
+		assert!(symbols.load_mlb("P:xyz:oops").is_err());
+
+		// MMC5 sized work RAM has banks the CPU can't see at a fixed address.
+		assert_eq!(symbols.load_mlb("S:A000:banked\nW:1FFF:last"), Ok(1));
+		assert_eq!(symbols.name(&bus, 0x7FFF, 4), "last");
+		assert_eq!(symbols.load_mlb("S:1FF0-2000:straddles"), Ok(0));
+		assert!(symbols.load_mlb("R:FFFF-10000:oops").is_err());
+		assert!(symbols.load_mlb("G:10000:oops").is_err());
+		assert!(symbols.load_mlb("P:0000-10000:oops").is_err());
+	}
+}